@@ -0,0 +1,121 @@
+//! Compare the GPU buffer suballocators (see `gfx::BufAlloc`) with:
+//!
+//! ```sh
+//! cargo bench --bench alloc
+//! ```
+//!
+//! Mesh sizes are log-uniform between a quad and a 64k vertex prop, in
+//! bytes of `Vtx` and `u32` indices, roughly what a level loads.
+
+use std::{hint::black_box, time::Instant};
+
+use qd::{
+    gfx::Vtx,
+    math::Rng,
+    mem::{MetaAlloc, MetaAllocStats, MetaAllocator, TlsfAllocator},
+};
+
+const BUF_SIZE: usize = 64 * 1024 * 1024;
+const CHURN_OPS: usize = 1_000_000;
+
+trait SubAlloc {
+    fn alloc(&mut self, size: usize) -> Option<MetaAlloc>;
+    fn free(&mut self, alloc: MetaAlloc);
+    fn stats(&self) -> MetaAllocStats;
+}
+
+macro_rules! sub_alloc_impl {
+    ($ty:ty) => {
+        impl SubAlloc for $ty {
+            #[inline]
+            fn alloc(&mut self, size: usize) -> Option<MetaAlloc> {
+                <$ty>::alloc(self, size)
+            }
+
+            #[inline]
+            fn free(&mut self, alloc: MetaAlloc) {
+                <$ty>::free(self, alloc)
+            }
+
+            #[inline]
+            fn stats(&self) -> MetaAllocStats {
+                <$ty>::stats(self)
+            }
+        }
+    };
+}
+
+sub_alloc_impl!(MetaAllocator);
+sub_alloc_impl!(TlsfAllocator);
+
+fn mesh_size(rng: &mut Rng, elem_size: usize) -> usize {
+    let count = 4.0 * 2.0f32.powf(rng.range_f32(0.0..14.0));
+    count as usize * elem_size
+}
+
+fn bench<A: SubAlloc>(name: &str, mut alloc: A, elem_size: usize) {
+    let mut rng = Rng::new(0x5eed);
+
+    // fill: load meshes until the buffer is out of contiguous space
+    let mut live = Vec::new();
+    let mut requested = 0;
+    let start = Instant::now();
+    loop {
+        let size = mesh_size(&mut rng, elem_size);
+        let Some(range) = alloc.alloc(size) else {
+            break;
+        };
+        requested += size;
+        live.push((size, range));
+    }
+    let loaded = live.len();
+    let fill_nanos = start.elapsed().as_secs_f64() * 1.0e9 / (loaded as f64);
+    let stats = alloc.stats();
+    let waste = 1.0 - (requested as f64 / stats.used as f64);
+    let filled = requested as f64 / stats.size as f64;
+
+    // churn: stream meshes in and out at around half occupancy
+    while requested > BUF_SIZE / 2 {
+        let (size, range) = live.swap_remove(rng.next_u32() as usize % live.len());
+        requested -= size;
+        alloc.free(range);
+    }
+    let mut failures = 0;
+    let start = Instant::now();
+    for _ in 0..CHURN_OPS {
+        if rng.next_bool() && !live.is_empty() {
+            let (size, range) = live.swap_remove(rng.next_u32() as usize % live.len());
+            requested -= size;
+            alloc.free(black_box(range));
+        } else {
+            let size = mesh_size(&mut rng, elem_size);
+            match alloc.alloc(black_box(size)) {
+                Some(range) => {
+                    requested += size;
+                    live.push((size, range));
+                }
+                None => failures += 1,
+            }
+        }
+    }
+    let churn_nanos = start.elapsed().as_secs_f64() * 1.0e9 / (CHURN_OPS as f64);
+    let churn = alloc.stats();
+
+    println!(
+        "{name:<12} fill {loaded:>5} meshes {:>5.1}% full {:>5.1}% waste {fill_nanos:>7.1} ns/alloc | \
+         churn {churn_nanos:>6.1} ns/op {:>5.1}% full {failures:>6} failed {:>5.1}% fragmented",
+        filled * 100.0,
+        waste * 100.0,
+        requested as f64 / churn.size as f64 * 100.0,
+        churn.fragmentation * 100.0,
+    );
+}
+
+fn main() {
+    let vtx_size = std::mem::size_of::<Vtx>();
+    let idx_size = std::mem::size_of::<u32>();
+    bench("buddy vtx", MetaAllocator::new(BUF_SIZE, 512), vtx_size);
+    bench("tlsf vtx", TlsfAllocator::new(BUF_SIZE, 16), vtx_size);
+    bench("buddy idx", MetaAllocator::new(BUF_SIZE, 512), idx_size);
+    bench("tlsf idx", TlsfAllocator::new(BUF_SIZE, 16), idx_size);
+}
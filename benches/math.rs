@@ -0,0 +1,48 @@
+//! Compare scalar and SIMD math with:
+//!
+//! ```sh
+//! cargo bench --bench math
+//! cargo bench --bench math --features simd
+//! ```
+
+use std::{hint::black_box, time::Instant};
+
+use qd::math::{Dot, Mat4, Quat, V3, V4, Xform3};
+
+const ITERS: u32 = 10_000_000;
+
+fn bench<T, F: FnMut() -> T>(name: &str, mut func: F) {
+    // warm up
+    for _ in 0..(ITERS / 10) {
+        black_box(func());
+    }
+    let start = Instant::now();
+    for _ in 0..ITERS {
+        black_box(func());
+    }
+    let nanos = start.elapsed().as_secs_f64() * 1.0e9 / (ITERS as f64);
+    println!("{name:<24} {nanos:>8.2} ns/iter");
+}
+
+fn main() {
+    let a = V4([1.0, 2.0, 3.0, 4.0]);
+    let b = V4([0.5, -1.0, 2.0, 0.25]);
+    let q = Quat::from_axis_angle(V3([0.2, 1.0, -0.3]), 1.3);
+    let r = Quat::from_axis_angle(V3([-0.7, 0.1, 0.4]), -0.4);
+    let xform = Xform3 {
+        pos: V3([1.0, 2.0, 3.0]),
+        scale: V3([2.0, 3.0, 4.0]),
+        rot: q,
+    };
+    let m = Mat4::from(xform);
+    let n = Mat4::from(r);
+
+    bench("V4 + V4", || black_box(a) + black_box(b));
+    bench("V4 * f32", || black_box(a) * black_box(2.0));
+    bench("V4 . V4", || black_box(a).dot(black_box(b)));
+    bench("Quat * Quat", || black_box(q) * black_box(r));
+    bench("Mat4 * V4", || black_box(m) * black_box(a));
+    bench("Mat4 * Mat4", || black_box(m) * black_box(n));
+    bench("Mat4::inverse", || black_box(m).inverse());
+    bench("Mat4::from(Xform3)", || Mat4::from(black_box(xform)));
+}
@@ -1,8 +1,10 @@
 use std::time::{Duration, Instant};
 
 use qd::{
-    gfx::{Camera, Drawable, Gfx, PassSettings, Proj, Settings, Target, Vtx},
-    math::{UV2, V3, V4, Xform3},
+    gfx::{
+        Budgets, BufAlloc, Camera, Drawable, Gfx, Growth, PassSettings, Proj, Settings, Target, Vtx,
+    },
+    math::{Color, Rgba8, Rng, UV2, V3, V4, Xform3},
     scene::{Node, Scene},
 };
 use sdl2::{
@@ -20,6 +22,7 @@ fn main() {
     let gl_attr = video.gl_attr();
     gl_attr.set_context_version(4, 1);
     gl_attr.set_context_profile(GLProfile::Core);
+    gl_attr.set_framebuffer_srgb_compatible(true);
 
     let win = qd::ensure!(
         video
@@ -44,30 +47,48 @@ fn main() {
         idx_buffer_size: 1024 * 1024 * 16,
         tex_dim: 256,
         tex_count: 512,
+
+        reverse_z: false,
+        srgb: true,
+
+        vtx_buffer_alloc: BufAlloc::Tlsf,
+        idx_buffer_alloc: BufAlloc::Tlsf,
+
+        growth: Growth::Factor(2.0),
+        vtx_buffer_max: 1024 * 1024 * 64,
+        idx_buffer_max: 1024 * 1024 * 256,
+        tex_count_max: 2048,
+        mesh_count_max: 65536,
+        store_count: 512,
+        store_count_max: 8192,
+
+        budgets: Budgets {
+            vtx: Some(256 * 1024 * 1024),
+            ..Default::default()
+        },
     });
 
     let mesh = gfx.mesh_alloc(4, 6);
-    {
-        let (mut vmap, mut imap) = gfx.mesh_map(mesh);
+    if let Some((mut vmap, mut imap)) = gfx.mesh_map(mesh) {
         vmap.write(&[
             Vtx {
                 pos: V3([0.0, 0.0, 0.0]),
-                color: V4::splat(1.0),
+                color: Color::WHITE,
                 ..Default::default()
             },
             Vtx {
                 pos: V3([0.0, 32.0, 0.0]),
-                color: V4::splat(1.0),
+                color: Color::WHITE,
                 ..Default::default()
             },
             Vtx {
                 pos: V3([32.0, 0.0, 0.0]),
-                color: V4::splat(1.0),
+                color: Color::WHITE,
                 ..Default::default()
             },
             Vtx {
                 pos: V3([32.0, 32.0, 0.0]),
-                color: V4::splat(1.0),
+                color: Color::WHITE,
                 ..Default::default()
             },
         ]);
@@ -75,9 +96,8 @@ fn main() {
     }
 
     let tex = gfx.tex_alloc();
-    {
-        let mut tmap = gfx.tex_map(tex);
-        tmap.write(&vec![0xFFFF00FF; 256 * 266]);
+    if let Some(mut tmap) = gfx.tex_map(tex) {
+        tmap.write(&vec![Rgba8::from(Color::MAGENTA); 256 * 256]);
     }
 
     let mut events = qd::ensure!(sdl.event_pump());
@@ -112,7 +132,7 @@ fn main() {
             draw: Drawable::Mesh {
                 hnd: mesh,
                 tex,
-                blend: V4::splat(((N - i) as f32) / (N as f32)),
+                blend: Color(V4::splat(((N - i) as f32) / (N as f32))),
             },
         });
     }
@@ -127,6 +147,8 @@ fn main() {
         },
     };
 
+    let mut rng = Rng::new(0);
+
     'mainloop: loop {
         for event in events.poll_iter() {
             match event {
@@ -138,7 +160,7 @@ fn main() {
         }
 
         for node in scene.active_mut() {
-            node.local.pos.0[1] += unsafe { sdl2::libc::rand() % 4 } as f32;
+            node.local.pos.0[1] += rng.range_u32(0..4) as f32;
             if node.local.pos.0[1] > 1080.0 {
                 node.local.pos.0[1] = -32.0;
             }
@@ -164,6 +186,7 @@ fn main() {
         if delta > Duration::from_secs(5) {
             last = now;
             log::debug!("fps: {}", frames / delta.as_secs_f32());
+            gfx.log_memory_report();
             frames = 0.0;
         }
     }
@@ -0,0 +1,398 @@
+//! Compact little-endian binary encoding for persisting scenes and configs.
+//!
+//! A stream is a fixed header (`MAGIC` followed by the `u32` format
+//! `VERSION`) and the encoded value. Fixed-size math types are written
+//! through their `Pod` representation one 4-byte word at a time, so the
+//! output is identical on big-endian hosts.
+
+use std::{fmt, slice};
+
+use bytemuck::Pod;
+
+use crate::{
+    math::{
+        Aabb, Color, IV2, IV3, IV4, Mat2, Mat3, Mat4, Plane, Quat, Ray, Rgba8, Sphere, Triangle,
+        UV2, UV3, UV4, V2, V3, V4, Xform3,
+    },
+    mem::Handle,
+};
+
+pub const MAGIC: [u8; 4] = *b"QDBN";
+pub const VERSION: u32 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The stream does not start with `MAGIC`.
+    BadMagic,
+    /// The stream was written by a newer format version.
+    Version(u32),
+    /// The stream ended before the value did.
+    Truncated,
+    /// Bytes were left over after the value.
+    Trailing(usize),
+    /// A field held a value that is out of range for its type.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "not a qd binary stream"),
+            DecodeError::Version(version) => {
+                write!(f, "unsupported version {} (max {})", version, VERSION)
+            }
+            DecodeError::Truncated => write!(f, "unexpected end of stream"),
+            DecodeError::Trailing(len) => write!(f, "{} trailing bytes", len),
+            DecodeError::Invalid(what) => write!(f, "invalid {}", what),
+        }
+    }
+}
+
+pub trait Encode {
+    fn encode(&self, w: &mut Writer);
+}
+
+pub trait Decode: Sized {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError>;
+}
+
+/// Encode `value` into a new buffer, header included.
+pub fn to_bytes<T: Encode + ?Sized>(value: &T) -> Vec<u8> {
+    let mut w = Writer { buf: Vec::new() };
+    w.bytes(&MAGIC);
+    w.u32(VERSION);
+    value.encode(&mut w);
+    w.buf
+}
+
+/// Decode a value written by `to_bytes`. The whole buffer must be consumed.
+pub fn from_bytes<T: Decode>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let mut r = Reader { bytes, version: 0 };
+    if r.bytes(MAGIC.len())? != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    r.version = r.u32()?;
+    if r.version == 0 || r.version > VERSION {
+        return Err(DecodeError::Version(r.version));
+    }
+    let value = T::decode(&mut r)?;
+    if !r.bytes.is_empty() {
+        return Err(DecodeError::Trailing(r.bytes.len()));
+    }
+    Ok(value)
+}
+
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    #[inline]
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    #[inline]
+    pub fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    #[inline]
+    pub fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    /// Write a `Pod` value made up entirely of 4-byte scalars.
+    #[inline]
+    pub fn words<T: Pod>(&mut self, value: &T) {
+        for word in bytemuck::cast_slice::<T, u32>(slice::from_ref(value)) {
+            self.u32(*word);
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    version: u32,
+}
+
+impl<'a> Reader<'a> {
+    /// Format version of the stream being read.
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.bytes.len() < len {
+            return Err(DecodeError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    #[inline]
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    #[inline]
+    pub fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    #[inline]
+    pub fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    #[inline]
+    pub fn bool(&mut self) -> Result<bool, DecodeError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Invalid("bool")),
+        }
+    }
+
+    /// Read a `Pod` value made up entirely of 4-byte scalars.
+    #[inline]
+    pub fn words<T: Pod>(&mut self) -> Result<T, DecodeError> {
+        let mut value = T::zeroed();
+        for word in bytemuck::cast_slice_mut::<T, u32>(slice::from_mut(&mut value)) {
+            *word = self.u32()?;
+        }
+        Ok(value)
+    }
+
+    /// Read a `u64` length that must fit in a `usize`.
+    #[inline]
+    pub fn length(&mut self) -> Result<usize, DecodeError> {
+        usize::try_from(self.u64()?).map_err(|_| DecodeError::Invalid("length"))
+    }
+}
+
+macro_rules! words_codec_impl {
+    ($($ty:ident),+) => {
+        $(
+            impl Encode for $ty {
+                #[inline]
+                fn encode(&self, w: &mut Writer) {
+                    w.words(self);
+                }
+            }
+
+            impl Decode for $ty {
+                #[inline]
+                fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+                    r.words()
+                }
+            }
+        )+
+    };
+}
+
+words_codec_impl!(
+    V2, V3, V4, IV2, IV3, IV4, UV2, UV3, UV4, Mat2, Mat3, Mat4, Color
+);
+
+/// Encode a struct field by field, in declaration order.
+macro_rules! struct_codec_impl {
+    ($ty:ident { $($field:ident),+ }) => {
+        impl Encode for $ty {
+            #[inline]
+            fn encode(&self, w: &mut Writer) {
+                $(self.$field.encode(w);)+
+            }
+        }
+
+        impl Decode for $ty {
+            #[inline]
+            fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+                Ok(Self {
+                    $($field: Decode::decode(r)?,)+
+                })
+            }
+        }
+    };
+}
+
+pub(crate) use struct_codec_impl;
+
+struct_codec_impl!(Xform3 { pos, scale, rot });
+struct_codec_impl!(Aabb { min, max });
+struct_codec_impl!(Sphere { center, radius });
+struct_codec_impl!(Plane { normal, dist });
+struct_codec_impl!(Ray { origin, dir });
+
+impl Encode for Quat {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        self.0.encode(w);
+    }
+}
+
+impl Decode for Quat {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Self(V4::decode(r)?))
+    }
+}
+
+impl Encode for Triangle {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        self.0.iter().for_each(|v| v.encode(w));
+    }
+}
+
+impl Decode for Triangle {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Self([V3::decode(r)?, V3::decode(r)?, V3::decode(r)?]))
+    }
+}
+
+impl Encode for Rgba8 {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        w.bytes(&self.0);
+    }
+}
+
+impl Decode for Rgba8 {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Self(r.bytes(4)?.try_into().unwrap()))
+    }
+}
+
+impl<T> Encode for Handle<T> {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        w.u32(self.idx());
+        w.u32(self.generation());
+    }
+}
+
+impl<T> Decode for Handle<T> {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Handle::new(r.u32()?, r.u32()?))
+    }
+}
+
+impl Encode for f32 {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        w.u32(self.to_bits());
+    }
+}
+
+impl Decode for f32 {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(f32::from_bits(r.u32()?))
+    }
+}
+
+impl Encode for u32 {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        w.u32(*self);
+    }
+}
+
+impl Decode for u32 {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        r.u32()
+    }
+}
+
+impl Encode for usize {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        w.u64(*self as u64);
+    }
+}
+
+impl Decode for usize {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        r.length()
+    }
+}
+
+impl Encode for bool {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        w.bool(*self);
+    }
+}
+
+impl Decode for bool {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        r.bool()
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, w: &mut Writer) {
+        self.len().encode(w);
+        self.iter().for_each(|item| item.encode(w));
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        self.as_slice().encode(w);
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let len = r.length()?;
+        // don't trust the length for the allocation, it may be corrupt
+        let mut items = Vec::with_capacity(len.min(r.bytes.len()));
+        for _ in 0..len {
+            items.push(T::decode(r)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        self.is_some().encode(w);
+        if let Some(value) = self {
+            value.encode(w);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        if r.bool()? {
+            Ok(Some(T::decode(r)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
@@ -4,6 +4,7 @@ uniform sampler2DArray tbo;
 
 flat in uint tex;
 in vec2 tex_coord;
+in vec3 vtx_norm;
 in vec4 vtx_color;
 
 out vec4 color;
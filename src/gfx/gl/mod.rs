@@ -1,18 +1,25 @@
-use std::{cmp::Ordering, marker::PhantomData, mem, ops::Range, ptr};
+use std::{cmp::Ordering, marker::PhantomData, mem, ops::Range, ptr, sync::Arc};
 
 use bytemuck::{NoUninit, Pod, Zeroable};
+use crossbeam_channel::Receiver;
 use gl::types::{GLenum, GLint, GLintptr, GLsizei, GLsizeiptr, GLuint};
 
 use crate::{
-    math::{Cross, Dot, IV2, Mat4, V3, V4, Xform3},
-    mem::{BitMap, Handles, MetaAlloc, MetaAllocator},
+    math::{IV2, Mat3, Mat4, Rgba8, V4, Xform3},
+    mem::{
+        ArenaVec, BitMap, FrameArena, Handle, MetaAlloc, MetaAllocStats, MetaAllocator,
+        SecondaryMap, SharedHandles, SlotMap, TlsfAllocator,
+    },
 };
 
-use super::{Drawable, PassSettings, Settings, Vtx};
+use super::{
+    BufAlloc, Drawable, Growth, MemoryReport, Mesh, MeshHnd, PassSettings, PoolReport, Settings,
+    Tex, TexHnd, Upload, Uploader, Vtx,
+};
 
 const SBO_INST_SIZE: usize = mem::size_of::<MeshInst>() / mem::size_of::<V4>();
-const SBO_SIZE: usize = 512;
 const SBO_DIM: usize = 128 * SBO_INST_SIZE;
+const ARENA_SIZE: usize = 256 * 1024;
 
 pub struct Gl {
     vbo: Buf<Vtx>,
@@ -28,38 +35,76 @@ pub struct Gl {
     usbo: GLint,
     ustore: GLint,
 
-    meshes: Handles<(u32, u32)>,
-    mesh_batches: Vec<MeshBatch>,
+    reverse_z: bool,
+
+    meshes: SecondaryMap<Mesh, (Handle<MetaAlloc>, Handle<MetaAlloc>)>,
+    textures: SecondaryMap<Tex, u32>, // layer of each texture
+    uploader: Uploader,
+    uploads: Receiver<Upload>,
+    arena: FrameArena,
 }
 
 impl Gl {
     pub fn new(settings: &Settings) -> Self {
         log::trace!("Initializing Gfx...");
 
-        let vbo = Buf::new(gl::ARRAY_BUFFER, settings.vtx_buffer_size);
-        let vbo_size = (settings.vtx_buffer_size * mem::size_of::<Vtx>()).next_power_of_two();
-        log::debug!("VBO: {} MiB", vbo_size / 1024 / 1024);
+        let vbo = Buf::new(
+            gl::ARRAY_BUFFER,
+            settings.vtx_buffer_size,
+            settings.vtx_buffer_max,
+            settings.vtx_buffer_alloc,
+            settings.growth,
+            Usage::new("Vertex buffer", settings.budgets.vtx),
+        );
+        log::debug!(
+            "VBO: {} MiB ({:?})",
+            vbo.inner.size / 1024 / 1024,
+            settings.vtx_buffer_alloc
+        );
 
-        let ibo = Buf::new(gl::ELEMENT_ARRAY_BUFFER, settings.idx_buffer_size);
-        let ibo_size = (settings.idx_buffer_size * mem::size_of::<u32>()).next_power_of_two();
-        log::debug!("IBO: {} MiB", ibo_size / 1024 / 1024);
+        let ibo = Buf::new(
+            gl::ELEMENT_ARRAY_BUFFER,
+            settings.idx_buffer_size,
+            settings.idx_buffer_max,
+            settings.idx_buffer_alloc,
+            settings.growth,
+            Usage::new("Index buffer", settings.budgets.idx),
+        );
+        log::debug!(
+            "IBO: {} MiB ({:?})",
+            ibo.inner.size / 1024 / 1024,
+            settings.idx_buffer_alloc
+        );
 
-        let tbo = TexBuf::new(settings.tex_dim, settings.tex_count);
-        let tbo_size =
-            settings.tex_dim * settings.tex_dim * settings.tex_count * mem::size_of::<u32>();
+        let tbo = TexBuf::new(
+            settings.tex_dim,
+            settings.tex_count,
+            settings.tex_count_max,
+            settings.growth,
+            settings.srgb,
+            Usage::new("Textures", settings.budgets.tex),
+        );
+        let tbo_size = tbo.layer_size() * settings.tex_count;
         log::debug!(
             "TBO: {} textures ({} MiB)",
             settings.tex_count,
             tbo_size / 1024 / 1024
         );
 
-        let sbo = StoreBuf::new(SBO_DIM, SBO_SIZE);
+        let sbo = StoreBuf::new(
+            SBO_DIM,
+            settings.store_count,
+            settings.store_count_max,
+            settings.growth,
+            Usage::new("Stores", settings.budgets.store),
+        );
         log::debug!(
-            "SBO: {SBO_SIZE} stores ({} MiB)",
-            (SBO_DIM * mem::size_of::<V4>() * SBO_SIZE) / 1024 / 1024
+            "SBO: {} stores ({} MiB)",
+            settings.store_count,
+            (SBO_DIM * mem::size_of::<V4>() * settings.store_count) / 1024 / 1024
         );
 
-        let vao = create_vao();
+        let vao = create_vao(vbo.inner.hnd, ibo.inner.hnd);
         let shader = compile_and_link_shaders();
 
         let uproj;
@@ -100,8 +145,36 @@ impl Gl {
             gl::Enable(gl::DEPTH_TEST);
             gl::Enable(gl::CULL_FACE);
             gl::CullFace(gl::BACK);
+            if settings.srgb {
+                // shaders work in linear space, encode to sRGB on output
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
+        }
+
+        if settings.reverse_z {
+            if gl::ClipControl::is_loaded() {
+                unsafe {
+                    gl::ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
+                }
+            } else {
+                // still correct, but depth precision is no better than usual
+                log::warn!("glClipControl is unavailable, reverse-Z will not improve precision");
+            }
+            unsafe {
+                gl::DepthFunc(gl::GEQUAL);
+                gl::ClearDepth(0.0);
+            }
         }
 
+        let (tx, uploads) = crossbeam_channel::unbounded();
+        let uploader = Uploader {
+            meshes: Arc::new(SharedHandles::new(settings.mesh_count_max)),
+            texs: Arc::new(SharedHandles::new(
+                settings.tex_count_max.max(settings.tex_count),
+            )),
+            tx,
+        };
+
         log::trace!("Initialized Gfx");
         Self {
             vbo,
@@ -117,70 +190,231 @@ impl Gl {
             usbo,
             ustore,
 
-            meshes: Handles::new(),
-            mesh_batches: Vec::new(),
+            reverse_z: settings.reverse_z,
+
+            meshes: SecondaryMap::new(),
+            textures: SecondaryMap::new(),
+            uploader,
+            uploads,
+            arena: FrameArena::new(ARENA_SIZE),
         }
     }
 
     #[inline]
     pub fn pass<'a>(&'a mut self, settings: PassSettings<'a>) -> Pass<'a> {
-        let view = look_at(settings.camera.pos, settings.camera.at, V3::UP);
+        let view = settings.camera.view();
+        let proj = if self.reverse_z {
+            settings.camera.proj.reverse_z()
+        } else {
+            Mat4::from(settings.camera.proj)
+        };
         unsafe {
-            gl::UniformMatrix4fv(
-                self.uproj,
-                1,
-                gl::FALSE,
-                Mat4::from(settings.camera.proj).0.as_ptr() as _,
-            );
+            gl::UniformMatrix4fv(self.uproj, 1, gl::FALSE, proj.0.as_ptr() as _);
             gl::UniformMatrix4fv(self.uview, 1, gl::FALSE, view.0.as_ptr() as _);
         }
-        Pass { gl: self, settings }
+        self.apply_uploads();
+        // the last pass's batches were drawn when it dropped
+        self.arena.reset();
+        let Self {
+            ref vbo,
+            ref ibo,
+            ref mut sbo,
+            ustore,
+            ref meshes,
+            ref textures,
+            ref uploader,
+            ref arena,
+            ..
+        } = *self;
+        Pass {
+            vbo,
+            ibo,
+            sbo,
+            ustore,
+            meshes,
+            textures,
+            uploader,
+            arena,
+            batches: ArenaVec::new_in(arena),
+            settings,
+        }
     }
 
     #[inline]
-    pub fn mesh_alloc(&mut self, vtxs: usize, idxs: usize) -> u32 {
+    pub fn uploader(&self) -> Uploader {
+        self.uploader.clone()
+    }
+
+    /// Apply the uploads staged since the last pass. Uploads to handles
+    /// freed in the meantime are dropped.
+    fn apply_uploads(&mut self) {
+        while let Ok(upload) = self.uploads.try_recv() {
+            match upload {
+                Upload::Mesh { hnd, vtxs, idxs } => {
+                    if !self.uploader.meshes.is_valid(hnd) {
+                        log::debug!("Dropping upload to freed mesh handle {hnd:?}");
+                        continue;
+                    }
+                    self.mesh_detach(hnd);
+                    self.mesh_attach(hnd, vtxs.len(), idxs.len());
+                    if let Some((mut vmap, mut imap)) = self.mesh_map(hnd) {
+                        vmap.write(&vtxs);
+                        imap.write(&idxs);
+                    }
+                }
+                Upload::Tex { hnd, texels } => {
+                    if !self.uploader.texs.is_valid(hnd) {
+                        log::debug!("Dropping upload to freed texture handle {hnd:?}");
+                        continue;
+                    }
+                    if texels.len() != self.tbo.dim * self.tbo.dim {
+                        log::warn!(
+                            "Dropping upload of {} texels to texture handle {hnd:?}",
+                            texels.len()
+                        );
+                        continue;
+                    }
+                    if !self.textures.contains_key(hnd) {
+                        let layer = self.tbo.alloc();
+                        self.textures.insert(hnd, layer);
+                    }
+                    if let Some(mut map) = self.tex_map(hnd) {
+                        map.write(&texels);
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub fn mesh_alloc(&mut self, vtxs: usize, idxs: usize) -> MeshHnd {
+        let Some(hnd) = self.uploader.meshes.reserve() else {
+            crate::fatal!("Out of mesh handles");
+        };
+        self.mesh_attach(hnd, vtxs, idxs);
+        hnd
+    }
+
+    /// Allocate buffer space for a reserved handle.
+    fn mesh_attach(&mut self, hnd: MeshHnd, vtxs: usize, idxs: usize) {
+        let bufs = (self.vbo.inner.hnd, self.ibo.inner.hnd);
         let vhnd = self.vbo.alloc(vtxs);
         let ihnd = self.ibo.alloc(idxs);
-        self.meshes.track((vhnd, ihnd)) as u32
+        if bufs != (self.vbo.inner.hnd, self.ibo.inner.hnd) {
+            // a buffer grew into a new one, which the VAO doesn't know about
+            bind_vao_buffers(self.vao, self.vbo.inner.hnd, self.ibo.inner.hnd);
+        }
+        self.meshes.insert(hnd, (vhnd, ihnd));
+    }
+
+    /// Free the buffer space of a handle, if it has any.
+    fn mesh_detach(&mut self, hnd: MeshHnd) {
+        if let Some((vhnd, ihnd)) = self.meshes.remove(hnd) {
+            self.vbo.free(vhnd);
+            self.ibo.free(ihnd);
+        }
     }
 
     #[inline]
-    pub fn mesh_free(&mut self, hnd: u32) {
-        let (vhnd, ihnd) = self.meshes.items[hnd as usize];
-        self.vbo.free(vhnd);
-        self.ibo.free(ihnd);
-        self.meshes.untrack(hnd as usize);
+    pub fn mesh_free(&mut self, hnd: MeshHnd) {
+        if !self.uploader.meshes.release(hnd) {
+            log::warn!("Ignoring free of stale mesh handle {hnd:?}");
+            return;
+        }
+        self.mesh_detach(hnd);
     }
 
     #[inline]
-    pub fn mesh_map<'a>(&'a mut self, hnd: u32) -> (BufMap<'a, Vtx>, BufMap<'a, u32>) {
+    pub fn mesh_map<'a>(&'a mut self, hnd: MeshHnd) -> Option<(BufMap<'a, Vtx>, BufMap<'a, u32>)> {
         let &mut Self {
             ref mut vbo,
             ref mut ibo,
             ref meshes,
             ..
         } = self;
-        let (vhnd, ihnd) = meshes.items[hnd as usize];
-        (vbo.map(vhnd), ibo.map(ihnd))
+        let Some(&(vhnd, ihnd)) = meshes.get(hnd) else {
+            log::warn!("Ignoring map of stale or unallocated mesh handle {hnd:?}");
+            return None;
+        };
+        Some((vbo.map(vhnd), ibo.map(ihnd)))
+    }
+
+    #[inline]
+    pub fn vtx_stats(&self) -> MetaAllocStats {
+        self.vbo.inner.stats()
+    }
+
+    #[inline]
+    pub fn idx_stats(&self) -> MetaAllocStats {
+        self.ibo.inner.stats()
+    }
+
+    #[inline]
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport {
+            vtx: self.vbo.inner.report(),
+            idx: self.ibo.inner.report(),
+            tex: self.tbo.report(),
+            store: self.sbo.report(),
+        }
+    }
+
+    /// Returns the bytes reclaimed, as growth of the largest free blocks.
+    pub fn compact(&mut self) -> usize {
+        self.vbo.inner.compact() + self.ibo.inner.compact()
+    }
+
+    #[inline]
+    fn tex_reserve(&self) -> TexHnd {
+        match self.uploader.texs.reserve() {
+            Some(hnd) => hnd,
+            None => crate::fatal!("Out of texture handles"),
+        }
+    }
+
+    #[inline]
+    pub fn tex_alloc(&mut self) -> TexHnd {
+        let hnd = self.tex_reserve();
+        let layer = self.tbo.alloc();
+        self.textures.insert(hnd, layer);
+        hnd
     }
 
     #[inline]
-    pub fn tex_alloc(&mut self) -> u32 {
-        self.tbo.alloc()
+    pub fn tex_alloc_array(&mut self, count: usize) -> Vec<TexHnd> {
+        self.tbo
+            .alloc_array(count)
+            .map(|layer| {
+                let hnd = self.tex_reserve();
+                self.textures.insert(hnd, layer);
+                hnd
+            })
+            .collect()
     }
 
     #[inline]
-    pub fn tex_free(&mut self, hnd: u32) {
-        self.tbo.free(hnd);
+    pub fn tex_free(&mut self, hnd: TexHnd) {
+        if !self.uploader.texs.release(hnd) {
+            log::warn!("Ignoring free of stale texture handle {hnd:?}");
+            return;
+        }
+        if let Some(layer) = self.textures.remove(hnd) {
+            self.tbo.free(layer);
+        }
     }
 
     #[inline]
-    pub fn tex_map<'a>(&'a mut self, hnd: u32) -> TexMap<'a> {
-        log::trace!("Mapping texture handle {hnd}",);
-        TexMap {
+    pub fn tex_map<'a>(&'a mut self, hnd: TexHnd) -> Option<TexMap<'a>> {
+        let Some(&layer) = self.textures.get(hnd) else {
+            log::warn!("Ignoring map of stale or unallocated texture handle {hnd:?}");
+            return None;
+        };
+        log::trace!("Mapping texture handle {hnd:?}");
+        Some(TexMap {
             buf: &mut self.tbo,
             hnd,
-        }
+            layer,
+        })
     }
 }
 
@@ -188,25 +422,36 @@ impl Gl {
 #[derive(Copy, Clone, Pod, Zeroable)]
 struct MeshInst {
     world: Mat4,
+    norm: [V4; 3],
     blend: V4,
     tex: V4,
 }
 
-struct MeshBatch {
-    hnd: u32,
+struct MeshBatch<'a> {
+    hnd: MeshHnd,
     store: u32,
-    insts: Vec<MeshInst>,
+    insts: ArenaVec<'a, MeshInst>,
 }
 
-impl MeshBatch {
+impl<'a> MeshBatch<'a> {
     #[inline]
     fn is_full(&self) -> bool {
         (self.insts.len() * SBO_INST_SIZE) >= SBO_DIM
     }
 }
 
+/// Batches and their instances live in `Gl`'s frame arena, so drawing
+/// doesn't touch the global allocator once the arena has grown to fit.
 pub struct Pass<'a> {
-    gl: &'a mut Gl,
+    vbo: &'a Buf<Vtx>,
+    ibo: &'a Buf<u32>,
+    sbo: &'a mut StoreBuf,
+    ustore: GLint,
+    meshes: &'a SecondaryMap<Mesh, (Handle<MetaAlloc>, Handle<MetaAlloc>)>,
+    textures: &'a SecondaryMap<Tex, u32>,
+    uploader: &'a Uploader,
+    arena: &'a FrameArena,
+    batches: ArenaVec<'a, MeshBatch<'a>>,
     settings: PassSettings<'a>,
 }
 
@@ -220,27 +465,26 @@ impl<'a> Pass<'a> {
     }
 
     #[inline]
-    fn find_mesh_batch(&mut self, hnd: &u32) -> &mut MeshBatch {
-        match self.gl.mesh_batches.binary_search_by(|batch| {
-            if batch.insts.is_empty() {
-                return Ordering::Greater;
-            }
-            if batch.is_full() {
-                return Ordering::Less;
-            }
-            batch.hnd.cmp(hnd)
+    fn find_mesh_batch(&mut self, hnd: MeshHnd) -> &mut MeshBatch<'a> {
+        // full batches sort before the open one for the same mesh
+        match self.batches.binary_search_by(|batch| {
+            batch.hnd.cmp(&hnd).then(if batch.is_full() {
+                Ordering::Less
+            } else {
+                Ordering::Equal
+            })
         }) {
-            Ok(idx) => &mut self.gl.mesh_batches[idx],
+            Ok(idx) => &mut self.batches[idx],
             Err(idx) => {
-                self.gl.mesh_batches.insert(
+                self.batches.insert(
                     idx,
                     MeshBatch {
-                        hnd: *hnd,
-                        store: self.gl.sbo.alloc(),
-                        insts: Vec::new(),
+                        hnd,
+                        store: self.sbo.alloc(),
+                        insts: ArenaVec::new_in(self.arena),
                     },
                 );
-                &mut self.gl.mesh_batches[idx]
+                &mut self.batches[idx]
             }
         }
     }
@@ -253,10 +497,25 @@ impl<'a> Pass<'a> {
             match draw {
                 Drawable::None => {}
                 Drawable::Mesh { hnd, tex, blend } => {
-                    self.find_mesh_batch(hnd).insts.push(MeshInst {
+                    // reserved handles draw nothing until their upload lands
+                    if !self.meshes.contains_key(*hnd) {
+                        if !self.uploader.meshes.is_valid(*hnd) {
+                            log::warn!("Skipping draw of stale mesh handle {hnd:?}");
+                        }
+                        continue;
+                    }
+                    let Some(&layer) = self.textures.get(*tex) else {
+                        if !self.uploader.texs.is_valid(*tex) {
+                            log::warn!("Skipping draw with stale texture handle {tex:?}");
+                        }
+                        continue;
+                    };
+                    let Mat3([a, b, c]) = world.normal_matrix();
+                    self.find_mesh_batch(*hnd).insts.push(MeshInst {
                         world: Mat4::from(world),
-                        blend: *blend,
-                        tex: V4([*tex as f32, 0.0, 0.0, 0.0]),
+                        norm: [a.extended(0.0), b.extended(0.0), c.extended(0.0)],
+                        blend: blend.0,
+                        tex: V4([layer as f32, 0.0, 0.0, 0.0]),
                     });
                 }
             }
@@ -266,51 +525,39 @@ impl<'a> Pass<'a> {
 
 impl<'a> Drop for Pass<'a> {
     fn drop(&mut self) {
-        for batch in &mut self.gl.mesh_batches {
-            if batch.insts.is_empty() {
-                break;
-            }
-            self.gl
-                .sbo
+        for batch in self.batches.iter() {
+            self.sbo
                 .map(batch.store)
                 .write(bytemuck::cast_slice(&batch.insts));
-            let (_, ihnd) = self.gl.meshes.items[batch.hnd as usize];
-            let range = &self.gl.ibo.inner.allocs.items[ihnd as usize].range;
+            // handles were validated by `draw`, and can't be freed during the pass
+            let Some(&(vhnd, ihnd)) = self.meshes.get(batch.hnd) else {
+                crate::fatal!("Mesh handle {:?} was freed during a pass", batch.hnd);
+            };
+            let vrange = self.vbo.inner.alloc_range(vhnd);
+            let range = self.ibo.inner.alloc_range(ihnd);
             let err;
             unsafe {
-                gl::Uniform1ui(self.gl.ustore, batch.store);
+                gl::Uniform1ui(self.ustore, batch.store);
                 gl::DrawElementsInstancedBaseVertex(
                     gl::TRIANGLES,
                     (range.len() / mem::size_of::<u32>()) as GLsizei,
                     gl::UNSIGNED_INT,
                     ptr::without_provenance(range.start),
                     batch.insts.len() as GLsizei,
-                    // we store index values relative to their offset in the index buffer
-                    (range.start / mem::size_of::<u32>()) as GLint,
+                    // index values are relative to the mesh's first vertex
+                    (vrange.start / mem::size_of::<Vtx>()) as GLint,
                 );
                 err = gl::GetError();
             }
             if err != gl::NO_ERROR {
                 crate::fatal!("Failed to draw batch: {err:X}");
             }
-            batch.insts.clear();
+            // GL orders the upload for the next pass after this draw
+            self.sbo.free(batch.store);
         }
     }
 }
 
-fn look_at(pos: V3, at: V3, up: V3) -> Mat4 {
-    let forward = (at - pos).normalized();
-    let backward = -forward;
-    let right = forward.cross(up).normalized();
-    let up = right.cross(forward);
-    Mat4([
-        V4([right.0[0], up.0[0], backward.0[0], 0.0]),
-        V4([right.0[1], up.0[1], backward.0[1], 0.0]),
-        V4([right.0[2], up.0[2], backward.0[2], 0.0]),
-        V4([-right.dot(pos), -up.dot(pos), forward.dot(pos), 1.0]),
-    ])
-}
-
 struct Buf<T> {
     inner: RawBuf,
     _marker: PhantomData<T>,
@@ -318,25 +565,40 @@ struct Buf<T> {
 
 impl<T> Buf<T> {
     #[inline]
-    fn new(target: GLenum, size: usize) -> Self {
+    fn new(
+        target: GLenum,
+        size: usize,
+        max: usize,
+        alloc: BufAlloc,
+        growth: Growth,
+        usage: Usage,
+    ) -> Self {
         Self {
-            inner: RawBuf::new(target, size * mem::size_of::<T>()),
+            inner: RawBuf::new(
+                target,
+                size * mem::size_of::<T>(),
+                max * mem::size_of::<T>(),
+                alloc,
+                growth,
+                usage,
+            ),
             _marker: PhantomData,
         }
     }
 
     #[inline]
-    fn alloc(&mut self, size: usize) -> u32 {
-        self.inner.alloc(size * mem::size_of::<T>())
+    fn alloc(&mut self, size: usize) -> Handle<MetaAlloc> {
+        self.inner
+            .alloc(size * mem::size_of::<T>(), mem::size_of::<T>())
     }
 
     #[inline]
-    fn free(&mut self, hnd: u32) {
+    fn free(&mut self, hnd: Handle<MetaAlloc>) {
         self.inner.free(hnd);
     }
 
     #[inline]
-    fn map<'a>(&'a mut self, hnd: u32) -> BufMap<'a, T> {
+    fn map<'a>(&'a mut self, hnd: Handle<MetaAlloc>) -> BufMap<'a, T> {
         BufMap {
             inner: self.inner.map(hnd),
             _marker: PhantomData,
@@ -344,58 +606,372 @@ impl<T> Buf<T> {
     }
 }
 
+enum SubAllocator {
+    Buddy(MetaAllocator),
+    Tlsf(TlsfAllocator),
+}
+
+impl SubAllocator {
+    #[inline]
+    fn new(kind: BufAlloc, size: usize) -> Self {
+        match kind {
+            BufAlloc::Buddy => SubAllocator::Buddy(MetaAllocator::new(size, 512)),
+            BufAlloc::Tlsf => SubAllocator::Tlsf(TlsfAllocator::new(size, 16)),
+        }
+    }
+
+    /// Allocations always start at a multiple of this.
+    #[inline]
+    fn granularity(&self) -> usize {
+        match self {
+            SubAllocator::Buddy(_) => 512,
+            SubAllocator::Tlsf(_) => 16,
+        }
+    }
+
+    #[inline]
+    fn alloc(&mut self, size: usize) -> Option<MetaAlloc> {
+        match self {
+            SubAllocator::Buddy(alloc) => alloc.alloc(size),
+            SubAllocator::Tlsf(alloc) => alloc.alloc(size),
+        }
+    }
+
+    #[inline]
+    fn free(&mut self, block: MetaAlloc) {
+        match self {
+            SubAllocator::Buddy(alloc) => alloc.free(block),
+            SubAllocator::Tlsf(alloc) => alloc.free(block),
+        }
+    }
+
+    #[inline]
+    fn stats(&self) -> MetaAllocStats {
+        match self {
+            SubAllocator::Buddy(alloc) => alloc.stats(),
+            SubAllocator::Tlsf(alloc) => alloc.stats(),
+        }
+    }
+}
+
+/// Bytes a pool has handed out, with the high-water mark and a soft budget.
+struct Usage {
+    name: &'static str,
+    used: usize,
+    peak: usize,
+    budget: Option<usize>,
+}
+
+impl Usage {
+    #[inline]
+    fn new(name: &'static str, budget: Option<usize>) -> Self {
+        Self {
+            name,
+            used: 0,
+            peak: 0,
+            budget,
+        }
+    }
+
+    #[inline]
+    fn add(&mut self, bytes: usize) {
+        self.used += bytes;
+        if self.used <= self.peak {
+            return;
+        }
+        // only the first crossing warns, pools that churn around their
+        // budget would otherwise warn every pass
+        if let Some(budget) = self.budget
+            && self.peak <= budget
+            && self.used > budget
+        {
+            log::warn!(
+                "{} went over budget: {} of {} KiB",
+                self.name,
+                self.used / 1024,
+                budget / 1024
+            );
+        }
+        self.peak = self.used;
+    }
+
+    #[inline]
+    fn sub(&mut self, bytes: usize) {
+        self.used -= bytes;
+    }
+}
+
+/// A block from the suballocator, and the data inside it. The data starts at
+/// a multiple of the element size, so that it can be addressed by index.
+struct Suballoc {
+    block: MetaAlloc,
+    range: Range<usize>,
+    align: usize,
+}
+
 struct RawBuf {
     hnd: GLuint,
     target: GLenum,
-    alloc: MetaAllocator,
-    allocs: Handles<MetaAlloc>,
+    size: usize,
+    max: usize,
+    growth: Growth,
+    kind: BufAlloc,
+    alloc: SubAllocator,
+    allocs: SlotMap<MetaAlloc, Suballoc>,
+    usage: Usage,
 }
 
 impl RawBuf {
-    fn new(target: GLenum, size: usize) -> Self {
-        let mut hnd = 0;
-        let mut err;
-        unsafe {
-            gl::GenBuffers(1, &mut hnd);
-            err = gl::GetError();
+    fn new(
+        target: GLenum,
+        size: usize,
+        max: usize,
+        kind: BufAlloc,
+        growth: Growth,
+        usage: Usage,
+    ) -> Self {
+        let size = match kind {
+            // the buddy allocator can hand out anything up to the next power of two
+            BufAlloc::Buddy => size.next_power_of_two(),
+            BufAlloc::Tlsf => size,
+        };
+        let alloc = SubAllocator::new(kind, size);
+        let hnd = create_buf(target, size);
+        Self {
+            hnd,
+            target,
+            size,
+            max: max.max(size),
+            growth,
+            kind,
+            alloc,
+            allocs: SlotMap::new(),
+            usage,
         }
-        if err != gl::NO_ERROR {
-            crate::fatal!("Failed to name buffer: {err:X}");
+    }
+
+    #[inline]
+    fn stats(&self) -> MetaAllocStats {
+        self.alloc.stats()
+    }
+
+    fn report(&self) -> PoolReport {
+        PoolReport {
+            capacity: self.size,
+            used: self.usage.used,
+            allocs: self.allocs.len(),
+            peak: self.usage.peak,
+            largest_free: self.alloc.stats().largest_free,
         }
+    }
+
+    fn alloc(&mut self, size: usize, align: usize) -> Handle<MetaAlloc> {
+        // blocks start on the granularity, anything coarser needs padding
+        let padding = if self.alloc.granularity().is_multiple_of(align) {
+            0
+        } else {
+            align - 1
+        };
+        loop {
+            if let Some(block) = self.alloc.alloc(size + padding) {
+                self.usage.add(block.range.len());
+                let start = block.range.start.next_multiple_of(align);
+                return self.allocs.insert(Suballoc {
+                    block,
+                    range: start..(start + size),
+                    align,
+                });
+            }
+            if !self.grow(size + padding) {
+                crate::fatal!("Out of contiguous buffer space");
+            }
+        }
+    }
+
+    /// Move everything into a larger buffer with room for `needed` more
+    /// bytes. Handles stay valid, but the GL buffer changes. Returns `false`
+    /// if the buffer can't grow that far.
+    fn grow(&mut self, needed: usize) -> bool {
+        let size = match self.kind {
+            // packed largest first, a buddy buffer of at least the old size
+            // plus the request's block has a free block that fits it
+            BufAlloc::Buddy => {
+                let max = 1 << self.max.ilog2();
+                match self
+                    .growth
+                    .next_size(self.size, needed.next_power_of_two(), max)
+                {
+                    Some(size) => size.next_power_of_two().min(max),
+                    None => return false,
+                }
+            }
+            BufAlloc::Tlsf => match self.growth.next_size(self.size, needed, self.max) {
+                Some(size) => size,
+                None => return false,
+            },
+        };
+        let Some(moves) = self.repack(size) else {
+            crate::fatal!("Failed to plan buffer growth to {size} bytes");
+        };
+
+        let hnd = create_buf(gl::COPY_WRITE_BUFFER, size);
+        let err;
         unsafe {
-            gl::BindBuffer(target, hnd);
-            gl::BufferData(target, size as GLsizeiptr, ptr::null(), gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::COPY_READ_BUFFER, self.hnd);
+            for (range, start) in &moves {
+                gl::CopyBufferSubData(
+                    gl::COPY_READ_BUFFER,
+                    gl::COPY_WRITE_BUFFER,
+                    range.start as GLintptr,
+                    *start as GLintptr,
+                    range.len() as GLsizeiptr,
+                );
+            }
+            gl::DeleteBuffers(1, &self.hnd);
             err = gl::GetError();
         }
         if err != gl::NO_ERROR {
-            crate::fatal!("Failed to allocate buffer: {err:X}");
-        }
-        Self {
-            hnd,
-            target,
-            alloc: MetaAllocator::new(size, 512),
-            allocs: Handles::new(),
+            crate::fatal!("Failed to grow buffer: {err:X}");
         }
+        log::warn!(
+            "{} grew from {} to {} KiB",
+            self.usage.name,
+            self.size / 1024,
+            size / 1024
+        );
+        self.hnd = hnd;
+        self.size = size;
+        true
     }
 
     #[inline]
-    fn alloc(&mut self, size: usize) -> u32 {
-        if let Some(alloc) = self.alloc.alloc(size) {
-            return self.allocs.track(alloc) as u32;
+    fn free(&mut self, hnd: Handle<MetaAlloc>) {
+        match self.allocs.remove(hnd) {
+            Some(suballoc) => {
+                self.usage.sub(suballoc.block.range.len());
+                self.alloc.free(suballoc.block);
+            }
+            None => log::warn!("Ignoring free of stale buffer handle {hnd:?}"),
         }
-        crate::fatal!("Out of contiguous buffer space");
     }
 
+    /// Byte range of a live allocation's data. Handles are owned by `Gl`, so
+    /// a stale one is a bug.
     #[inline]
-    fn free(&mut self, hnd: u32) {
-        self.allocs.untrack(hnd as usize);
+    fn alloc_range(&self, hnd: Handle<MetaAlloc>) -> Range<usize> {
+        match self.allocs.get(hnd) {
+            Some(suballoc) => suballoc.range.clone(),
+            None => crate::fatal!("Stale buffer handle {hnd:?}"),
+        }
+    }
+
+    /// Lay the live allocations out in a fresh allocator of `size` bytes.
+    /// Returns each allocation's old data range and new start, or `None`
+    /// (leaving everything as is) if they don't fit.
+    fn repack(&mut self, size: usize) -> Option<Vec<(Range<usize>, usize)>> {
+        // buddy blocks pack without holes largest first, TLSF blocks in
+        // address order
+        let mut order: Vec<_> = self
+            .allocs
+            .iter()
+            .map(|(hnd, suballoc)| (hnd, suballoc.block.range.clone()))
+            .collect();
+        match self.kind {
+            BufAlloc::Buddy => {
+                order.sort_by_key(|(_, block)| (usize::MAX - block.len(), block.start))
+            }
+            BufAlloc::Tlsf => order.sort_by_key(|(_, block)| block.start),
+        }
+        let mut alloc = SubAllocator::new(self.kind, size);
+        let mut plan = Vec::with_capacity(order.len());
+        for (hnd, block) in order {
+            plan.push((hnd, alloc.alloc(block.len())?));
+        }
+        self.alloc = alloc;
+
+        let mut moves = Vec::with_capacity(plan.len());
+        for (hnd, block) in plan {
+            let suballoc = self.allocs.get_mut(hnd).unwrap();
+            // the block is as large as the old one, so the data still fits
+            let start = block.range.start.next_multiple_of(suballoc.align);
+            moves.push((suballoc.range.clone(), start));
+            suballoc.range = start..(start + suballoc.range.len());
+            suballoc.block = block;
+        }
+        Some(moves)
+    }
+
+    /// Repack the live allocations and move their data on the GPU, through a
+    /// staging buffer so that moves can't overlap. Handles stay valid.
+    /// Returns how much the largest free block grew by.
+    fn compact(&mut self) -> usize {
+        let before = self.alloc.stats().largest_free;
+        let Some(mut moves) = self.repack(self.size) else {
+            log::warn!("Failed to plan buffer compaction, leaving the buffer as is");
+            return 0;
+        };
+        moves.retain(|(range, start)| range.start != *start);
+
+        let staged: usize = moves.iter().map(|(range, _)| range.len()).sum();
+        if staged > 0 {
+            let mut staging = 0;
+            let err;
+            unsafe {
+                gl::GenBuffers(1, &mut staging);
+                gl::BindBuffer(gl::COPY_WRITE_BUFFER, staging);
+                gl::BufferData(
+                    gl::COPY_WRITE_BUFFER,
+                    staged as GLsizeiptr,
+                    ptr::null(),
+                    gl::STREAM_COPY,
+                );
+                gl::BindBuffer(gl::COPY_READ_BUFFER, self.hnd);
+                let mut offset = 0;
+                for (range, _) in &moves {
+                    gl::CopyBufferSubData(
+                        gl::COPY_READ_BUFFER,
+                        gl::COPY_WRITE_BUFFER,
+                        range.start as GLintptr,
+                        offset as GLintptr,
+                        range.len() as GLsizeiptr,
+                    );
+                    offset += range.len();
+                }
+                gl::BindBuffer(gl::COPY_READ_BUFFER, staging);
+                gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.hnd);
+                let mut offset = 0;
+                for (range, start) in &moves {
+                    gl::CopyBufferSubData(
+                        gl::COPY_READ_BUFFER,
+                        gl::COPY_WRITE_BUFFER,
+                        offset as GLintptr,
+                        *start as GLintptr,
+                        range.len() as GLsizeiptr,
+                    );
+                    offset += range.len();
+                }
+                gl::DeleteBuffers(1, &staging);
+                err = gl::GetError();
+            }
+            if err != gl::NO_ERROR {
+                crate::fatal!("Failed to compact buffer: {err:X}");
+            }
+        }
+
+        let reclaimed = self.alloc.stats().largest_free.saturating_sub(before);
+        log::debug!(
+            "Compacted buffer {}: moved {} allocations ({staged} bytes), {reclaimed} bytes reclaimed",
+            self.hnd,
+            moves.len()
+        );
+        reclaimed
     }
 
     #[inline]
-    fn map<'a>(&'a mut self, hnd: u32) -> RawMap<'a> {
-        let range = self.allocs.items[hnd as usize].range.clone();
+    fn map<'a>(&'a mut self, hnd: Handle<MetaAlloc>) -> RawMap<'a> {
+        let range = self.alloc_range(hnd);
         log::trace!(
-            "Mapping buffer handle {hnd} ({}:{})",
+            "Mapping buffer handle {hnd:?} ({}:{})",
             range.start,
             range.len()
         );
@@ -423,7 +999,7 @@ impl Drop for RawBuf {
 
 struct RawMap<'a> {
     buf: &'a mut RawBuf,
-    hnd: u32,
+    hnd: Handle<MetaAlloc>,
     range: Range<usize>,
 }
 
@@ -435,14 +1011,14 @@ impl<'a> RawMap<'a> {
             gl::BufferSubData(
                 self.buf.target,
                 self.range.start as GLintptr,
-                self.range.len() as GLsizeiptr,
+                data.len().min(self.range.len()) as GLsizeiptr,
                 data.as_ptr() as _,
             );
             err = gl::GetError();
         }
         if err != gl::NO_ERROR {
             crate::fatal!(
-                "Failed to transfer buffer handle {} ({}:{}) to buffer: {err:X}",
+                "Failed to transfer buffer handle {:?} ({}:{}) to buffer: {err:X}",
                 self.hnd,
                 self.range.start,
                 self.range.len()
@@ -455,7 +1031,7 @@ impl<'a> Drop for RawMap<'a> {
     #[inline]
     fn drop(&mut self) {
         log::trace!(
-            "Unmapping buffer handle {} ({}:{})",
+            "Unmapping buffer handle {:?} ({}:{})",
             self.hnd,
             self.range.start,
             self.range.len()
@@ -481,80 +1057,129 @@ impl<'a, T> BufMap<'a, T> {
 struct TexBuf {
     hnd: GLuint,
     dim: usize,
+    max: usize,
+    growth: Growth,
+    srgb: bool,
     alloc: BitMap,
+    usage: Usage,
 }
 
 impl TexBuf {
-    fn new(dim: usize, size: usize) -> Self {
-        let mut hnd = 0;
-        let mut err;
-        unsafe {
-            gl::GenTextures(1, &mut hnd);
-            err = gl::GetError();
+    fn new(dim: usize, size: usize, max: usize, growth: Growth, srgb: bool, usage: Usage) -> Self {
+        Self {
+            hnd: create_tex(dim, size, srgb),
+            dim,
+            max: max.max(size),
+            growth,
+            srgb,
+            alloc: BitMap::new(size),
+            usage,
         }
-        if err != gl::NO_ERROR {
-            crate::fatal!("Failed to name texture: {err:X}");
+    }
+
+    #[inline]
+    fn layer_size(&self) -> usize {
+        self.dim * self.dim * mem::size_of::<Rgba8>()
+    }
+
+    fn report(&self) -> PoolReport {
+        PoolReport {
+            capacity: self.alloc.len() * self.layer_size(),
+            used: self.usage.used,
+            allocs: self.alloc.count_ones(),
+            peak: self.usage.peak,
+            largest_free: self.alloc.longest_zero_run() * self.layer_size(),
         }
-        unsafe {
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D_ARRAY, hnd);
-            gl::TexStorage3D(
-                gl::TEXTURE_2D_ARRAY,
-                1,
-                gl::RGBA8,
-                dim as GLsizei,
-                dim as GLsizei,
-                size as GLsizei,
-            );
-            err = gl::GetError();
+    }
+
+    #[inline]
+    fn alloc(&mut self) -> u32 {
+        loop {
+            if let Some(layer) = self.alloc.set_any() {
+                self.usage.add(self.layer_size());
+                return layer as u32;
+            }
+            if !self.grow(1) {
+                crate::fatal!("Out of texture space");
+            }
         }
-        if err != gl::NO_ERROR {
-            crate::fatal!("Failed to allocate texture: {err:X}");
+    }
+
+    #[inline]
+    fn alloc_array(&mut self, count: usize) -> Range<u32> {
+        loop {
+            if let Some(range) = self.alloc.set_any_range(count) {
+                self.usage.add(count * self.layer_size());
+                return (range.start as u32)..(range.end as u32);
+            }
+            if !self.grow(count) {
+                crate::fatal!("Out of contiguous texture space for {count} layers");
+            }
         }
+    }
+
+    /// Copy the used layers into a texture with room for `needed` more.
+    /// Returns `false` if the texture can't grow that far.
+    fn grow(&mut self, needed: usize) -> bool {
+        let size = self.alloc.len();
+        let max = self.max.min(max_array_layers());
+        let Some(new_size) = self.growth.next_size(size, needed, max) else {
+            return false;
+        };
+        let hnd = create_tex(self.dim, new_size, self.srgb);
+        let mut fbo = 0;
+        let err;
         unsafe {
-            gl::TexParameteri(
-                gl::TEXTURE_2D_ARRAY,
-                gl::TEXTURE_MIN_FILTER,
-                gl::LINEAR as GLint,
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D_ARRAY,
-                gl::TEXTURE_MAG_FILTER,
-                gl::LINEAR as GLint,
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D_ARRAY,
-                gl::TEXTURE_WRAP_S,
-                gl::CLAMP_TO_EDGE as GLint,
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D_ARRAY,
-                gl::TEXTURE_WRAP_T,
-                gl::CLAMP_TO_EDGE as GLint,
-            );
+            let srgb_output = gl::IsEnabled(gl::FRAMEBUFFER_SRGB) == gl::TRUE;
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            // copy the encoded bytes as they are, without a round trip
+            // through linear
+            gl::Disable(gl::FRAMEBUFFER_SRGB);
+            for layer in self.alloc.iter_ones() {
+                gl::FramebufferTextureLayer(
+                    gl::READ_FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    self.hnd,
+                    0,
+                    layer as GLint,
+                );
+                gl::CopyTexSubImage3D(
+                    gl::TEXTURE_2D_ARRAY,
+                    0,
+                    0,
+                    0,
+                    layer as GLint,
+                    0,
+                    0,
+                    self.dim as GLsizei,
+                    self.dim as GLsizei,
+                );
+            }
+            if srgb_output {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::DeleteFramebuffers(1, &fbo);
+            gl::DeleteTextures(1, &self.hnd);
             err = gl::GetError();
         }
         if err != gl::NO_ERROR {
-            crate::fatal!("Failed to set texture parameters: {err:X}");
-        }
-        Self {
-            hnd,
-            dim,
-            alloc: BitMap::new(size),
+            crate::fatal!("Failed to grow texture: {err:X}");
         }
+        log::warn!("{} grew from {size} to {new_size} layers", self.usage.name);
+        self.hnd = hnd;
+        self.alloc.grow(new_size);
+        true
     }
 
     #[inline]
-    fn alloc(&mut self) -> u32 {
-        if let Some(hnd) = self.alloc.set_any() {
-            return hnd as u32;
+    fn free(&mut self, layer: u32) {
+        if !self.alloc.unset(layer as usize) {
+            crate::fatal!("Texture layer {layer} was freed twice");
         }
-        crate::fatal!("Out of texture space");
-    }
-
-    #[inline]
-    fn free(&mut self, hnd: u32) {
-        self.alloc.unset(hnd as usize);
+        self.usage.sub(self.layer_size());
     }
 }
 
@@ -574,11 +1199,22 @@ impl Drop for TexBuf {
 
 pub struct TexMap<'a> {
     buf: &'a mut TexBuf,
-    hnd: u32,
+    hnd: TexHnd,
+    layer: u32,
 }
 
 impl<'a> TexMap<'a> {
-    pub fn write(&mut self, data: &[u32]) {
+    /// `data` must cover the whole square layer, anything else is rejected.
+    pub fn write(&mut self, data: &[Rgba8]) {
+        let texels = self.buf.dim * self.buf.dim;
+        if data.len() != texels {
+            log::warn!(
+                "Rejecting {} texels for texture handle {:?}, expected {texels}",
+                data.len(),
+                self.hnd
+            );
+            return;
+        }
         let err;
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0);
@@ -587,7 +1223,7 @@ impl<'a> TexMap<'a> {
                 0,
                 0,
                 0,
-                self.hnd as GLint,
+                self.layer as GLint,
                 self.buf.dim as GLsizei,
                 self.buf.dim as GLsizei,
                 1,
@@ -599,7 +1235,7 @@ impl<'a> TexMap<'a> {
         }
         if err != gl::NO_ERROR {
             crate::fatal!(
-                "Failed to transfer texture handle {} to texture: {err:X}",
+                "Failed to transfer texture handle {:?} to texture: {err:X}",
                 self.hnd
             );
         }
@@ -609,58 +1245,90 @@ impl<'a> TexMap<'a> {
 impl<'a> Drop for TexMap<'a> {
     #[inline]
     fn drop(&mut self) {
-        log::trace!("Unmapping texture handle {}", self.hnd);
+        log::trace!("Unmapping texture handle {:?}", self.hnd);
     }
 }
 
 struct StoreBuf {
     hnd: GLuint,
+    dim: usize,
+    max: usize,
+    growth: Growth,
     alloc: BitMap,
+    usage: Usage,
 }
 
 impl StoreBuf {
-    fn new(dim: usize, size: usize) -> Self {
-        let mut hnd = 0;
-        let mut err;
-        unsafe {
-            gl::GenTextures(1, &mut hnd);
-            err = gl::GetError();
-        }
-        if err != gl::NO_ERROR {
-            crate::fatal!("Failed to name storage: {err:X}");
-        }
-        unsafe {
-            gl::ActiveTexture(gl::TEXTURE1);
-            gl::BindTexture(gl::TEXTURE_1D_ARRAY, hnd);
-            gl::TexStorage2D(
-                gl::TEXTURE_1D_ARRAY,
-                1,
-                gl::RGBA32F, // 1 `V4` per texel
-                dim as GLsizei,
-                size as GLsizei,
-            );
-            err = gl::GetError();
-        }
-        if err != gl::NO_ERROR {
-            crate::fatal!("Failed to allocate storage: {err:X}");
-        }
+    fn new(dim: usize, size: usize, max: usize, growth: Growth, usage: Usage) -> Self {
         Self {
-            hnd,
+            hnd: create_store(dim, size),
+            dim,
+            max: max.max(size),
+            growth,
             alloc: BitMap::new(size),
+            usage,
+        }
+    }
+
+    #[inline]
+    fn store_size(&self) -> usize {
+        self.dim * mem::size_of::<V4>()
+    }
+
+    fn report(&self) -> PoolReport {
+        PoolReport {
+            capacity: self.alloc.len() * self.store_size(),
+            used: self.usage.used,
+            allocs: self.alloc.count_ones(),
+            peak: self.usage.peak,
+            largest_free: self.alloc.longest_zero_run() * self.store_size(),
         }
     }
 
     #[inline]
     fn alloc(&mut self) -> u32 {
-        if let Some(hnd) = self.alloc.set_any() {
-            return hnd as u32;
+        loop {
+            if let Some(hnd) = self.alloc.set_any() {
+                self.usage.add(self.store_size());
+                return hnd as u32;
+            }
+            if !self.grow() {
+                crate::fatal!("Out of storage space");
+            }
         }
-        crate::fatal!("Out of storage space");
+    }
+
+    /// Replace the storage with a larger one. Stores are only written once a
+    /// pass has allocated all of its stores, so there is nothing to copy.
+    /// Returns `false` if the storage can't grow.
+    fn grow(&mut self) -> bool {
+        let size = self.alloc.len();
+        let max = self.max.min(max_array_layers());
+        let Some(new_size) = self.growth.next_size(size, 1, max) else {
+            return false;
+        };
+        let hnd = create_store(self.dim, new_size);
+        let err;
+        unsafe {
+            gl::DeleteTextures(1, &self.hnd);
+            err = gl::GetError();
+        }
+        if err != gl::NO_ERROR {
+            crate::fatal!("Failed to free storage: {err:X}");
+        }
+        log::warn!("{} grew from {size} to {new_size} stores", self.usage.name);
+        self.hnd = hnd;
+        self.alloc.grow(new_size);
+        true
     }
 
     #[inline]
     fn free(&mut self, hnd: u32) {
-        self.alloc.unset(hnd as usize);
+        if self.alloc.unset(hnd as usize) {
+            self.usage.sub(self.store_size());
+        } else {
+            log::warn!("Ignoring double free of store {hnd}");
+        }
     }
 
     #[inline]
@@ -715,9 +1383,129 @@ impl<'a> StoreMap<'a> {
     }
 }
 
-fn create_vao() -> GLuint {
+/// Name a buffer and allocate `size` bytes for it, leaving it bound to
+/// `target`.
+fn create_buf(target: GLenum, size: usize) -> GLuint {
+    let mut hnd = 0;
+    let mut err;
+    unsafe {
+        gl::GenBuffers(1, &mut hnd);
+        err = gl::GetError();
+    }
+    if err != gl::NO_ERROR {
+        crate::fatal!("Failed to name buffer: {err:X}");
+    }
+    unsafe {
+        gl::BindBuffer(target, hnd);
+        gl::BufferData(target, size as GLsizeiptr, ptr::null(), gl::DYNAMIC_DRAW);
+        err = gl::GetError();
+    }
+    if err != gl::NO_ERROR {
+        crate::fatal!("Failed to allocate buffer: {err:X}");
+    }
+    hnd
+}
+
+/// Name a texture array of `size` layers and allocate it, leaving it bound
+/// to `TEXTURE0`.
+fn create_tex(dim: usize, size: usize, srgb: bool) -> GLuint {
     let mut hnd = 0;
     let mut err;
+    unsafe {
+        gl::GenTextures(1, &mut hnd);
+        err = gl::GetError();
+    }
+    if err != gl::NO_ERROR {
+        crate::fatal!("Failed to name texture: {err:X}");
+    }
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, hnd);
+        gl::TexStorage3D(
+            gl::TEXTURE_2D_ARRAY,
+            1,
+            // sRGB sampling decodes to linear, matching `Color`
+            if srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 },
+            dim as GLsizei,
+            dim as GLsizei,
+            size as GLsizei,
+        );
+        err = gl::GetError();
+    }
+    if err != gl::NO_ERROR {
+        crate::fatal!("Failed to allocate texture: {err:X}");
+    }
+    unsafe {
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_MIN_FILTER,
+            gl::LINEAR as GLint,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_MAG_FILTER,
+            gl::LINEAR as GLint,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_WRAP_S,
+            gl::CLAMP_TO_EDGE as GLint,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_WRAP_T,
+            gl::CLAMP_TO_EDGE as GLint,
+        );
+        err = gl::GetError();
+    }
+    if err != gl::NO_ERROR {
+        crate::fatal!("Failed to set texture parameters: {err:X}");
+    }
+    hnd
+}
+
+/// Name an instance storage of `size` stores and allocate it, leaving it
+/// bound to `TEXTURE1`.
+fn create_store(dim: usize, size: usize) -> GLuint {
+    let mut hnd = 0;
+    let mut err;
+    unsafe {
+        gl::GenTextures(1, &mut hnd);
+        err = gl::GetError();
+    }
+    if err != gl::NO_ERROR {
+        crate::fatal!("Failed to name storage: {err:X}");
+    }
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE1);
+        gl::BindTexture(gl::TEXTURE_1D_ARRAY, hnd);
+        gl::TexStorage2D(
+            gl::TEXTURE_1D_ARRAY,
+            1,
+            gl::RGBA32F, // 1 `V4` per texel
+            dim as GLsizei,
+            size as GLsizei,
+        );
+        err = gl::GetError();
+    }
+    if err != gl::NO_ERROR {
+        crate::fatal!("Failed to allocate storage: {err:X}");
+    }
+    hnd
+}
+
+/// Layers the implementation supports in an array texture.
+fn max_array_layers() -> usize {
+    let mut layers = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAX_ARRAY_TEXTURE_LAYERS, &mut layers);
+    }
+    layers as usize
+}
+
+fn create_vao(vbo: GLuint, ibo: GLuint) -> GLuint {
+    let mut hnd = 0;
+    let err;
     unsafe {
         gl::GenVertexArrays(1, &mut hnd);
         err = gl::GetError();
@@ -725,6 +1513,13 @@ fn create_vao() -> GLuint {
     if err != gl::NO_ERROR {
         crate::fatal!("Failed to name attribute array: {err:X}");
     }
+    bind_vao_buffers(hnd, vbo, ibo);
+    hnd
+}
+
+/// Point the attributes and indices of the VAO at the buffers.
+fn bind_vao_buffers(vao: GLuint, vbo: GLuint, ibo: GLuint) {
+    let err;
     const STRIDE: GLsizei = mem::size_of::<Vtx>() as GLsizei;
     unsafe {
         let pos = ptr::without_provenance(bytemuck::offset_of!(Vtx, pos));
@@ -732,7 +1527,9 @@ fn create_vao() -> GLuint {
         let norm = ptr::without_provenance(bytemuck::offset_of!(Vtx, norm));
         let ty = ptr::without_provenance(bytemuck::offset_of!(Vtx, ty));
         let color = ptr::without_provenance(bytemuck::offset_of!(Vtx, color));
-        gl::BindVertexArray(hnd);
+        gl::BindVertexArray(vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
         gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, STRIDE, pos);
         gl::EnableVertexAttribArray(0);
         gl::VertexAttribPointer(1, 1, gl::FLOAT, gl::FALSE, STRIDE, tx);
@@ -748,7 +1545,6 @@ fn create_vao() -> GLuint {
     if err != gl::NO_ERROR {
         crate::fatal!("Failed to configure attribute array: {err:X}");
     }
-    hnd
 }
 
 fn compile_shader(kind: GLenum, src: &str) -> GLuint {
@@ -1,6 +1,6 @@
 #version 410 core
 
-const uint NUM_INST_COMPONENTS = 6;
+const uint NUM_INST_COMPONENTS = 9;
 
 uniform mat4 proj;
 uniform mat4 view;
@@ -16,6 +16,7 @@ layout (location = 4) in vec4 color;
 
 flat out uint tex;
 out vec2 tex_coord;
+out vec3 vtx_norm;
 out vec4 vtx_color;
 
 mat4 fetchModel(uint offset) {
@@ -26,12 +27,20 @@ mat4 fetchModel(uint offset) {
     return model;
 }
 
+mat3 fetchNormal(uint offset) {
+    mat3 normal;
+    for (uint i = 0; i < 3; i++) {
+        normal[i] = texelFetch(sbo, ivec2(offset + 4 + i, store), 0).xyz;
+    }
+    return normal;
+}
+
 vec4 fetchBlend(uint offset) {
-    return texelFetch(sbo, ivec2(offset + 4, store), 0);
+    return texelFetch(sbo, ivec2(offset + 7, store), 0);
 }
 
 uint fetchTex(uint offset) {
-    vec4 texel = texelFetch(sbo, ivec2(offset + 5, store), 0);
+    vec4 texel = texelFetch(sbo, ivec2(offset + 8, store), 0);
     return uint(texel.x);
 }
 
@@ -39,11 +48,14 @@ void main() {
     uint offset = gl_InstanceID * NUM_INST_COMPONENTS;
 
     mat4 model = fetchModel(offset);
+    mat3 normal = fetchNormal(offset);
     vec4 blend = fetchBlend(offset);
 
     tex = fetchTex(offset);
     vtx_color = color * blend;
     tex_coord = vec2(tx, ty);
+    // the normal matrix keeps normals perpendicular under non-uniform scale
+    vtx_norm = normalize(normal * norm);
 
     gl_Position = proj * view * model * vec4(pos, 1.0);
 }
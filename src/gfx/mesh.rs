@@ -0,0 +1,333 @@
+//! Geometry preparation for indexed triangle lists before they are written
+//! through `Gfx::mesh_map`.
+
+use std::collections::HashMap;
+
+use super::{Vtx, mikktspace};
+use crate::math::{Aabb, Cross, Sphere, V3, V4};
+
+/// Area-weighted smooth normals, shared between triangles that share a
+/// vertex.
+pub fn smooth_normals(verts: &mut [Vtx], idxs: &[u32]) {
+    for vtx in verts.iter_mut() {
+        vtx.norm = V3::splat(0.0);
+    }
+    for tri in idxs.chunks_exact(3) {
+        let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| i as usize);
+        // the un-normalized cross product is twice the area
+        let norm = (verts[b].pos - verts[a].pos).cross(verts[c].pos - verts[a].pos);
+        verts[a].norm = verts[a].norm + norm;
+        verts[b].norm = verts[b].norm + norm;
+        verts[c].norm = verts[c].norm + norm;
+    }
+    for vtx in verts.iter_mut() {
+        vtx.norm = normalized_or_zero(vtx.norm);
+    }
+}
+
+/// Face normals. Every index gets its own vertex, so `idxs` becomes
+/// `0..idxs.len()`.
+pub fn flat_normals(verts: &mut Vec<Vtx>, idxs: &mut [u32]) {
+    let mut flat = Vec::with_capacity(idxs.len());
+    for tri in idxs.chunks_exact(3) {
+        let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| verts[i as usize]);
+        let norm = normalized_or_zero((b.pos - a.pos).cross(c.pos - a.pos));
+        flat.extend([a, b, c].map(|vtx| Vtx { norm, ..vtx }));
+    }
+    for (i, idx) in idxs.iter_mut().enumerate() {
+        *idx = i as u32;
+    }
+    *verts = flat;
+}
+
+/// MikkTSpace tangents, matching the reference implementation that normal
+/// maps are usually baked with. `w` holds the handedness so that
+/// `bitangent = w * norm.cross(tangent)`. Requires normals and UVs (`tx`,
+/// `ty`). Vertices whose corners end up with different tangents are split,
+/// the copies are appended to `verts` and `idxs` remapped, so the result
+/// lines up with the new `verts`.
+pub fn tangents(verts: &mut Vec<Vtx>, idxs: &mut [u32]) -> Vec<V4> {
+    let corners = mikktspace::generate(verts, idxs);
+    let mut tans: Vec<Option<V4>> = vec![None; verts.len()];
+    let mut copies = HashMap::new();
+    for (idx, tan) in idxs.iter_mut().zip(corners) {
+        let bits = tan.0.map(f32::to_bits);
+        match tans[*idx as usize] {
+            None => tans[*idx as usize] = Some(tan),
+            Some(cur) if cur.0.map(f32::to_bits) == bits => {}
+            Some(_) => {
+                let orig = *idx;
+                *idx = *copies.entry((orig, bits)).or_insert_with(|| {
+                    verts.push(verts[orig as usize]);
+                    tans.push(Some(tan));
+                    (verts.len() - 1) as u32
+                });
+            }
+        }
+    }
+    // vertices no triangle uses get any tangent
+    tans.into_iter()
+        .map(|tan| tan.unwrap_or(V4([1.0, 0.0, 0.0, 1.0])))
+        .collect()
+}
+
+/// Merge identical vertices and remap `idxs` to match. Vertices keep the
+/// order of their first occurrence.
+pub fn weld(verts: &mut Vec<Vtx>, idxs: &mut [u32]) {
+    let mut unique = HashMap::with_capacity(verts.len());
+    let mut remap = Vec::with_capacity(verts.len());
+    let mut welded = Vec::with_capacity(verts.len());
+    for vtx in verts.iter() {
+        // adding `0.0` folds `-0.0` into `0.0` so they compare equal
+        let key = bytemuck::cast::<_, [f32; 12]>(*vtx).map(|f| (f + 0.0).to_bits());
+        let idx = *unique.entry(key).or_insert_with(|| {
+            welded.push(*vtx);
+            welded.len() as u32 - 1
+        });
+        remap.push(idx);
+    }
+    for idx in idxs.iter_mut() {
+        *idx = remap[*idx as usize];
+    }
+    *verts = welded;
+}
+
+const CACHE_SIZE: usize = 32;
+const NOT_CACHED: usize = usize::MAX;
+
+/// Reorder triangles for the post-transform vertex cache, using Tom
+/// Forsyth's linear-speed algorithm. Winding within a triangle is kept.
+pub fn optimize_vertex_cache(idxs: &mut [u32], vert_count: usize) {
+    let tri_count = idxs.len() / 3;
+    let idxs = &mut idxs[..tri_count * 3];
+
+    // triangles adjacent to each vertex, the first `valence[v]` entries of
+    // a vertex's range are the ones not yet emitted
+    let mut valence = vec![0u32; vert_count];
+    for &idx in idxs.iter() {
+        valence[idx as usize] += 1;
+    }
+    let mut offsets = Vec::with_capacity(vert_count + 1);
+    offsets.push(0);
+    for v in 0..vert_count {
+        offsets.push(offsets[v] + valence[v] as usize);
+    }
+    let mut adj = vec![0u32; idxs.len()];
+    let mut fill = offsets.clone();
+    for (i, &idx) in idxs.iter().enumerate() {
+        adj[fill[idx as usize]] = (i / 3) as u32;
+        fill[idx as usize] += 1;
+    }
+
+    let mut cache_pos = vec![NOT_CACHED; vert_count];
+    let mut vert_score: Vec<f32> = valence
+        .iter()
+        .map(|&val| vertex_score(NOT_CACHED, val))
+        .collect();
+    let tri_verts = |tri: usize| [idxs[tri * 3], idxs[tri * 3 + 1], idxs[tri * 3 + 2]];
+    let tri_score = |vert_score: &[f32], tri: usize| -> f32 {
+        tri_verts(tri).iter().map(|&v| vert_score[v as usize]).sum()
+    };
+    let mut emitted = vec![false; tri_count];
+
+    let mut out = Vec::with_capacity(idxs.len());
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut next_cache = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut best = (0..tri_count)
+        .max_by(|&a, &b| tri_score(&vert_score, a).total_cmp(&tri_score(&vert_score, b)));
+    let mut scan = 0;
+    while out.len() < tri_count * 3 {
+        let tri = match best {
+            Some(tri) => tri,
+            None => {
+                // nothing adjacent to the cache is left, resume a linear scan
+                while emitted[scan] {
+                    scan += 1;
+                }
+                scan
+            }
+        };
+        emitted[tri] = true;
+        let verts = tri_verts(tri);
+        out.extend_from_slice(&verts);
+
+        for &v in &verts {
+            let v = v as usize;
+            let range = offsets[v]..offsets[v] + valence[v] as usize;
+            let at = adj[range.clone()]
+                .iter()
+                .position(|&t| t as usize == tri)
+                .unwrap();
+            adj.swap(range.start + at, range.end - 1);
+            valence[v] -= 1;
+        }
+
+        next_cache.clear();
+        for &v in verts.iter().chain(cache.iter()) {
+            if !next_cache.contains(&v) {
+                next_cache.push(v);
+            }
+        }
+        for (pos, &v) in next_cache.iter().enumerate() {
+            let v = v as usize;
+            cache_pos[v] = if pos < CACHE_SIZE { pos } else { NOT_CACHED };
+            vert_score[v] = vertex_score(cache_pos[v], valence[v]);
+        }
+
+        best = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for &v in &next_cache {
+            let v = v as usize;
+            for &t in &adj[offsets[v]..offsets[v] + valence[v] as usize] {
+                let t = t as usize;
+                let score = tri_score(&vert_score, t);
+                if score > best_score {
+                    best_score = score;
+                    best = Some(t);
+                }
+            }
+        }
+
+        next_cache.truncate(CACHE_SIZE);
+        std::mem::swap(&mut cache, &mut next_cache);
+    }
+    idxs.copy_from_slice(&out);
+}
+
+#[inline]
+fn vertex_score(cache_pos: usize, valence: u32) -> f32 {
+    if valence == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_pos {
+        NOT_CACHED => 0.0,
+        // the last triangle's vertices get a fixed score so that the
+        // algorithm does not favour strips too much
+        0..3 => 0.75,
+        pos => (1.0 - (pos - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+    cache_score + 2.0 * (valence as f32).powf(-0.5)
+}
+
+/// Average cache miss ratio (transformed vertices per triangle) of `idxs`
+/// through a FIFO cache of `cache_size` entries. Lower is better, `0.5` is
+/// the ideal for large regular meshes.
+pub fn cache_miss_ratio(idxs: &[u32], cache_size: usize) -> f32 {
+    let tri_count = idxs.len() / 3;
+    if tri_count == 0 {
+        return 0.0;
+    }
+    let mut fifo = std::collections::VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+    for &idx in &idxs[..tri_count * 3] {
+        if fifo.contains(&idx) {
+            continue;
+        }
+        misses += 1;
+        if fifo.len() == cache_size {
+            fifo.pop_front();
+        }
+        fifo.push_back(idx);
+    }
+    misses as f32 / tri_count as f32
+}
+
+#[inline]
+pub fn bounds(verts: &[Vtx]) -> Aabb {
+    Aabb::from_points(verts.iter().map(|vtx| &vtx.pos))
+}
+
+/// Ritter's bounding sphere. Not minimal, but within a few percent.
+pub fn bounding_sphere(verts: &[Vtx]) -> Sphere {
+    let Some(first) = verts.first() else {
+        return Sphere {
+            center: V3::splat(0.0),
+            radius: 0.0,
+        };
+    };
+    let farthest = |from: V3| {
+        verts
+            .iter()
+            .map(|vtx| vtx.pos)
+            .max_by(|a, b| {
+                (*a - from)
+                    .normal_squared()
+                    .total_cmp(&(*b - from).normal_squared())
+            })
+            .unwrap()
+    };
+    let a = farthest(first.pos);
+    let b = farthest(a);
+    let mut center = (a + b) * 0.5;
+    let mut radius = (b - a).length() * 0.5;
+    for vtx in verts {
+        let dist = (vtx.pos - center).length();
+        if dist > radius {
+            radius = (radius + dist) * 0.5;
+            center = center + (vtx.pos - center) * ((dist - radius) / dist);
+        }
+    }
+    Sphere { center, radius }
+}
+
+#[inline]
+fn normalized_or_zero(v: V3) -> V3 {
+    let len_sq = v.normal_squared();
+    if len_sq > 0.0 {
+        v / len_sq.sqrt()
+    } else {
+        V3::splat(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vtx(pos: [f32; 3], norm: V3, uv: [f32; 2]) -> Vtx {
+        Vtx {
+            pos: V3(pos),
+            tx: uv[0],
+            norm,
+            ty: uv[1],
+            ..Vtx::default()
+        }
+    }
+
+    #[test]
+    fn tangents_match_reference_and_split_mirrored_seams() {
+        // two quads with mirrored UVs, the second tilted, plus a degenerate
+        // triangle, expected values come from the reference implementation
+        let up = V3([0.0, 0.0, 1.0]);
+        let tilt = V3([-0.28734788, 0.0, 0.95782626]);
+        let mut verts = vec![
+            vtx([0.0, 0.0, 0.0], up, [0.0, 0.0]),
+            vtx([1.0, 0.0, 0.0], up, [1.0, 0.0]),
+            vtx([1.0, 1.0, 0.0], up, [1.0, 1.0]),
+            vtx([0.0, 1.0, 0.0], up, [0.0, 1.0]),
+            vtx([2.0, 0.0, 0.5], tilt, [0.0, 0.0]),
+            vtx([2.0, 1.0, 0.5], tilt, [0.0, 1.0]),
+        ];
+        let mut idxs = vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2, 0, 0, 3];
+        let tans = tangents(&mut verts, &mut idxs);
+
+        // the seam vertices 1 and 2 get a copy for the mirrored side
+        assert_eq!(idxs, [0, 1, 2, 0, 2, 3, 6, 4, 5, 6, 5, 7, 0, 0, 3]);
+        assert_eq!(verts.len(), 8);
+        assert_eq!(verts[6].pos.0, verts[1].pos.0);
+        assert_eq!(verts[7].pos.0, verts[2].pos.0);
+        let expected = [
+            [1.0, 0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0, 1.0],
+            [-0.9578263, 0.0, -0.2873479, -1.0],
+            [-0.9578263, 0.0, -0.28734788, -1.0],
+            [-1.0, 0.0, 0.0, -1.0],
+            [-1.0, 0.0, 0.0, -1.0],
+        ];
+        for (tan, expected) in tans.iter().zip(expected) {
+            assert_eq!(tan.0, expected);
+        }
+    }
+}
@@ -0,0 +1,439 @@
+//! Morten S. Mikkelsen's MikkTSpace tangent generation, ported from the
+//! reference C implementation and altered to take triangle lists only, quads
+//! have to be split before they get here. The reference carries this notice:
+//!
+//! Copyright (C) 2011 by Morten S. Mikkelsen
+//!
+//! This software is provided 'as-is', without any express or implied
+//! warranty.  In no event will the authors be held liable for any damages
+//! arising from the use of this software.
+//!
+//! Permission is granted to anyone to use this software for any purpose,
+//! including commercial applications, and to alter it and redistribute it
+//! freely, subject to the following restrictions:
+//!
+//! 1. The origin of this software must not be misrepresented; you must not
+//!    claim that you wrote the original software. If you use this software
+//!    in a product, an acknowledgment in the product documentation would be
+//!    appreciated but is not required.
+//!
+//! 2. Altered source versions must be plainly marked as such, and must not be
+//!    misrepresented as being the original software.
+//!
+//! 3. This notice may not be removed or altered from any source distribution.
+
+use std::collections::HashMap;
+
+use super::Vtx;
+use crate::math::{Dot, V3, V4};
+
+/// Two corners share a position.
+const DEGENERATE: u8 = 1;
+/// The UVs give no usable gradient, so the triangle joins any group.
+const GROUP_WITH_ANY: u8 = 4;
+const ORIENT_PRESERVING: u8 = 8;
+
+/// Cosine of the reference's default 180 degree angular threshold.
+const THRES_COS: f32 = -1.0;
+
+#[derive(Clone, Copy)]
+struct TSpace {
+    os: V3,
+    mag_s: f32,
+    ot: V3,
+    mag_t: f32,
+    orient: bool,
+}
+
+const DEFAULT_TSPACE: TSpace = TSpace {
+    os: V3([1.0, 0.0, 0.0]),
+    mag_s: 1.0,
+    ot: V3([0.0, 1.0, 0.0]),
+    mag_t: 1.0,
+    orient: false,
+};
+
+#[derive(Clone, Copy)]
+struct TriInfo {
+    neighbors: [Option<usize>; 3],
+    groups: [Option<usize>; 3],
+    os: V3,
+    ot: V3,
+    mag_s: f32,
+    mag_t: f32,
+    /// Index of the triangle in the caller's list, triangles get reordered.
+    face: usize,
+    flags: u8,
+}
+
+struct Group {
+    faces: Vec<usize>,
+    /// Welded corner the group is built around.
+    vert: usize,
+    orient: bool,
+}
+
+struct SubGroup {
+    members: Vec<usize>,
+    tspace: TSpace,
+}
+
+/// Corner-level view of the caller's mesh, corner `3 * face + i` is corner `i`
+/// of triangle `face`.
+struct Geometry<'a> {
+    verts: &'a [Vtx],
+    idxs: &'a [u32],
+}
+
+impl Geometry<'_> {
+    #[inline]
+    fn vtx(&self, corner: usize) -> &Vtx {
+        &self.verts[self.idxs[corner] as usize]
+    }
+
+    #[inline]
+    fn pos(&self, corner: usize) -> V3 {
+        self.vtx(corner).pos
+    }
+
+    #[inline]
+    fn norm(&self, corner: usize) -> V3 {
+        self.vtx(corner).norm
+    }
+
+    #[inline]
+    fn uv(&self, corner: usize) -> [f32; 2] {
+        let vtx = self.vtx(corner);
+        [vtx.tx, vtx.ty]
+    }
+}
+
+/// Per-corner tangents for every whole triangle in `idxs`, `w` is `1.0` when
+/// the UV mapping preserves orientation and `-1.0` when it mirrors. Corners
+/// that get no tangent space keep the reference default of `+X` with `w` of
+/// `-1.0`.
+pub(super) fn generate(verts: &[Vtx], idxs: &[u32]) -> Vec<V4> {
+    let geo = Geometry { verts, idxs };
+    let tri_count = idxs.len() / 3;
+    let mut tspaces = vec![DEFAULT_TSPACE; tri_count * 3];
+    let mut list = weld(&geo, tri_count);
+    let mut infos = (0..tri_count)
+        .map(|face| {
+            let [p0, p1, p2] = [0, 1, 2].map(|i| geo.pos(list[face * 3 + i]).0);
+            let degenerate = p0 == p1 || p0 == p2 || p1 == p2;
+            TriInfo {
+                neighbors: [None; 3],
+                groups: [None; 3],
+                os: V3::splat(0.0),
+                ot: V3::splat(0.0),
+                mag_s: 0.0,
+                mag_t: 0.0,
+                face,
+                flags: if degenerate { DEGENERATE } else { 0 },
+            }
+        })
+        .collect::<Vec<_>>();
+    let good = infos.iter().filter(|t| t.flags & DEGENERATE == 0).count();
+    if good > 0 {
+        degen_prologue(&mut list, &mut infos, good);
+        init_tri_info(&geo, &list, &mut infos[..good]);
+        build_neighbors(&list, &mut infos[..good]);
+        let groups = build_groups(&list, &mut infos[..good]);
+        generate_tspaces(&geo, &list, &infos, &groups, &mut tspaces);
+        degen_epilogue(&list, &infos, good, &mut tspaces);
+    }
+    tspaces
+        .iter()
+        .map(|ts| ts.os.extended(if ts.orient { 1.0 } else { -1.0 }))
+        .collect()
+}
+
+/// Map every corner to the first corner with the same position, normal and
+/// UV, so corners can be compared by index.
+fn weld(geo: &Geometry, tri_count: usize) -> Vec<usize> {
+    // -0.0 and 0.0 compare equal in the reference
+    let key = |v: f32| (v + 0.0).to_bits();
+    let mut first = HashMap::with_capacity(tri_count * 3);
+    (0..tri_count * 3)
+        .map(|corner| {
+            let vtx = geo.vtx(corner);
+            let k = (
+                vtx.pos.0.map(key),
+                vtx.norm.0.map(key),
+                [vtx.tx, vtx.ty].map(key),
+            );
+            *first.entry(k).or_insert(corner)
+        })
+        .collect()
+}
+
+/// Move degenerate triangles to the end of the list.
+fn degen_prologue(list: &mut [usize], infos: &mut [TriInfo], good: usize) {
+    let mut next_good = 1;
+    for t in 0..good {
+        if infos[t].flags & DEGENERATE == 0 {
+            next_good = next_good.max(t + 2);
+            continue;
+        }
+        while next_good < infos.len() && infos[next_good].flags & DEGENERATE != 0 {
+            next_good += 1;
+        }
+        if next_good >= infos.len() {
+            break;
+        }
+        for i in 0..3 {
+            list.swap(t * 3 + i, next_good * 3 + i);
+        }
+        infos.swap(t, next_good);
+        next_good += 1;
+    }
+}
+
+fn init_tri_info(geo: &Geometry, list: &[usize], infos: &mut [TriInfo]) {
+    for (t, info) in infos.iter_mut().enumerate() {
+        info.flags |= GROUP_WITH_ANY;
+        let [v1, v2, v3] = [0, 1, 2].map(|i| geo.pos(list[t * 3 + i]));
+        let [t1, t2, t3] = [0, 1, 2].map(|i| geo.uv(list[t * 3 + i]));
+        let [t21x, t21y] = [t2[0] - t1[0], t2[1] - t1[1]];
+        let [t31x, t31y] = [t3[0] - t1[0], t3[1] - t1[1]];
+        let (d1, d2) = (v2 - v1, v3 - v1);
+        let area = t21x * t31y - t21y * t31x;
+        let os = d1 * t31y - d2 * t21y;
+        let ot = d2 * t21x - d1 * t31x;
+        if area > 0.0 {
+            info.flags |= ORIENT_PRESERVING;
+        }
+        if !not_zero(area) {
+            continue;
+        }
+        let sign = if area > 0.0 { 1.0 } else { -1.0 };
+        let (len_os, len_ot) = (os.length(), ot.length());
+        if not_zero(len_os) {
+            info.os = os * (sign / len_os);
+        }
+        if not_zero(len_ot) {
+            info.ot = ot * (sign / len_ot);
+        }
+        info.mag_s = len_os / area.abs();
+        info.mag_t = len_ot / area.abs();
+        if not_zero(info.mag_s) && not_zero(info.mag_t) {
+            info.flags &= !GROUP_WITH_ANY;
+        }
+    }
+}
+
+/// Pair up triangles sharing an edge in opposite directions, each edge gets
+/// at most one neighbor.
+fn build_neighbors(list: &[usize], infos: &mut [TriInfo]) {
+    let edge = |t: usize, i: usize| (list[t * 3 + i], list[t * 3 + (i + 1) % 3]);
+    let mut edges = (0..infos.len())
+        .flat_map(|t| (0..3).map(move |i| (t, i)))
+        .map(|(t, i)| {
+            let (a, b) = edge(t, i);
+            (a.min(b), a.max(b), t, i)
+        })
+        .collect::<Vec<_>>();
+    edges.sort_unstable();
+    for (n, &(lo, hi, t, i)) in edges.iter().enumerate() {
+        if infos[t].neighbors[i].is_some() {
+            continue;
+        }
+        let (a, b) = edge(t, i);
+        let other = edges[n + 1..]
+            .iter()
+            .take_while(|e| (e.0, e.1) == (lo, hi))
+            .find(|&&(_, _, u, j)| edge(u, j) == (b, a) && infos[u].neighbors[j].is_none());
+        if let Some(&(_, _, u, j)) = other {
+            infos[t].neighbors[i] = Some(u);
+            infos[u].neighbors[j] = Some(t);
+        }
+    }
+}
+
+/// Group the triangles around each vertex that can share a tangent space,
+/// spreading across shared edges until the orientation flips.
+fn build_groups(list: &[usize], infos: &mut [TriInfo]) -> Vec<Group> {
+    let mut groups = Vec::new();
+    for f in 0..infos.len() {
+        for i in 0..3 {
+            if infos[f].flags & GROUP_WITH_ANY != 0 || infos[f].groups[i].is_some() {
+                continue;
+            }
+            let g = groups.len();
+            groups.push(Group {
+                faces: vec![f],
+                vert: list[f * 3 + i],
+                orient: infos[f].flags & ORIENT_PRESERVING != 0,
+            });
+            infos[f].groups[i] = Some(g);
+            let [left, right] = [infos[f].neighbors[i], infos[f].neighbors[(i + 2) % 3]];
+            for t in [left, right].into_iter().flatten() {
+                assign_group(list, infos, &mut groups[g], g, t);
+            }
+        }
+    }
+    groups
+}
+
+fn assign_group(list: &[usize], infos: &mut [TriInfo], group: &mut Group, g: usize, t: usize) {
+    let i = corner_of(list, t, group.vert);
+    let info = &mut infos[t];
+    if info.groups[i].is_some() {
+        return;
+    }
+    if info.flags & GROUP_WITH_ANY != 0 && info.groups == [None; 3] {
+        info.flags &= !ORIENT_PRESERVING;
+        if group.orient {
+            info.flags |= ORIENT_PRESERVING;
+        }
+    }
+    if (info.flags & ORIENT_PRESERVING != 0) != group.orient {
+        return;
+    }
+    group.faces.push(t);
+    info.groups[i] = Some(g);
+    let [left, right] = [info.neighbors[i], info.neighbors[(i + 2) % 3]];
+    for t in [left, right].into_iter().flatten() {
+        assign_group(list, infos, group, g, t);
+    }
+}
+
+fn generate_tspaces(
+    geo: &Geometry,
+    list: &[usize],
+    infos: &[TriInfo],
+    groups: &[Group],
+    tspaces: &mut [TSpace],
+) {
+    for (g, group) in groups.iter().enumerate() {
+        let mut subgroups: Vec<SubGroup> = Vec::new();
+        for &f in &group.faces {
+            let i = (0..3)
+                .find(|&i| infos[f].groups[i] == Some(g))
+                .expect("grouped triangles have a corner in the group");
+            let norm = geo.norm(list[f * 3 + i]);
+            let os = project(infos[f].os, norm);
+            let ot = project(infos[f].ot, norm);
+            let mut members = group
+                .faces
+                .iter()
+                .copied()
+                .filter(|&t| {
+                    let any = (infos[f].flags | infos[t].flags) & GROUP_WITH_ANY != 0;
+                    let same_face = infos[f].face == infos[t].face;
+                    let cos_s = os.dot(project(infos[t].os, norm));
+                    let cos_t = ot.dot(project(infos[t].ot, norm));
+                    any || same_face || (cos_s > THRES_COS && cos_t > THRES_COS)
+                })
+                .collect::<Vec<_>>();
+            members.sort_unstable();
+            let tspace = match subgroups.iter().find(|s| s.members == members) {
+                Some(sub) => sub.tspace,
+                None => {
+                    let tspace = eval_tspace(geo, list, infos, &members, group.vert);
+                    subgroups.push(SubGroup { members, tspace });
+                    tspace
+                }
+            };
+            tspaces[infos[f].face * 3 + i] = TSpace {
+                orient: group.orient,
+                ..tspace
+            };
+        }
+    }
+}
+
+/// Corner angle weighted average of the members' tangent spaces at `vert`.
+fn eval_tspace(
+    geo: &Geometry,
+    list: &[usize],
+    infos: &[TriInfo],
+    members: &[usize],
+    vert: usize,
+) -> TSpace {
+    let mut res = TSpace {
+        os: V3::splat(0.0),
+        mag_s: 0.0,
+        ot: V3::splat(0.0),
+        mag_t: 0.0,
+        orient: false,
+    };
+    let mut angle_sum = 0.0;
+    for &f in members {
+        let info = &infos[f];
+        if info.flags & GROUP_WITH_ANY != 0 {
+            continue;
+        }
+        let i = corner_of(list, f, vert);
+        let norm = geo.norm(list[f * 3 + i]);
+        let os = project(info.os, norm);
+        let ot = project(info.ot, norm);
+        let p0 = geo.pos(list[f * 3 + (i + 2) % 3]);
+        let p1 = geo.pos(list[f * 3 + i]);
+        let p2 = geo.pos(list[f * 3 + (i + 1) % 3]);
+        let v1 = project(p0 - p1, norm);
+        let v2 = project(p2 - p1, norm);
+        let cos = v1.dot(v2).clamp(-1.0, 1.0);
+        // the reference takes the angle in double precision
+        let angle = (cos as f64).acos() as f32;
+        res.os = res.os + os * angle;
+        res.ot = res.ot + ot * angle;
+        res.mag_s += angle * info.mag_s;
+        res.mag_t += angle * info.mag_t;
+        angle_sum += angle;
+    }
+    if any_nonzero(res.os) {
+        res.os = normalize(res.os);
+    }
+    if any_nonzero(res.ot) {
+        res.ot = normalize(res.ot);
+    }
+    if angle_sum > 0.0 {
+        res.mag_s /= angle_sum;
+        res.mag_t /= angle_sum;
+    }
+    res
+}
+
+/// Degenerate triangles copy the tangent space of any good corner they weld
+/// to.
+fn degen_epilogue(list: &[usize], infos: &[TriInfo], good: usize, tspaces: &mut [TSpace]) {
+    for t in good..infos.len() {
+        for i in 0..3 {
+            let vert = list[t * 3 + i];
+            if let Some(j) = list[..good * 3].iter().position(|&v| v == vert) {
+                let src = infos[j / 3].face * 3 + j % 3;
+                tspaces[infos[t].face * 3 + i] = tspaces[src];
+            }
+        }
+    }
+}
+
+fn corner_of(list: &[usize], t: usize, vert: usize) -> usize {
+    (0..3)
+        .find(|&i| list[t * 3 + i] == vert)
+        .expect("grouped triangles contain the group's vertex")
+}
+
+#[inline]
+fn not_zero(v: f32) -> bool {
+    v.abs() > f32::MIN_POSITIVE
+}
+
+#[inline]
+fn any_nonzero(v: V3) -> bool {
+    v.0.iter().any(|&c| not_zero(c))
+}
+
+#[inline]
+fn normalize(v: V3) -> V3 {
+    v * (1.0 / v.length())
+}
+
+/// Project `v` onto the plane of `norm` and normalize it, if that leaves
+/// anything.
+#[inline]
+fn project(v: V3, norm: V3) -> V3 {
+    let v = v - norm * norm.dot(v);
+    if any_nonzero(v) { normalize(v) } else { v }
+}
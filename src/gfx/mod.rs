@@ -1,10 +1,21 @@
+use std::{fmt, sync::Arc};
+
 use bytemuck::{Pod, Zeroable};
+use crossbeam_channel::Sender;
 use gl::{BufMap, TexMap};
 
-use crate::math::{Mat4, UV2, V3, V4, Xform3};
+use crate::{
+    codec::{Decode, DecodeError, Encode, Reader, Writer},
+    math::{Color, Cross, Dot, Mat4, Ray, Rgba8, UV2, V2, V3, V4, Xform3},
+    mem::{Handle, MetaAllocStats, SharedHandles},
+};
 
 #[cfg(feature = "gl")]
 mod gl;
+pub mod mesh;
+mod mikktspace;
+pub mod path;
+pub mod shape;
 
 pub struct Gfx {
     #[cfg(feature = "gl")]
@@ -20,6 +31,7 @@ impl Gfx {
         }
     }
 
+    /// Applies the uploads staged since the last pass first.
     #[inline]
     pub fn pass<'a>(&'a mut self, settings: PassSettings<'a>) -> Pass<'a> {
         Pass {
@@ -29,42 +41,220 @@ impl Gfx {
     }
 
     #[inline]
-    pub fn mesh_alloc(&mut self, verts: usize, idxs: usize) -> u32 {
+    pub fn mesh_alloc(&mut self, verts: usize, idxs: usize) -> MeshHnd {
         #[cfg(feature = "gl")]
         self.gl.mesh_alloc(verts, idxs)
     }
 
+    /// For reserving handles and staging uploads on other threads.
+    #[inline]
+    pub fn uploader(&self) -> Uploader {
+        #[cfg(feature = "gl")]
+        self.gl.uploader()
+    }
+
+    /// Also takes reserved handles, dropping their pending uploads. Stale
+    /// handles and double frees are logged and ignored.
     #[inline]
-    pub fn mesh_free(&mut self, hnd: u32) {
+    pub fn mesh_free(&mut self, hnd: MeshHnd) {
         #[cfg(feature = "gl")]
         self.gl.mesh_free(hnd)
     }
 
+    /// `None` (logged) if the handle is stale or has no data yet.
     #[inline]
-    pub fn mesh_map<'a>(&'a mut self, hnd: u32) -> (BufMap<'a, Vtx>, BufMap<'a, u32>) {
+    pub fn mesh_map<'a>(&'a mut self, hnd: MeshHnd) -> Option<(BufMap<'a, Vtx>, BufMap<'a, u32>)> {
         #[cfg(feature = "gl")]
         self.gl.mesh_map(hnd)
     }
 
     #[inline]
-    pub fn tex_alloc(&mut self) -> u32 {
+    pub fn tex_alloc(&mut self) -> TexHnd {
         #[cfg(feature = "gl")]
         self.gl.tex_alloc()
     }
 
+    /// Defragment the vertex and index buffers by moving mesh data towards
+    /// their starts. Handles stay valid. Returns the bytes reclaimed, i.e.
+    /// how much the largest contiguous free blocks grew.
+    #[inline]
+    pub fn compact(&mut self) -> usize {
+        #[cfg(feature = "gl")]
+        self.gl.compact()
+    }
+
+    /// Textures on consecutive layers, so that a shader can step from the
+    /// first handle's layer to the others (e.g. animation frames).
+    #[inline]
+    pub fn tex_alloc_array(&mut self, count: usize) -> Vec<TexHnd> {
+        #[cfg(feature = "gl")]
+        self.gl.tex_alloc_array(count)
+    }
+
+    /// Also takes reserved handles, dropping their pending uploads. Stale
+    /// handles and double frees are logged and ignored.
     #[inline]
-    pub fn tex_free(&mut self, hnd: u32) {
+    pub fn tex_free(&mut self, hnd: TexHnd) {
         #[cfg(feature = "gl")]
         self.gl.tex_free(hnd)
     }
 
+    /// Vertex buffer usage, in bytes (see `Settings::vtx_buffer_size`).
+    #[inline]
+    pub fn vtx_stats(&self) -> MetaAllocStats {
+        #[cfg(feature = "gl")]
+        self.gl.vtx_stats()
+    }
+
+    /// Index buffer usage, in bytes (see `Settings::idx_buffer_size`).
+    #[inline]
+    pub fn idx_stats(&self) -> MetaAllocStats {
+        #[cfg(feature = "gl")]
+        self.gl.idx_stats()
+    }
+
+    #[inline]
+    pub fn memory_report(&self) -> MemoryReport {
+        #[cfg(feature = "gl")]
+        self.gl.memory_report()
+    }
+
+    /// Log `memory_report` at info level, one pool per line.
+    pub fn log_memory_report(&self) {
+        for (name, pool) in self.memory_report().pools() {
+            log::info!("{name}: {pool}");
+        }
+    }
+
+    /// `None` (logged) if the handle is stale or has no data yet.
     #[inline]
-    pub fn tex_map<'a>(&'a mut self, hnd: u32) -> TexMap<'a> {
+    pub fn tex_map<'a>(&'a mut self, hnd: TexHnd) -> Option<TexMap<'a>> {
         #[cfg(feature = "gl")]
         self.gl.tex_map(hnd)
     }
 }
 
+/// Tags `MeshHnd`.
+pub enum Mesh {}
+
+/// Tags `TexHnd`.
+pub enum Tex {}
+
+pub type MeshHnd = Handle<Mesh>;
+pub type TexHnd = Handle<Tex>;
+
+/// Usage of one GPU pool, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolReport {
+    pub capacity: usize,
+    pub used: usize,
+    /// Live allocations (meshes, texture layers or stores).
+    pub allocs: usize,
+    /// Highest `used` since startup.
+    pub peak: usize,
+    pub largest_free: usize,
+}
+
+impl fmt::Display for PoolReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} KiB used ({:.1}%), peak {} KiB, {} allocations, largest free {} KiB",
+            self.used / 1024,
+            self.capacity / 1024,
+            self.used as f64 / self.capacity.max(1) as f64 * 100.0,
+            self.peak / 1024,
+            self.allocs,
+            self.largest_free / 1024
+        )
+    }
+}
+
+/// Usage of every GPU pool (see `Gfx::memory_report`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub vtx: PoolReport,
+    pub idx: PoolReport,
+    pub tex: PoolReport,
+    pub store: PoolReport,
+}
+
+impl MemoryReport {
+    #[inline]
+    pub fn pools(&self) -> [(&'static str, PoolReport); 4] {
+        [
+            ("vertex buffer", self.vtx),
+            ("index buffer", self.idx),
+            ("textures", self.tex),
+            ("stores", self.store),
+        ]
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, pool) in self.pools() {
+            writeln!(f, "{name}: {pool}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reserves mesh and texture handles and stages their data from any thread,
+/// e.g. asset loaders. Staged uploads are applied when the next pass begins,
+/// and until then drawing a reserved handle draws nothing. Uploading to a
+/// handle that already has data replaces it.
+#[derive(Clone)]
+pub struct Uploader {
+    meshes: Arc<SharedHandles<Mesh>>,
+    texs: Arc<SharedHandles<Tex>>,
+    tx: Sender<Upload>,
+}
+
+enum Upload {
+    Mesh {
+        hnd: MeshHnd,
+        vtxs: Vec<Vtx>,
+        idxs: Vec<u32>,
+    },
+    Tex {
+        hnd: TexHnd,
+        texels: Vec<Rgba8>,
+    },
+}
+
+impl Uploader {
+    /// `None` if all `Settings::mesh_count_max` handles are in use.
+    #[inline]
+    pub fn mesh_reserve(&self) -> Option<MeshHnd> {
+        self.meshes.reserve()
+    }
+
+    /// `None` if all `Settings::tex_count_max` handles are in use.
+    #[inline]
+    pub fn tex_reserve(&self) -> Option<TexHnd> {
+        self.texs.reserve()
+    }
+
+    #[inline]
+    pub fn mesh_upload(&self, hnd: MeshHnd, vtxs: Vec<Vtx>, idxs: Vec<u32>) {
+        self.send(Upload::Mesh { hnd, vtxs, idxs });
+    }
+
+    /// `texels` covers a whole `Settings::tex_dim` square layer.
+    #[inline]
+    pub fn tex_upload(&self, hnd: TexHnd, texels: Vec<Rgba8>) {
+        self.send(Upload::Tex { hnd, texels });
+    }
+
+    #[inline]
+    fn send(&self, upload: Upload) {
+        if self.tx.send(upload).is_err() {
+            log::warn!("Dropping upload, the renderer is gone");
+        }
+    }
+}
+
 pub struct PassSettings<'a> {
     pub target: Target,
     pub camera: &'a Camera,
@@ -93,9 +283,15 @@ impl<'a> Pass<'a> {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Proj {
-    Ortho {
-        size: UV2,
+    /// Pixel-space orthographic projection with the origin at the top-left.
+    Ortho { size: UV2, near: f32, far: f32 },
+    OrthoOffCenter {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
         near: f32,
         far: f32,
     },
@@ -105,39 +301,131 @@ pub enum Proj {
         near: f32,
         far: f32,
     },
+    /// Perspective projection with the far plane at infinity.
+    PerspInfinite { fov: f32, ratio: f32, near: f32 },
+    /// Off-center perspective projection. The edges are given on the near
+    /// plane.
+    Frustum {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+impl Proj {
+    /// Projection into a reversed `[0, 1]` depth range (near at `1.0`, far at
+    /// `0.0`). Used by the backend when `Settings::reverse_z` is enabled.
+    #[inline]
+    pub fn reverse_z(&self) -> Mat4 {
+        self.to_mat4(true)
+    }
+
+    fn to_mat4(self, reverse_z: bool) -> Mat4 {
+        match self {
+            Proj::Ortho { size, near, far } => {
+                let V2([w, h]) = size.into();
+                Proj::OrthoOffCenter {
+                    left: 0.0,
+                    right: w,
+                    bottom: h,
+                    top: 0.0,
+                    near,
+                    far,
+                }
+                .to_mat4(reverse_z)
+            }
+            Proj::OrthoOffCenter {
+                left,
+                right,
+                bottom,
+                top,
+                near,
+                far,
+            } => {
+                let width = right - left;
+                let height = top - bottom;
+                let depth = far - near;
+                let (zz, zw) = if reverse_z {
+                    (1.0 / depth, far / depth)
+                } else {
+                    (-2.0 / depth, -(far + near) / depth)
+                };
+                Mat4([
+                    V4([2.0 / width, 0.0, 0.0, 0.0]),
+                    V4([0.0, 2.0 / height, 0.0, 0.0]),
+                    V4([0.0, 0.0, zz, 0.0]),
+                    V4([-(right + left) / width, -(top + bottom) / height, zw, 1.0]),
+                ])
+            }
+            Proj::Persp {
+                fov,
+                ratio,
+                near,
+                far,
+            } => {
+                let top = near * (fov * 0.5).tan();
+                let right = top * ratio;
+                Proj::Frustum {
+                    left: -right,
+                    right,
+                    bottom: -top,
+                    top,
+                    near,
+                    far,
+                }
+                .to_mat4(reverse_z)
+            }
+            Proj::PerspInfinite { fov, ratio, near } => {
+                let tan_half_fov = (fov * 0.5).tan();
+                let (zz, zw) = if reverse_z {
+                    (0.0, near)
+                } else {
+                    (-1.0, -2.0 * near)
+                };
+                Mat4([
+                    V4([1.0 / (tan_half_fov * ratio), 0.0, 0.0, 0.0]),
+                    V4([0.0, 1.0 / tan_half_fov, 0.0, 0.0]),
+                    V4([0.0, 0.0, zz, -1.0]),
+                    V4([0.0, 0.0, zw, 0.0]),
+                ])
+            }
+            Proj::Frustum {
+                left,
+                right,
+                bottom,
+                top,
+                near,
+                far,
+            } => {
+                let width = right - left;
+                let height = top - bottom;
+                let depth = far - near;
+                let (zz, zw) = if reverse_z {
+                    (near / depth, (far * near) / depth)
+                } else {
+                    (-(far + near) / depth, -(2.0 * far * near) / depth)
+                };
+                Mat4([
+                    V4([(2.0 * near) / width, 0.0, 0.0, 0.0]),
+                    V4([0.0, (2.0 * near) / height, 0.0, 0.0]),
+                    V4([(right + left) / width, (top + bottom) / height, zz, -1.0]),
+                    V4([0.0, 0.0, zw, 0.0]),
+                ])
+            }
+        }
+    }
 }
 
 macro_rules! from_proj_impl {
     ($proj:ty) => {
         impl From<$proj> for Mat4 {
+            /// Projection into the GL `[-1, 1]` depth range.
             #[inline]
             fn from(proj: $proj) -> Self {
-                match proj {
-                    Proj::Ortho { size, near, far } => {
-                        let depth = far - near;
-                        Mat4([
-                            V4([2.0 / (size.0[0] as f32), 0.0, 0.0, 0.0]),
-                            V4([0.0, -2.0 / (size.0[1] as f32), 0.0, 0.0]),
-                            V4([0.0, 0.0, 2.0 / depth, 0.0]),
-                            V4([-1.0, 1.0, 0.0, 1.0]),
-                        ])
-                    }
-                    Proj::Persp {
-                        fov,
-                        ratio,
-                        near,
-                        far,
-                    } => {
-                        let depth = far - near;
-                        let tan_half_fov = (fov * 0.5).tan();
-                        Mat4([
-                            V4([1.0 / (tan_half_fov * ratio), 0.0, 0.0, 0.0]),
-                            V4([0.0, 1.0 / tan_half_fov, 0.0, 0.0]),
-                            V4([0.0, 0.0, -(far + near) / depth, -1.0]),
-                            V4([0.0, 0.0, -(2.0 * far * near) / depth, 0.0]),
-                        ])
-                    }
-                }
+                proj.to_mat4(false)
             }
         }
     };
@@ -146,16 +434,78 @@ macro_rules! from_proj_impl {
 from_proj_impl!(Proj);
 from_proj_impl!(&Proj);
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Camera {
     pub pos: V3,
     pub at: V3,
     pub proj: Proj,
 }
 
+impl Camera {
+    pub fn view(&self) -> Mat4 {
+        let forward = (self.at - self.pos).normalized();
+        let backward = -forward;
+        let right = forward.cross(V3::UP).normalized();
+        let up = right.cross(forward);
+        Mat4([
+            V4([right.0[0], up.0[0], backward.0[0], 0.0]),
+            V4([right.0[1], up.0[1], backward.0[1], 0.0]),
+            V4([right.0[2], up.0[2], backward.0[2], 0.0]),
+            V4([
+                -right.dot(self.pos),
+                -up.dot(self.pos),
+                forward.dot(self.pos),
+                1.0,
+            ]),
+        ])
+    }
+
+    #[inline]
+    pub fn view_proj(&self) -> Mat4 {
+        Mat4::from(self.proj) * self.view()
+    }
+
+    /// World-space ray through a pixel (origin at the top-left of the
+    /// screen). The ray starts on the near plane. `None` if the view or
+    /// projection is degenerate (e.g. `pos == at`).
+    pub fn screen_to_world_ray(&self, pixel: V2, screen_size: UV2) -> Option<Ray> {
+        let V2([w, h]) = screen_size.into();
+        let V2([px, py]) = pixel;
+        let x = ((2.0 * px) / w) - 1.0;
+        let y = 1.0 - ((2.0 * py) / h);
+        let inv = self.view_proj().inverse()?;
+        let near = inv.project_point(V3([x, y, -1.0]));
+        // depth 0 stays finite for infinite projections, unlike 1
+        let mid = inv.project_point(V3([x, y, 0.0]));
+        let dir = (mid - near).normalized();
+        if !(near.0.iter().chain(&dir.0)).all(|c| c.is_finite()) {
+            return None;
+        }
+        Some(Ray { origin: near, dir })
+    }
+
+    /// Pixel position of a world-space point (origin at the top-left of the
+    /// screen), or `None` if it is behind the camera.
+    pub fn world_to_screen(&self, point: V3, screen_size: UV2) -> Option<V2> {
+        let (clip, w) = (self.view_proj() * point.extended(1.0)).narrowed();
+        if w <= 0.0 {
+            return None;
+        }
+        let V2([sw, sh]) = screen_size.into();
+        let V3([x, y, _]) = clip / w;
+        Some(V2([(x + 1.0) * 0.5 * sw, (1.0 - y) * 0.5 * sh]))
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Drawable {
     None,
-    Mesh { hnd: u32, tex: u32, blend: V4 },
+    Mesh {
+        hnd: MeshHnd,
+        tex: TexHnd,
+        blend: Color,
+    },
 }
 
 impl Drawable {
@@ -170,6 +520,40 @@ impl Drawable {
     }
 }
 
+impl Encode for Drawable {
+    fn encode(&self, w: &mut Writer) {
+        match self {
+            Drawable::None => w.u8(0),
+            Drawable::Mesh { hnd, tex, blend } => {
+                w.u8(1);
+                hnd.encode(w);
+                tex.encode(w);
+                blend.encode(w);
+            }
+        }
+    }
+}
+
+impl Decode for Drawable {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        match r.u8()? {
+            0 => Ok(Drawable::None),
+            // version 1 stored bare slot indices
+            1 if r.version() < 2 => Ok(Drawable::Mesh {
+                hnd: Handle::new(r.u32()?, 1),
+                tex: Handle::new(r.u32()?, 1),
+                blend: Decode::decode(r)?,
+            }),
+            1 => Ok(Drawable::Mesh {
+                hnd: Decode::decode(r)?,
+                tex: Decode::decode(r)?,
+                blend: Decode::decode(r)?,
+            }),
+            _ => Err(DecodeError::Invalid("drawable")),
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Default, Pod, Zeroable)]
 pub struct Vtx {
@@ -177,7 +561,7 @@ pub struct Vtx {
     pub tx: f32,
     pub norm: V3,
     pub ty: f32,
-    pub color: V4,
+    pub color: Color,
 }
 
 pub enum Target {
@@ -186,6 +570,7 @@ pub enum Target {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Settings {
     pub screen_size: UV2,
 
@@ -193,4 +578,290 @@ pub struct Settings {
     pub idx_buffer_size: usize,
     pub tex_dim: usize,
     pub tex_count: usize,
+
+    /// Use a reversed `[0, 1]` depth buffer (see `Proj::reverse_z`) for better
+    /// precision on large scenes.
+    pub reverse_z: bool,
+    /// Store textures as sRGB, decoded to linear when sampled, and encode the
+    /// framebuffer to sRGB on output, so shaders blend in linear space. When
+    /// off (and for streams from before version 7) texels and output pass
+    /// through as they are. The framebuffer must be sRGB-capable.
+    pub srgb: bool,
+
+    pub vtx_buffer_alloc: BufAlloc,
+    pub idx_buffer_alloc: BufAlloc,
+
+    /// How full pools grow. Each pool stops at its `*_max`, and running out
+    /// beyond that is fatal.
+    pub growth: Growth,
+    pub vtx_buffer_max: usize,
+    pub idx_buffer_max: usize,
+    pub tex_count_max: usize,
+    /// Live and reserved meshes.
+    pub mesh_count_max: usize,
+
+    pub budgets: Budgets,
+    /// Instance stores, one per mesh batch drawn in a pass.
+    pub store_count: usize,
+    pub store_count_max: usize,
+}
+
+impl Encode for Settings {
+    fn encode(&self, w: &mut Writer) {
+        self.screen_size.encode(w);
+        self.vtx_buffer_size.encode(w);
+        self.idx_buffer_size.encode(w);
+        self.tex_dim.encode(w);
+        self.tex_count.encode(w);
+        self.reverse_z.encode(w);
+        self.vtx_buffer_alloc.encode(w);
+        self.idx_buffer_alloc.encode(w);
+        self.growth.encode(w);
+        self.vtx_buffer_max.encode(w);
+        self.idx_buffer_max.encode(w);
+        self.tex_count_max.encode(w);
+        self.store_count.encode(w);
+        self.store_count_max.encode(w);
+        self.mesh_count_max.encode(w);
+        self.budgets.encode(w);
+        self.srgb.encode(w);
+    }
+}
+
+impl Decode for Settings {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let mut settings = Self {
+            screen_size: Decode::decode(r)?,
+            vtx_buffer_size: Decode::decode(r)?,
+            idx_buffer_size: Decode::decode(r)?,
+            tex_dim: Decode::decode(r)?,
+            tex_count: Decode::decode(r)?,
+            reverse_z: Decode::decode(r)?,
+            vtx_buffer_alloc: BufAlloc::default(),
+            idx_buffer_alloc: BufAlloc::default(),
+            growth: Growth::Fixed,
+            vtx_buffer_max: 0,
+            idx_buffer_max: 0,
+            tex_count_max: 0,
+            store_count: 512,
+            store_count_max: 0,
+            mesh_count_max: 65536,
+            budgets: Budgets::default(),
+            srgb: false,
+        };
+        // added in version 3
+        if r.version() >= 3 {
+            settings.vtx_buffer_alloc = Decode::decode(r)?;
+            settings.idx_buffer_alloc = Decode::decode(r)?;
+        }
+        // added in version 4, older streams keep their fixed sizes
+        if r.version() >= 4 {
+            settings.growth = Decode::decode(r)?;
+            settings.vtx_buffer_max = Decode::decode(r)?;
+            settings.idx_buffer_max = Decode::decode(r)?;
+            settings.tex_count_max = Decode::decode(r)?;
+            settings.store_count = Decode::decode(r)?;
+            settings.store_count_max = Decode::decode(r)?;
+        }
+        // added in version 5
+        if r.version() >= 5 {
+            settings.mesh_count_max = Decode::decode(r)?;
+        }
+        // added in version 6
+        if r.version() >= 6 {
+            settings.budgets = Decode::decode(r)?;
+        }
+        // added in version 7
+        if r.version() >= 7 {
+            settings.srgb = Decode::decode(r)?;
+        }
+        Ok(settings)
+    }
+}
+
+/// Soft limits on the bytes used by each pool. A pool logs a warning the
+/// first time it goes over its budget, `Gfx::memory_report` has the rest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Budgets {
+    pub vtx: Option<usize>,
+    pub idx: Option<usize>,
+    pub tex: Option<usize>,
+    pub store: Option<usize>,
+}
+
+impl Encode for Budgets {
+    fn encode(&self, w: &mut Writer) {
+        self.vtx.encode(w);
+        self.idx.encode(w);
+        self.tex.encode(w);
+        self.store.encode(w);
+    }
+}
+
+impl Decode for Budgets {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Self {
+            vtx: Decode::decode(r)?,
+            idx: Decode::decode(r)?,
+            tex: Decode::decode(r)?,
+            store: Decode::decode(r)?,
+        })
+    }
+}
+
+/// How a pool grows when it runs out of space (see `Settings::growth`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Growth {
+    /// Pools keep their initial size.
+    #[default]
+    Fixed,
+    /// Pools grow by this factor, or by as much as the request needs if
+    /// that is more.
+    Factor(f32),
+}
+
+impl Growth {
+    /// Next size of a pool of `size` that needs room for `needed` more,
+    /// clamped to `max`. `None` if the pool can't grow enough.
+    pub fn next_size(self, size: usize, needed: usize, max: usize) -> Option<usize> {
+        let Growth::Factor(factor) = self else {
+            return None;
+        };
+        let min = size.checked_add(needed)?;
+        if min > max {
+            return None;
+        }
+        let grown = (size as f64 * factor as f64) as usize;
+        Some(grown.clamp(min, max))
+    }
+}
+
+impl Encode for Growth {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        match *self {
+            Growth::Fixed => w.u8(0),
+            Growth::Factor(factor) => {
+                w.u8(1);
+                factor.encode(w);
+            }
+        }
+    }
+}
+
+impl Decode for Growth {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        match r.u8()? {
+            0 => Ok(Growth::Fixed),
+            1 => Ok(Growth::Factor(Decode::decode(r)?)),
+            _ => Err(DecodeError::Invalid("pool growth")),
+        }
+    }
+}
+
+/// Suballocation strategy for a GPU buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BufAlloc {
+    /// Power-of-two blocks (`mem::MetaAllocator`). Splits and merges
+    /// cleanly, but wastes up to half of every allocation.
+    #[default]
+    Buddy,
+    /// Two-level segregated fit with 16 byte granularity
+    /// (`mem::TlsfAllocator`). Little waste on odd sizes.
+    Tlsf,
+}
+
+impl Encode for BufAlloc {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        w.u8(*self as u8);
+    }
+}
+
+impl Decode for BufAlloc {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        match r.u8()? {
+            0 => Ok(BufAlloc::Buddy),
+            1 => Ok(BufAlloc::Tlsf),
+            _ => Err(DecodeError::Invalid("buffer allocator")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec;
+
+    const PERSP: Proj = Proj::Persp {
+        fov: 1.0,
+        ratio: 1.0,
+        near: 0.1,
+        far: 100.0,
+    };
+
+    #[test]
+    fn screen_to_world_ray_through_the_centre_looks_at_the_target() {
+        let camera = Camera {
+            pos: V3([0.0, 0.0, 5.0]),
+            at: V3::splat(0.0),
+            proj: PERSP,
+        };
+        let ray = camera
+            .screen_to_world_ray(V2([50.0, 50.0]), UV2([100, 100]))
+            .unwrap();
+        assert!((ray.dir - V3::BACKWARD).length() < 1.0e-4, "{ray:?}");
+    }
+
+    #[test]
+    fn screen_to_world_ray_of_degenerate_camera_is_none() {
+        let camera = Camera {
+            pos: V3::splat(1.0),
+            at: V3::splat(1.0),
+            proj: PERSP,
+        };
+        assert!(
+            camera
+                .screen_to_world_ray(V2([50.0, 50.0]), UV2([100, 100]))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn version_1_drawables_decode_bare_indices() {
+        let mut bytes = codec::MAGIC.to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bytes.push(1);
+        bytes.extend(7u32.to_le_bytes());
+        bytes.extend(3u32.to_le_bytes());
+        for channel in [1.0f32, 0.5, 0.25, 1.0] {
+            bytes.extend(channel.to_bits().to_le_bytes());
+        }
+        let Ok(Drawable::Mesh { hnd, tex, blend }) = codec::from_bytes(&bytes) else {
+            panic!("not a mesh");
+        };
+        assert_eq!((hnd.idx(), hnd.generation()), (7, 1));
+        assert_eq!((tex.idx(), tex.generation()), (3, 1));
+        assert_eq!(blend.0.0, [1.0, 0.5, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn drawables_keep_handle_generations() {
+        let drawable = Drawable::Mesh {
+            hnd: Handle::new(7, 5),
+            tex: Handle::new(3, 9),
+            blend: Color::default(),
+        };
+        let Ok(Drawable::Mesh { hnd, tex, .. }) = codec::from_bytes(&codec::to_bytes(&drawable))
+        else {
+            panic!("not a mesh");
+        };
+        assert_eq!((hnd.idx(), hnd.generation()), (7, 5));
+        assert_eq!((tex.idx(), tex.generation()), (3, 9));
+    }
 }
@@ -0,0 +1,795 @@
+//! 2D polygon filling and stroking, output as meshes on the `z = 0` plane.
+//!
+//! Triangles wind counter-clockwise with `y` pointing down, as in the pixel
+//! space of `Proj::Ortho`, and normals face `+Z` towards its camera. `tx` and
+//! `ty` are left at zero, so draw them with a solid texture.
+
+use std::f32::consts::PI;
+
+use super::Vtx;
+use crate::math::{Color, CubicBezier, Curve, Dot, V2, V3};
+
+/// Ear-clipping triangulation of a simple polygon with holes. Either winding
+/// is accepted for any ring. Indices refer to `outer` followed by each hole
+/// in turn.
+pub fn triangulate(outer: &[V2], holes: &[&[V2]]) -> Vec<u32> {
+    let mut points = outer.to_vec();
+    let mut ring: Vec<u32> = (0..outer.len() as u32).collect();
+    if signed_area(outer) < 0.0 {
+        ring.reverse();
+    }
+
+    // holes are bridged into the outer ring rightmost first, which makes
+    // the ring a single (weakly) simple polygon
+    let mut hole_rings = Vec::with_capacity(holes.len());
+    for hole in holes {
+        let base = points.len() as u32;
+        points.extend_from_slice(hole);
+        if hole.len() < 3 {
+            continue;
+        }
+        let mut hole_ring: Vec<u32> = (base..base + hole.len() as u32).collect();
+        if signed_area(hole) > 0.0 {
+            hole_ring.reverse();
+        }
+        let right = (0..hole_ring.len())
+            .max_by(|&a, &b| {
+                let (pa, pb) = (points[hole_ring[a] as usize], points[hole_ring[b] as usize]);
+                pa.0[0]
+                    .total_cmp(&pb.0[0])
+                    .then(pb.0[1].total_cmp(&pa.0[1]))
+            })
+            .unwrap();
+        hole_ring.rotate_left(right);
+        hole_rings.push(hole_ring);
+    }
+    hole_rings.sort_by(|a, b| {
+        let (pa, pb) = (points[a[0] as usize], points[b[0] as usize]);
+        pb.0[0].total_cmp(&pa.0[0])
+    });
+    for hole_ring in hole_rings {
+        let Some(at) = bridge(&points, &ring, points[hole_ring[0] as usize]) else {
+            log::warn!("Could not bridge polygon hole, skipping it");
+            continue;
+        };
+        let join = [ring[at]];
+        ring.splice(
+            at + 1..at + 1,
+            hole_ring
+                .iter()
+                .chain(&hole_ring[..1])
+                .chain(&join)
+                .copied(),
+        );
+    }
+
+    let mut idxs = Vec::with_capacity(ring.len().saturating_sub(2) * 3);
+    clip_ears(&points, &ring, &mut idxs);
+    idxs
+}
+
+/// Fill a polygon with holes, see `triangulate`.
+pub fn fill(outer: &[V2], holes: &[&[V2]], color: Color) -> (Vec<Vtx>, Vec<u32>) {
+    let mut b = Builder::new(color);
+    b.fill(outer, holes);
+    b.finish()
+}
+
+/// Stroke a polyline, connecting the last point back to the first when
+/// `closed`.
+pub fn stroke(points: &[V2], closed: bool, style: &StrokeStyle) -> (Vec<Vtx>, Vec<u32>) {
+    let mut b = Builder::new(style.color);
+    b.stroke(points, closed, style);
+    b.finish()
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Join {
+    /// Sharp corners, beveled once the miter is longer than `limit` times
+    /// the half width.
+    Miter(f32),
+    Bevel,
+    Round,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Cap {
+    Butt,
+    /// Extends the ends by half the width.
+    Square,
+    Round,
+}
+
+/// Segments of a stroke overlap on the inner side of joins, which shows
+/// when drawn with translucent colors.
+#[derive(Clone, Copy, Debug)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub join: Join,
+    pub cap: Cap,
+    pub color: Color,
+    /// Maximum distance of round joins and caps from the true arc.
+    pub tolerance: f32,
+}
+
+impl Default for StrokeStyle {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            join: Join::Miter(4.0),
+            cap: Cap::Butt,
+            color: Color::WHITE,
+            tolerance: 0.25,
+        }
+    }
+}
+
+/// Vector path of lines and Bezier curves, flattened into polylines as it
+/// is built.
+#[derive(Clone, Debug)]
+pub struct Path {
+    points: Vec<V2>,
+    /// Start of each contour in `points` and whether it is closed.
+    contours: Vec<(usize, bool)>,
+    tolerance: f32,
+}
+
+impl Path {
+    /// Curves are flattened to within `tolerance` of the true curve.
+    /// `tolerance` is raised to at least `1e-3`, so curves always end up
+    /// with a finite number of points.
+    #[inline]
+    pub fn new(tolerance: f32) -> Self {
+        Self {
+            points: Vec::new(),
+            contours: Vec::new(),
+            tolerance: tolerance.max(1e-3),
+        }
+    }
+
+    #[inline]
+    pub fn move_to(&mut self, point: V2) -> &mut Self {
+        self.contours.push((self.points.len(), false));
+        self.points.push(point);
+        self
+    }
+
+    #[inline]
+    pub fn line_to(&mut self, point: V2) -> &mut Self {
+        self.current();
+        self.points.push(point);
+        self
+    }
+
+    #[inline]
+    pub fn quad_to(&mut self, ctrl: V2, point: V2) -> &mut Self {
+        let from = self.current();
+        self.cubic_to(
+            from + (ctrl - from) * (2.0 / 3.0),
+            point + (ctrl - point) * (2.0 / 3.0),
+            point,
+        )
+    }
+
+    pub fn cubic_to(&mut self, ctrl1: V2, ctrl2: V2, point: V2) -> &mut Self {
+        let curve = CubicBezier {
+            p0: self.current(),
+            p1: ctrl1,
+            p2: ctrl2,
+            p3: point,
+        };
+        // Wang's formula for the number of segments
+        let dd = (curve.p0 - curve.p1 * 2.0 + curve.p2)
+            .length()
+            .max((curve.p1 - curve.p2 * 2.0 + curve.p3).length());
+        let segments = (0.75 * dd / self.tolerance).sqrt().ceil().max(1.0) as u32;
+        for i in 1..segments {
+            self.points.push(curve.eval(i as f32 / segments as f32));
+        }
+        self.points.push(point);
+        self
+    }
+
+    /// Close the current contour. Drawing continues from its first point.
+    #[inline]
+    pub fn close(&mut self) -> &mut Self {
+        if let Some((_, closed)) = self.contours.last_mut() {
+            *closed = true;
+        }
+        self
+    }
+
+    /// Flattened contours and whether each is closed.
+    pub fn contours(&self) -> impl Iterator<Item = (&[V2], bool)> {
+        self.contours
+            .iter()
+            .enumerate()
+            .map(|(i, &(start, closed))| {
+                let end = self
+                    .contours
+                    .get(i + 1)
+                    .map_or(self.points.len(), |next| next.0);
+                (&self.points[start..end], closed)
+            })
+    }
+
+    /// Fill all contours, closing open ones. Contours wound the same way as
+    /// the first are outlines, the others are holes in whichever outline
+    /// contains them.
+    pub fn fill(&self, color: Color) -> (Vec<Vtx>, Vec<u32>) {
+        let contours: Vec<&[V2]> = self
+            .contours()
+            .map(|(points, _)| points)
+            .filter(|points| points.len() >= 3)
+            .collect();
+        let Some(sign) = contours
+            .iter()
+            .map(|points| signed_area(points))
+            .find(|area| *area != 0.0)
+            .map(f32::signum)
+        else {
+            return (Vec::new(), Vec::new());
+        };
+        let (outers, holes): (Vec<&[V2]>, Vec<&[V2]>) = contours
+            .into_iter()
+            .partition(|points| signed_area(points) * sign >= 0.0);
+        let mut owned = vec![Vec::new(); outers.len()];
+        for hole in holes {
+            // prefer the smallest outline, for outlines nested in holes
+            let owner = (0..outers.len())
+                .filter(|&i| contains(outers[i], hole[0]))
+                .min_by(|&a, &b| {
+                    signed_area(outers[a])
+                        .abs()
+                        .total_cmp(&signed_area(outers[b]).abs())
+                });
+            match owner {
+                Some(i) => owned[i].push(hole),
+                None => log::warn!("Path hole is outside every outline, skipping it"),
+            }
+        }
+        let mut b = Builder::new(color);
+        for (outer, holes) in outers.iter().zip(&owned) {
+            b.fill(outer, holes);
+        }
+        b.finish()
+    }
+
+    pub fn stroke(&self, style: &StrokeStyle) -> (Vec<Vtx>, Vec<u32>) {
+        let mut b = Builder::new(style.color);
+        for (points, closed) in self.contours() {
+            b.stroke(points, closed, style);
+        }
+        b.finish()
+    }
+
+    /// End point of the current contour, starting a new one from the
+    /// previous closed contour's first point (or the origin) if needed.
+    fn current(&mut self) -> V2 {
+        match self.contours.last() {
+            Some(&(_, false)) => *self.points.last().unwrap(),
+            Some(&(start, true)) => {
+                let point = self.points[start];
+                self.move_to(point);
+                point
+            }
+            None => {
+                self.move_to(V2::splat(0.0));
+                V2::splat(0.0)
+            }
+        }
+    }
+}
+
+/// Twice the signed area, positive for counter-clockwise winding with `y`
+/// pointing up.
+#[inline]
+fn signed_area(points: &[V2]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        area += cross(*a, points[(i + 1) % points.len()]);
+    }
+    area
+}
+
+#[inline]
+fn cross(a: V2, b: V2) -> f32 {
+    a.0[0] * b.0[1] - a.0[1] * b.0[0]
+}
+
+/// Positive when `c` is left of the line from `a` to `b` (with `y` up).
+#[inline]
+fn orient(a: V2, b: V2, c: V2) -> f32 {
+    cross(b - a, c - a)
+}
+
+/// Even-odd point in polygon test.
+fn contains(points: &[V2], point: V2) -> bool {
+    let V2([x, y]) = point;
+    let mut inside = false;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        let (V2([ax, ay]), V2([bx, by])) = (*a, b);
+        if (ay > y) != (by > y) && x < ax + (y - ay) * (bx - ax) / (by - ay) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[inline]
+fn in_triangle(a: V2, b: V2, c: V2, p: V2) -> bool {
+    orient(a, b, p) >= 0.0 && orient(b, c, p) >= 0.0 && orient(c, a, p) >= 0.0
+}
+
+/// Whether `point` lies in the interior angle of the ring at `at`.
+fn locally_inside(points: &[V2], ring: &[u32], at: usize, point: V2) -> bool {
+    let n = ring.len();
+    let a = points[ring[(at + n - 1) % n] as usize];
+    let p = points[ring[at] as usize];
+    let b = points[ring[(at + 1) % n] as usize];
+    if orient(a, p, b) >= 0.0 {
+        orient(a, p, point) >= 0.0 && orient(p, b, point) >= 0.0
+    } else {
+        orient(a, p, point) >= 0.0 || orient(p, b, point) >= 0.0
+    }
+}
+
+/// Position in the counter-clockwise `ring` of a vertex visible from
+/// `hole`, a point inside it, after David Eberly's "Triangulation by Ear
+/// Clipping".
+fn bridge(points: &[V2], ring: &[u32], hole: V2) -> Option<usize> {
+    let V2([hx, hy]) = hole;
+    let n = ring.len();
+
+    // closest edge hit by a ray towards +X, only upward edges can be hit
+    // from the inside of a counter-clockwise ring
+    let mut hit: Option<(f32, usize)> = None;
+    for i in 0..n {
+        let (a, b) = (points[ring[i] as usize], points[ring[(i + 1) % n] as usize]);
+        let (V2([ax, ay]), V2([bx, by])) = (a, b);
+        if !(ay <= hy && hy <= by && ay < by) {
+            continue;
+        }
+        let x = ax + (hy - ay) * (bx - ax) / (by - ay);
+        if x >= hx && hit.is_none_or(|(hit_x, _)| x < hit_x) {
+            // candidate is the edge's endpoint furthest along the ray
+            let end = if ax > bx { i } else { (i + 1) % n };
+            hit = Some((x, end));
+        }
+    }
+    let (hit_x, end) = hit?;
+    let hit = V2([hit_x, hy]);
+    let candidate = points[ring[end] as usize];
+    if candidate.0 == hit.0 {
+        return pick(points, ring, candidate, hole);
+    }
+
+    // reflex vertices inside the triangle formed with the hit may block
+    // the candidate, the one with the smallest angle to the ray is visible
+    let (t0, t1) = if hit.0[1] < candidate.0[1] {
+        (hit, candidate)
+    } else {
+        (candidate, hit)
+    };
+    let mut best = candidate;
+    let mut best_key = (f32::INFINITY, f32::INFINITY);
+    for i in 0..n {
+        let p = points[ring[i] as usize];
+        let prev = points[ring[(i + n - 1) % n] as usize];
+        let next = points[ring[(i + 1) % n] as usize];
+        if orient(prev, p, next) >= 0.0 || p.0 == hole.0 || !in_triangle(hole, t0, t1, p) {
+            continue;
+        }
+        let d = p - hole;
+        let key = ((d.0[1] / d.length()).abs(), d.length());
+        if key < best_key {
+            best_key = key;
+            best = p;
+        }
+    }
+    pick(points, ring, best, hole)
+}
+
+/// The occurrence of `point` in `ring` that `hole` lies in the interior
+/// angle of, since earlier bridges duplicate vertices.
+fn pick(points: &[V2], ring: &[u32], point: V2, hole: V2) -> Option<usize> {
+    let mut found = None;
+    for (i, &idx) in ring.iter().enumerate() {
+        if points[idx as usize].0 != point.0 {
+            continue;
+        }
+        if locally_inside(points, ring, i, hole) {
+            return Some(i);
+        }
+        found.get_or_insert(i);
+    }
+    found
+}
+
+fn clip_ears(points: &[V2], ring: &[u32], idxs: &mut Vec<u32>) {
+    let n = ring.len();
+    if n < 3 {
+        return;
+    }
+    let mut prev: Vec<usize> = (0..n).map(|i| (i + n - 1) % n).collect();
+    let mut next: Vec<usize> = (0..n).map(|i| (i + 1) % n).collect();
+    let pos = |i: usize| points[ring[i] as usize];
+    let mut remaining = n;
+    let mut i = 0;
+    let mut stalled = 0;
+    while remaining > 3 {
+        let (a, c) = (prev[i], next[i]);
+        let (pa, pb, pc) = (pos(a), pos(i), pos(c));
+        let area = orient(pa, pb, pc);
+        let ear = area > 0.0 && {
+            let mut j = next[c];
+            let mut clear = true;
+            while j != a {
+                let p = pos(j);
+                // skip bridge duplicates of the triangle's own corners
+                if p.0 != pa.0 && p.0 != pb.0 && p.0 != pc.0 && in_triangle(pa, pb, pc, p) {
+                    clear = false;
+                    break;
+                }
+                j = next[j];
+            }
+            clear
+        };
+        let degenerate = area == 0.0;
+        if !ear && !degenerate && stalled <= remaining {
+            i = c;
+            stalled += 1;
+            continue;
+        }
+        if stalled > remaining {
+            log::warn!("Polygon is not simple, triangulation may overlap");
+        }
+        if !degenerate {
+            idxs.extend([ring[a], ring[i], ring[c]]);
+        }
+        next[a] = c;
+        prev[c] = a;
+        remaining -= 1;
+        i = c;
+        stalled = 0;
+    }
+    if orient(pos(prev[i]), pos(i), pos(next[i])) != 0.0 {
+        idxs.extend([ring[prev[i]], ring[i], ring[next[i]]]);
+    }
+    // counter-clockwise with `y` up is clockwise with `y` down
+    for tri in idxs.chunks_exact_mut(3) {
+        tri.swap(1, 2);
+    }
+}
+
+struct Builder {
+    verts: Vec<Vtx>,
+    idxs: Vec<u32>,
+    color: Color,
+}
+
+impl Builder {
+    #[inline]
+    fn new(color: Color) -> Self {
+        Self {
+            verts: Vec::new(),
+            idxs: Vec::new(),
+            color,
+        }
+    }
+
+    #[inline]
+    fn vtx(&mut self, pos: V2) -> u32 {
+        self.verts.push(Vtx {
+            pos: V3([pos.0[0], pos.0[1], 0.0]),
+            norm: V3::FORWARD,
+            color: self.color,
+            ..Default::default()
+        });
+        self.verts.len() as u32 - 1
+    }
+
+    /// Triangle wound counter-clockwise with `y` down, whatever the order.
+    #[inline]
+    fn tri(&mut self, a: V2, b: V2, c: V2) {
+        let area = orient(a, b, c);
+        if area == 0.0 {
+            return;
+        }
+        let (a, b, c) = (self.vtx(a), self.vtx(b), self.vtx(c));
+        if area > 0.0 {
+            self.idxs.extend([a, c, b]);
+        } else {
+            self.idxs.extend([a, b, c]);
+        }
+    }
+
+    fn fill(&mut self, outer: &[V2], holes: &[&[V2]]) {
+        let base = self.verts.len() as u32;
+        for point in outer
+            .iter()
+            .chain(holes.iter().flat_map(|hole| hole.iter()))
+        {
+            self.vtx(*point);
+        }
+        let idxs = triangulate(outer, holes);
+        self.idxs.extend(idxs.into_iter().map(|idx| base + idx));
+    }
+
+    fn stroke(&mut self, points: &[V2], closed: bool, style: &StrokeStyle) {
+        let mut pts: Vec<V2> = Vec::with_capacity(points.len());
+        for point in points {
+            if pts.last().is_none_or(|last| last.0 != point.0) {
+                pts.push(*point);
+            }
+        }
+        if closed && pts.len() > 1 && pts[0].0 == pts[pts.len() - 1].0 {
+            pts.pop();
+        }
+        let hw = style.width * 0.5;
+        match pts.len() {
+            0 => return,
+            1 => {
+                let p = pts[0];
+                match style.cap {
+                    Cap::Butt => {}
+                    Cap::Square => {
+                        let (x, y) = (V2([hw, 0.0]), V2([0.0, hw]));
+                        self.tri(p - x - y, p + x - y, p + x + y);
+                        self.tri(p - x - y, p + x + y, p - x + y);
+                    }
+                    Cap::Round => self.arc(p, V2([hw, 0.0]), 2.0 * PI, style.tolerance),
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        let closed = closed && pts.len() > 2;
+        let n = pts.len();
+        let segments = if closed { n } else { n - 1 };
+        let dir = |k: usize| (pts[(k + 1) % n] - pts[k]).normalized();
+        let left = |d: V2| V2([-d.0[1], d.0[0]]);
+
+        for k in 0..segments {
+            let d = dir(k);
+            let off = left(d) * hw;
+            let (mut s, mut e) = (pts[k], pts[(k + 1) % n]);
+            if !closed && matches!(style.cap, Cap::Square) {
+                if k == 0 {
+                    s = s - d * hw;
+                }
+                if k == segments - 1 {
+                    e = e + d * hw;
+                }
+            }
+            self.tri(s + off, s - off, e - off);
+            self.tri(s + off, e - off, e + off);
+        }
+
+        let joins = if closed { 0..n } else { 1..n - 1 };
+        for k in joins {
+            let (d0, d1) = (dir((k + n - 1) % n), dir(k));
+            let turn = cross(d0, d1);
+            if turn == 0.0 && d0.dot(d1) > 0.0 {
+                continue;
+            }
+            // the gap to fill is on the outside of the turn
+            let side = if turn > 0.0 { -1.0 } else { 1.0 };
+            let (u0, u1) = (left(d0) * side, left(d1) * side);
+            let p = pts[k];
+            match style.join {
+                Join::Bevel => self.tri(p, p + u0 * hw, p + u1 * hw),
+                Join::Miter(limit) => {
+                    let mid = u0 + u1;
+                    let cos = mid.normalized().dot(u0);
+                    if mid.normal_squared() > 0.0 && cos > 0.0 && 1.0 / cos <= limit {
+                        let tip = p + mid.normalized() * (hw / cos);
+                        self.tri(p, p + u0 * hw, tip);
+                        self.tri(p, tip, p + u1 * hw);
+                    } else {
+                        self.tri(p, p + u0 * hw, p + u1 * hw);
+                    }
+                }
+                Join::Round => {
+                    let angle = cross(u0, u1).atan2(u0.dot(u1));
+                    self.arc(p, u0 * hw, angle, style.tolerance);
+                }
+            }
+        }
+
+        if !closed && matches!(style.cap, Cap::Round) {
+            let start = left(dir(0)) * hw;
+            self.arc(pts[0], start, PI, style.tolerance);
+            let end = -left(dir(segments - 1)) * hw;
+            self.arc(pts[n - 1], end, PI, style.tolerance);
+        }
+    }
+
+    /// Fan around `center` sweeping `from` (a radius vector) by `angle`
+    /// radians, counter-clockwise with `y` up.
+    fn arc(&mut self, center: V2, from: V2, angle: f32, tolerance: f32) {
+        let radius = from.length();
+        if radius == 0.0 {
+            return;
+        }
+        let step = 2.0 * (1.0 - (tolerance / radius).min(1.0)).acos();
+        let steps = (angle.abs() / step.max(1e-3)).ceil().max(1.0) as u32;
+        let mut last = center + from;
+        for i in 1..=steps {
+            let (sin, cos) = (angle * i as f32 / steps as f32).sin_cos();
+            let V2([x, y]) = from;
+            let next = center + V2([x * cos - y * sin, x * sin + y * cos]);
+            self.tri(center, last, next);
+            last = next;
+        }
+    }
+
+    #[inline]
+    fn finish(self) -> (Vec<Vtx>, Vec<u32>) {
+        (self.verts, self.idxs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xy(vtx: &Vtx) -> V2 {
+        V2([vtx.pos.0[0], vtx.pos.0[1]])
+    }
+
+    /// Total area of the triangles, checking they all wind counter-clockwise
+    /// with `y` down.
+    fn area(points: &[V2], idxs: &[u32]) -> f32 {
+        assert_eq!(idxs.len() % 3, 0);
+        idxs.chunks_exact(3)
+            .map(|tri| {
+                let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| points[i as usize]);
+                let twice = orient(a, b, c);
+                assert!(twice < 0.0, "{tri:?} winds the wrong way");
+                -0.5 * twice
+            })
+            .sum()
+    }
+
+    fn mesh_area((verts, idxs): &(Vec<Vtx>, Vec<u32>)) -> f32 {
+        area(&verts.iter().map(xy).collect::<Vec<_>>(), idxs)
+    }
+
+    fn covers(points: &[V2], idxs: &[u32], p: V2) -> usize {
+        idxs.chunks_exact(3)
+            .filter(|tri| {
+                let [a, b, c] = [tri[0], tri[2], tri[1]].map(|i| points[i as usize]);
+                in_triangle(a, b, c, p)
+            })
+            .count()
+    }
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> Vec<V2> {
+        vec![
+            V2([x, y]),
+            V2([x + w, y]),
+            V2([x + w, y + h]),
+            V2([x, y + h]),
+        ]
+    }
+
+    #[test]
+    fn triangulate_bridges_holes() {
+        let outer = rect(0.0, 0.0, 10.0, 10.0);
+        let small = rect(1.0, 1.0, 2.0, 2.0);
+        let mut tall = rect(6.0, 2.0, 1.0, 7.0);
+        tall.reverse();
+        for outer in [outer.clone(), outer.iter().rev().copied().collect()] {
+            let idxs = triangulate(&outer, &[&small, &tall]);
+            let points: Vec<V2> = outer.iter().chain(&small).chain(&tall).copied().collect();
+            // a ring of 12 points with two bridges
+            assert_eq!(idxs.len(), (12 + 2 * 2 - 2) * 3);
+            assert!((area(&points, &idxs) - 89.0).abs() < 1e-4);
+            for hole in [V2([2.0, 2.0]), V2([6.5, 5.0])] {
+                assert_eq!(covers(&points, &idxs, hole), 0);
+            }
+            for solid in [V2([4.5, 5.1]), V2([8.3, 8.7]), V2([0.5, 9.5])] {
+                assert_eq!(covers(&points, &idxs, solid), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn triangulate_clips_concave_polygons() {
+        // an arrow pointing down with a reflex vertex at the top, and a
+        // collinear point on the bottom edge
+        let arrow = [
+            V2([0.0, 0.0]),
+            V2([2.0, 2.0]),
+            V2([4.0, 0.0]),
+            V2([4.0, 4.0]),
+            V2([2.0, 4.0]),
+            V2([0.0, 4.0]),
+        ];
+        for ring in [arrow.to_vec(), arrow.iter().rev().copied().collect()] {
+            let idxs = triangulate(&ring, &[]);
+            assert_eq!(idxs.len(), 3 * 3);
+            assert!((area(&ring, &idxs) - 12.0).abs() < 1e-5);
+            assert_eq!(covers(&ring, &idxs, V2([2.0, 1.0])), 0);
+            assert_eq!(covers(&ring, &idxs, V2([0.7, 3.1])), 1);
+        }
+    }
+
+    #[test]
+    fn stroke_joins_fill_the_outside_of_the_turn() {
+        let corner = [V2([0.0, 0.0]), V2([10.0, 0.0]), V2([10.0, 10.0])];
+        // two 10 by 2 segments, plus whatever fills the corner
+        for (join, expected) in [
+            (Join::Bevel, 40.5),
+            (Join::Miter(4.0), 41.0),
+            (Join::Miter(1.2), 40.5),
+            (Join::Round, 40.0 + PI / 4.0),
+        ] {
+            let style = StrokeStyle {
+                width: 2.0,
+                join,
+                tolerance: 0.001,
+                ..Default::default()
+            };
+            let mesh = stroke(&corner, false, &style);
+            assert!((mesh_area(&mesh) - expected).abs() < 0.01, "{join:?}");
+            for vtx in &mesh.0 {
+                assert!(xy(vtx).0[0] <= 11.0 + 1e-5 && xy(vtx).0[1] >= -1.0 - 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn stroke_caps_extend_the_ends() {
+        let line = [V2([0.0, 0.0]), V2([10.0, 0.0])];
+        for (cap, expected, reach) in [
+            (Cap::Butt, 20.0, 0.0),
+            (Cap::Square, 24.0, 1.0),
+            (Cap::Round, 20.0 + PI, 1.0),
+        ] {
+            let style = StrokeStyle {
+                width: 2.0,
+                cap,
+                tolerance: 0.001,
+                ..Default::default()
+            };
+            let mesh = stroke(&line, false, &style);
+            assert!((mesh_area(&mesh) - expected).abs() < 0.01, "{cap:?}");
+            let xs = || mesh.0.iter().map(|vtx| vtx.pos.0[0]);
+            assert!(
+                (xs().fold(f32::MAX, f32::min) + reach).abs() < 1e-5,
+                "{cap:?}"
+            );
+            assert!((xs().fold(f32::MIN, f32::max) - 10.0 - reach).abs() < 1e-5);
+        }
+        // a lone point is just its caps
+        let dot = [V2([3.0, 3.0])];
+        for (cap, expected) in [(Cap::Butt, 0.0), (Cap::Square, 4.0), (Cap::Round, PI)] {
+            let style = StrokeStyle {
+                width: 2.0,
+                cap,
+                tolerance: 0.001,
+                ..Default::default()
+            };
+            assert!((mesh_area(&stroke(&dot, false, &style)) - expected).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn tolerance_is_clamped() {
+        for tolerance in [0.0, -1.0, f32::NAN] {
+            let mut path = Path::new(tolerance);
+            path.move_to(V2([0.0, 0.0])).cubic_to(
+                V2([0.0, 100.0]),
+                V2([100.0, 100.0]),
+                V2([100.0, 0.0]),
+            );
+            let (points, _) = path.contours().next().unwrap();
+            assert!(points.len() > 100 && points.len() < 10_000);
+        }
+    }
+}
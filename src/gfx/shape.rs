@@ -0,0 +1,511 @@
+//! Primitive mesh generators, ready for `Gfx::mesh_alloc` and `mesh_map`.
+//!
+//! Meshes are centered on the origin with `V3::UP` as their axis. Triangles
+//! wind counter-clockwise seen from outside. `tx` runs left to right and
+//! `ty` top to bottom of the texture, seen from outside with up (or `-Z`
+//! for faces pointing along the Y axis) at the top.
+
+use std::{
+    collections::HashMap,
+    f32::consts::{FRAC_PI_2, PI, TAU},
+};
+
+use super::Vtx;
+use crate::math::{Color, Cross, Dot, V2, V3};
+
+/// Flat plane facing `V3::UP`, `size` along X and Z.
+pub fn plane(size: V2, subdiv: u32) -> (Vec<Vtx>, Vec<u32>) {
+    let V2([w, d]) = size;
+    let mut b = Builder::default();
+    b.face(V3::splat(0.0), V3([w, 0.0, 0.0]), V3([0.0, 0.0, d]), subdiv);
+    b.finish()
+}
+
+/// Box with each face split into `subdiv * subdiv` quads. Faces do not share
+/// vertices, so normals are flat.
+pub fn cube(size: V3, subdiv: u32) -> (Vec<Vtx>, Vec<u32>) {
+    let V3([w, h, d]) = size * 0.5;
+    let mut b = Builder::default();
+    let (x, y, z) = (V3([w, 0.0, 0.0]), V3([0.0, h, 0.0]), V3([0.0, 0.0, d]));
+    for (normal, right, down) in [
+        (z, x, -y),
+        (-z, -x, -y),
+        (x, -z, -y),
+        (-x, z, -y),
+        (y, x, z),
+        (-y, x, -z),
+    ] {
+        b.face(normal, right * 2.0, down * 2.0, subdiv);
+    }
+    b.finish()
+}
+
+/// Sphere with `sectors` around the axis and `stacks` from pole to pole.
+pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> (Vec<Vtx>, Vec<u32>) {
+    let stacks = stacks.max(2);
+    let profile = (0..=stacks).map(|j| {
+        let phi = j as f32 / stacks as f32 * PI;
+        let norm = V2([phi.sin(), phi.cos()]);
+        (norm * radius, norm)
+    });
+    let mut b = Builder::default();
+    b.lathe(profile, sectors);
+    b.finish()
+}
+
+/// Sphere made by splitting each icosahedron face `subdiv` times, which
+/// spaces vertices more evenly than `uv_sphere`.
+pub fn icosphere(radius: f32, subdiv: u32) -> (Vec<Vtx>, Vec<u32>) {
+    let t = (1.0 + 5f32.sqrt()) * 0.5;
+    let mut points: Vec<V3> = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .into_iter()
+    .map(|p| V3(p).normalized())
+    .collect();
+    let mut tris: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdiv {
+        let mut mids = HashMap::new();
+        let mut mid = |a: u32, b: u32| {
+            *mids.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let p = (points[a as usize] + points[b as usize]).normalized();
+                points.push(p);
+                points.len() as u32 - 1
+            })
+        };
+        tris = tris
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (mid(a, b), mid(b, c), mid(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    // same mapping as `uv_sphere`, with the seam at `+Z`
+    let uv = |p: V3| {
+        let V3([x, y, z]) = p;
+        let u = x.atan2(z) / TAU;
+        V2([
+            if u < 0.0 { u + 1.0 } else { u },
+            y.clamp(-1.0, 1.0).acos() / PI,
+        ])
+    };
+    let mut b = Builder::default();
+    for &p in &points {
+        b.vtx(p * radius, p, uv(p));
+    }
+    // triangles that cross the seam are cut along it, cut points and
+    // vertices on the seam get a copy with `u` of `1.0` for the `-X` side,
+    // poles have no meaningful `u` so they get a copy per triangle centered
+    // between the other two vertices
+    let side = |i: u32| {
+        let x = points[i as usize].0[0];
+        (x > 0.0) as i32 - (x < 0.0) as i32
+    };
+    let on_seam = |i: u32| {
+        let V3([x, _, z]) = points[i as usize];
+        x == 0.0 && z > 0.0
+    };
+    let is_pole = |p: V3| p.0[0].abs() < 1e-6 && p.0[2].abs() < 1e-6;
+    let mut wrapped = HashMap::new();
+    let mut wrap = |b: &mut Builder, i: u32| {
+        if !on_seam(i) {
+            return i;
+        }
+        *wrapped.entry(i).or_insert_with(|| {
+            let vtx = Vtx {
+                tx: 1.0,
+                ..b.verts[i as usize]
+            };
+            b.verts.push(vtx);
+            b.verts.len() as u32 - 1
+        })
+    };
+    let mut cuts = HashMap::new();
+    let mut cut_tris = Vec::with_capacity(tris.len());
+    for tri in tris {
+        let crossing = |k: usize| {
+            let (a, c) = (tri[k], tri[(k + 1) % 3]);
+            if side(a) * side(c) >= 0 {
+                return None;
+            }
+            // cut on the edge rather than the sphere, so the mesh stays closed
+            let (lo, hi) = (points[a.min(c) as usize], points[a.max(c) as usize]);
+            let mut p = lo + (hi - lo) * (lo.0[0] / (lo.0[0] - hi.0[0]));
+            p.0[0] = 0.0;
+            (p.0[2] >= 0.0).then_some(((a.min(c), a.max(c)), p))
+        };
+        if (0..3).all(|k| crossing(k).is_none()) {
+            if tri.iter().any(|&i| side(i) < 0) {
+                cut_tris.push(tri.map(|i| wrap(&mut b, i)));
+            } else {
+                cut_tris.push(tri);
+            }
+            continue;
+        }
+        let (mut neg, mut pos) = (Vec::with_capacity(4), Vec::with_capacity(4));
+        for (k, &i) in tri.iter().enumerate() {
+            if side(i) <= 0 {
+                neg.push(wrap(&mut b, i));
+            }
+            if side(i) >= 0 {
+                pos.push(i);
+            }
+            if let Some((edge, p)) = crossing(k) {
+                let (u0, u1) = *cuts.entry(edge).or_insert_with(|| {
+                    let n = p.normalized();
+                    b.vtx(p * radius, n, uv(n));
+                    b.vtx(p * radius, n, V2([1.0, uv(n).0[1]]));
+                    (b.verts.len() as u32 - 2, b.verts.len() as u32 - 1)
+                });
+                neg.push(u1);
+                pos.push(u0);
+            }
+        }
+        for poly in [neg, pos] {
+            cut_tris.extend((1..poly.len() - 1).map(|k| [poly[0], poly[k], poly[k + 1]]));
+        }
+    }
+    for tri in &mut cut_tris {
+        let poles = tri.map(|i| is_pole(b.verts[i as usize].norm));
+        if let Some(at) = poles.iter().position(|&pole| pole) {
+            let u = (0..3)
+                .filter(|&k| k != at)
+                .map(|k| b.verts[tri[k] as usize].tx)
+                .sum::<f32>()
+                * 0.5;
+            let vtx = Vtx {
+                tx: u,
+                ..b.verts[tri[at] as usize]
+            };
+            b.verts.push(vtx);
+            tri[at] = b.verts.len() as u32 - 1;
+        }
+    }
+    b.idxs.extend(cut_tris.iter().flatten());
+    b.finish()
+}
+
+/// Capped cylinder of `height` along the Y axis. Caps are mapped radially.
+pub fn cylinder(radius: f32, height: f32, sectors: u32, stacks: u32) -> (Vec<Vtx>, Vec<u32>) {
+    let h = height * 0.5;
+    let stacks = stacks.max(1);
+    let mut b = Builder::default();
+    b.lathe(
+        [
+            (V2([0.0, h]), V2([0.0, 1.0])),
+            (V2([radius, h]), V2([0.0, 1.0])),
+        ],
+        sectors,
+    );
+    b.lathe(
+        (0..=stacks).map(|j| {
+            let y = h - height * j as f32 / stacks as f32;
+            (V2([radius, y]), V2([1.0, 0.0]))
+        }),
+        sectors,
+    );
+    b.lathe(
+        [
+            (V2([radius, -h]), V2([0.0, -1.0])),
+            (V2([0.0, -h]), V2([0.0, -1.0])),
+        ],
+        sectors,
+    );
+    b.finish()
+}
+
+/// Cone with its apex at the top and a capped base.
+pub fn cone(radius: f32, height: f32, sectors: u32, stacks: u32) -> (Vec<Vtx>, Vec<u32>) {
+    let h = height * 0.5;
+    let stacks = stacks.max(1);
+    let norm = V2([height, radius]).normalized();
+    let mut b = Builder::default();
+    b.lathe(
+        (0..=stacks).map(|j| {
+            let t = j as f32 / stacks as f32;
+            (V2([radius * t, h - height * t]), norm)
+        }),
+        sectors,
+    );
+    b.lathe(
+        [
+            (V2([radius, -h]), V2([0.0, -1.0])),
+            (V2([0.0, -h]), V2([0.0, -1.0])),
+        ],
+        sectors,
+    );
+    b.finish()
+}
+
+/// Cylinder with hemispherical ends. `height` is the length of the
+/// cylindrical middle, so the total height is `height + 2 * radius`.
+/// `stacks` is per hemisphere.
+pub fn capsule(radius: f32, height: f32, sectors: u32, stacks: u32) -> (Vec<Vtx>, Vec<u32>) {
+    let h = height * 0.5;
+    let stacks = stacks.max(1);
+    let cap = move |offset: f32, from: f32| {
+        (0..=stacks).map(move |j| {
+            let phi = from + j as f32 / stacks as f32 * FRAC_PI_2;
+            let norm = V2([phi.sin(), phi.cos()]);
+            (norm * radius + V2([0.0, offset]), norm)
+        })
+    };
+    let mut b = Builder::default();
+    b.lathe(cap(h, 0.0).chain(cap(-h, FRAC_PI_2)), sectors);
+    b.finish()
+}
+
+/// Torus around the Y axis. `major` is the distance from the center to the
+/// middle of the tube, `minor` the tube radius.
+pub fn torus(major: f32, minor: f32, sectors: u32, sides: u32) -> (Vec<Vtx>, Vec<u32>) {
+    let sides = sides.max(3);
+    // starts on the inner equator and runs over the top first, so that the
+    // outside is traversed downwards like the other shapes
+    let profile = (0..=sides).map(|j| {
+        let phi = j as f32 / sides as f32 * TAU;
+        let norm = V2([-phi.cos(), phi.sin()]);
+        (norm * minor + V2([major, 0.0]), norm)
+    });
+    let mut b = Builder::default();
+    b.lathe(profile, sectors);
+    b.finish()
+}
+
+#[derive(Default)]
+struct Builder {
+    verts: Vec<Vtx>,
+    idxs: Vec<u32>,
+}
+
+impl Builder {
+    #[inline]
+    fn vtx(&mut self, pos: V3, norm: V3, uv: V2) {
+        self.verts.push(Vtx {
+            pos,
+            tx: uv.0[0],
+            norm,
+            ty: uv.0[1],
+            color: Color::WHITE,
+        });
+    }
+
+    /// Quads between a `(cols + 1) * (rows + 1)` grid of vertices starting at
+    /// `base`. Columns run right and rows down seen from the front.
+    /// Triangles touching a collapsed (pole) row are skipped.
+    fn quads(&mut self, base: u32, cols: u32, rows: u32, pole: impl Fn(u32) -> bool) {
+        let stride = cols + 1;
+        for j in 0..rows {
+            for i in 0..cols {
+                let a = base + j * stride + i;
+                let (b, c, d) = (a + 1, a + stride + 1, a + stride);
+                if !pole(j + 1) {
+                    self.idxs.extend([a, d, c]);
+                }
+                if !pole(j) {
+                    self.idxs.extend([a, c, b]);
+                }
+            }
+        }
+    }
+
+    /// Flat rectangle at `center` spanning `right` and `down`.
+    fn face(&mut self, center: V3, right: V3, down: V3, subdiv: u32) {
+        let subdiv = subdiv.max(1);
+        let norm = down.cross(right).normalized();
+        let base = self.verts.len() as u32;
+        for j in 0..=subdiv {
+            for i in 0..=subdiv {
+                let (u, v) = (i as f32 / subdiv as f32, j as f32 / subdiv as f32);
+                let pos = center + right * (u - 0.5) + down * (v - 0.5);
+                self.vtx(pos, norm, V2([u, v]));
+            }
+        }
+        self.quads(base, subdiv, subdiv, |_| false);
+    }
+
+    /// Surface of revolution around the Y axis. `profile` holds `(radius,
+    /// y)` points and `(radial, y)` normals, from top to bottom on the
+    /// outside. `ty` follows the profile's arc length.
+    fn lathe<I>(&mut self, profile: I, sectors: u32)
+    where
+        I: IntoIterator<Item = (V2, V2)>,
+    {
+        let sectors = sectors.max(3);
+        let mut profile: Vec<(V2, V2)> = profile.into_iter().collect();
+        let mut dist = Vec::with_capacity(profile.len());
+        let mut total = 0.0;
+        for (j, (p, _)) in profile.iter().enumerate() {
+            if j > 0 {
+                total += (*p - profile[j - 1].0).length();
+            }
+            dist.push(total);
+        }
+        // snap radii that only missed the axis by rounding, so pole rows
+        // are detected below
+        for (p, _) in &mut profile {
+            if p.0[0].abs() <= total * 1e-6 {
+                p.0[0] = 0.0;
+            }
+        }
+
+        let base = self.verts.len() as u32;
+        for ((V2([r, y]), V2([nr, ny])), dist) in profile.iter().zip(&dist) {
+            for i in 0..=sectors {
+                let u = i as f32 / sectors as f32;
+                let (sin, cos) = (u * TAU).sin_cos();
+                let pos = V3([r * sin, *y, r * cos]);
+                let norm = V3([nr * sin, *ny, nr * cos]);
+                self.vtx(pos, norm, V2([u, dist / total.max(f32::EPSILON)]));
+            }
+        }
+        let rows = profile.len() as u32 - 1;
+        self.quads(base, sectors, rows, |j| profile[j as usize].0.0[0] == 0.0);
+    }
+
+    #[inline]
+    fn finish(self) -> (Vec<Vtx>, Vec<u32>) {
+        debug_assert!(self.idxs.chunks_exact(3).all(|tri| {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| &self.verts[i as usize]);
+            (b.pos - a.pos)
+                .cross(c.pos - a.pos)
+                .dot(a.norm + b.norm + c.norm)
+                >= 0.0
+        }));
+        (self.verts, self.idxs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Mesh = (Vec<Vtx>, Vec<u32>);
+    type Case = (String, Mesh, fn(V3) -> V3);
+
+    /// Every generator at a few levels of detail, with a point inside the
+    /// surface near `pos` that normals point away from.
+    fn meshes() -> Vec<Case> {
+        let origin: fn(V3) -> V3 = |_| V3::splat(0.0);
+        let mut meshes: Vec<Case> = Vec::new();
+        for n in [1, 2, 5] {
+            meshes.push((format!("plane {n}"), plane(V2([2.0, 1.0]), n), |pos| {
+                pos - V3::UP
+            }));
+            meshes.push((format!("cube {n}"), cube(V3([1.0, 2.0, 3.0]), n), origin));
+        }
+        for subdiv in 0..4 {
+            meshes.push((
+                format!("icosphere {subdiv}"),
+                icosphere(1.5, subdiv),
+                origin,
+            ));
+        }
+        for (sectors, stacks) in [(3, 1), (8, 2), (17, 5)] {
+            meshes.push((
+                format!("uv_sphere {sectors} {stacks}"),
+                uv_sphere(1.5, sectors, stacks),
+                origin,
+            ));
+            meshes.push((
+                format!("cylinder {sectors} {stacks}"),
+                cylinder(1.0, 2.0, sectors, stacks),
+                origin,
+            ));
+            meshes.push((
+                format!("cone {sectors} {stacks}"),
+                cone(1.0, 2.0, sectors, stacks),
+                origin,
+            ));
+            meshes.push((
+                format!("capsule {sectors} {stacks}"),
+                capsule(0.5, 2.0, sectors, stacks),
+                origin,
+            ));
+            meshes.push((
+                format!("torus {sectors} {stacks}"),
+                torus(1.0, 0.25, sectors, stacks),
+                // the middle of the tube
+                |pos| V3([pos.0[0], 0.0, pos.0[2]]).normalized(),
+            ));
+        }
+        meshes
+    }
+
+    #[test]
+    fn normals_are_unit_and_point_outwards() {
+        for (name, (verts, _), inside) in meshes() {
+            for vtx in &verts {
+                assert!((vtx.norm.length() - 1.0).abs() < 1e-5, "{name}");
+                assert!(vtx.norm.dot(vtx.pos - inside(vtx.pos)) > 0.0, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn triangles_wind_counter_clockwise_from_outside() {
+        for (name, (verts, idxs), _) in meshes() {
+            assert_eq!(idxs.len() % 3, 0, "{name}");
+            for tri in idxs.chunks_exact(3) {
+                let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| &verts[i as usize]);
+                let face = (b.pos - a.pos).cross(c.pos - a.pos);
+                assert!(face.length() > 1e-6, "{name} has a degenerate triangle");
+                for vtx in [a, b, c] {
+                    assert!(face.dot(vtx.norm) > 0.0, "{name} {tri:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn uvs_stay_in_range_without_mirroring() {
+        for (name, (verts, idxs), _) in meshes() {
+            for vtx in &verts {
+                assert!((0.0..=1.0).contains(&vtx.tx), "{name} tx {}", vtx.tx);
+                assert!((0.0..=1.0).contains(&vtx.ty), "{name} ty {}", vtx.ty);
+            }
+            // `ty` runs down the texture, so counter-clockwise triangles are
+            // clockwise in UV space, a triangle wrapping across a seam would
+            // flip
+            for tri in idxs.chunks_exact(3) {
+                let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| &verts[i as usize]);
+                let area = (b.tx - a.tx) * (c.ty - a.ty) - (c.tx - a.tx) * (b.ty - a.ty);
+                assert!(area < 0.0, "{name} {tri:?} has UV area {area}");
+            }
+        }
+    }
+}
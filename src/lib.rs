@@ -1,5 +1,6 @@
 #![deny(unused_imports)]
 
+pub mod codec;
 pub mod gfx;
 pub mod log;
 pub mod math;
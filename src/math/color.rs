@@ -0,0 +1,294 @@
+use bytemuck::{Pod, Zeroable};
+
+use super::V4;
+
+/// Linear RGBA color with straight (non-premultiplied) alpha unless stated
+/// otherwise.
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color(pub V4);
+
+/// sRGB-encoded 8-bit RGBA. The bytes are stored in `[r, g, b, a]` order,
+/// which is what the GPU expects for texture data.
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rgba8(pub [u8; 4]);
+
+impl Color {
+    pub const TRANSPARENT: Self = Self::rgba(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Self = Self::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Self = Self::rgb(1.0, 1.0, 1.0);
+    pub const RED: Self = Self::rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Self = Self::rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Self = Self::rgb(0.0, 0.0, 1.0);
+    pub const YELLOW: Self = Self::rgb(1.0, 1.0, 0.0);
+    pub const CYAN: Self = Self::rgb(0.0, 1.0, 1.0);
+    pub const MAGENTA: Self = Self::rgb(1.0, 0.0, 1.0);
+
+    #[inline]
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self(V4([r, g, b, 1.0]))
+    }
+
+    #[inline]
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self(V4([r, g, b, a]))
+    }
+
+    #[inline]
+    pub const fn with_alpha(&self, a: f32) -> Self {
+        let [r, g, b, _] = self.0.0;
+        Self(V4([r, g, b, a]))
+    }
+
+    /// Decode from sRGB-encoded components. Alpha is always linear.
+    #[inline]
+    pub fn from_srgb(srgb: V4) -> Self {
+        let [r, g, b, a] = srgb.0;
+        Self(V4([
+            srgb_to_linear(r),
+            srgb_to_linear(g),
+            srgb_to_linear(b),
+            a,
+        ]))
+    }
+
+    /// Encode into sRGB components. Alpha is left linear.
+    #[inline]
+    pub fn to_srgb(&self) -> V4 {
+        let [r, g, b, a] = self.0.0;
+        V4([linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a])
+    }
+
+    /// Decode an sRGB `0xRRGGBBAA` value.
+    #[inline]
+    pub fn from_hex(hex: u32) -> Self {
+        Self::from(Rgba8::from_hex(hex))
+    }
+
+    /// Encode as an sRGB `0xRRGGBBAA` value.
+    #[inline]
+    pub fn to_hex(&self) -> u32 {
+        Rgba8::from(*self).to_hex()
+    }
+
+    /// `hue` is in degrees, the rest in `[0, 1]`. Like most color pickers,
+    /// HSV is interpreted in sRGB space.
+    pub fn from_hsv(hue: f32, sat: f32, val: f32, alpha: f32) -> Self {
+        let c = val * sat;
+        Self::from_hue_chroma(hue, c, val - c, alpha)
+    }
+
+    /// Returns `[hue, sat, val, alpha]`. See `from_hsv`.
+    pub fn to_hsv(&self) -> V4 {
+        let (hue, max, min, a) = self.hue_max_min();
+        let sat = if max > 0.0 { (max - min) / max } else { 0.0 };
+        V4([hue, sat, max, a])
+    }
+
+    /// `hue` is in degrees, the rest in `[0, 1]`. Interpreted in sRGB space.
+    pub fn from_hsl(hue: f32, sat: f32, light: f32, alpha: f32) -> Self {
+        let c = (1.0 - ((2.0 * light) - 1.0).abs()) * sat;
+        Self::from_hue_chroma(hue, c, light - (c * 0.5), alpha)
+    }
+
+    /// Returns `[hue, sat, light, alpha]`. See `from_hsl`.
+    pub fn to_hsl(&self) -> V4 {
+        let (hue, max, min, a) = self.hue_max_min();
+        let light = (max + min) * 0.5;
+        let sat = if (light > 0.0) && (light < 1.0) {
+            (max - min) / (1.0 - ((2.0 * light) - 1.0).abs())
+        } else {
+            0.0
+        };
+        V4([hue, sat, light, a])
+    }
+
+    fn from_hue_chroma(hue: f32, c: f32, m: f32, alpha: f32) -> Self {
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let x = c * (1.0 - ((h % 2.0) - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        Self::from_srgb(V4([r + m, g + m, b + m, alpha]))
+    }
+
+    fn hue_max_min(&self) -> (f32, f32, f32, f32) {
+        let V4([r, g, b, a]) = self.to_srgb();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let d = max - min;
+        let hue = if d <= 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / d).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * (((b - r) / d) + 2.0)
+        } else {
+            60.0 * (((r - g) / d) + 4.0)
+        };
+        (hue, max, min, a)
+    }
+
+    #[inline]
+    pub fn premultiplied(&self) -> Self {
+        let [r, g, b, a] = self.0.0;
+        Self(V4([r * a, g * a, b * a, a]))
+    }
+
+    /// Inverse of `premultiplied`. Fully transparent colors become
+    /// `TRANSPARENT`.
+    #[inline]
+    pub fn unpremultiplied(&self) -> Self {
+        let [r, g, b, a] = self.0.0;
+        if a <= 0.0 {
+            return Self::TRANSPARENT;
+        }
+        Self(V4([r / a, g / a, b / a, a]))
+    }
+
+    #[inline]
+    pub fn lerp(&self, rhs: Color, t: f32) -> Self {
+        Self(self.0 + ((rhs.0 - self.0) * t))
+    }
+}
+
+impl Rgba8 {
+    #[inline]
+    pub const fn from_hex(hex: u32) -> Self {
+        Self(hex.to_be_bytes())
+    }
+
+    #[inline]
+    pub const fn to_hex(&self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+}
+
+impl From<Rgba8> for Color {
+    #[inline]
+    fn from(value: Rgba8) -> Self {
+        let [r, g, b, a] = value.0.map(|c| (c as f32) / 255.0);
+        Self::from_srgb(V4([r, g, b, a]))
+    }
+}
+
+impl From<Color> for Rgba8 {
+    #[inline]
+    fn from(value: Color) -> Self {
+        Self(
+            value
+                .to_srgb()
+                .0
+                .map(|c| ((c.clamp(0.0, 1.0) * 255.0) + 0.5) as u8),
+        )
+    }
+}
+
+impl From<V4> for Color {
+    #[inline]
+    fn from(value: V4) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Color> for V4 {
+    #[inline]
+    fn from(value: Color) -> Self {
+        value.0
+    }
+}
+
+#[inline]
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[inline]
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        (1.055 * c.powf(1.0 / 2.4)) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color_near(a: Color, b: Color, eps: f32) {
+        let d = a.0 - b.0;
+        assert!(d.0.iter().all(|c| c.abs() < eps), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn srgb_transfer_matches_reference_points() {
+        // sRGB 0.5 is about 21.4% linear, and the curve is linear near 0
+        let linear = Color::from_srgb(V4([0.5, 0.04, 1.0, 0.5]));
+        assert!((linear.0.0[0] - 0.214_041).abs() < 1.0e-5, "{linear:?}");
+        assert!((linear.0.0[1] - 0.04 / 12.92).abs() < 1.0e-7, "{linear:?}");
+        assert_eq!(linear.0.0[2], 1.0);
+        assert_eq!(linear.0.0[3], 0.5);
+        assert_color_near(
+            Color(linear.to_srgb()),
+            Color(V4([0.5, 0.04, 1.0, 0.5])),
+            1.0e-6,
+        );
+    }
+
+    #[test]
+    fn every_byte_round_trips_through_color() {
+        for c in 0..=255u8 {
+            let rgba = Rgba8([c, 255 - c, c / 2, c]);
+            assert_eq!(Rgba8::from(Color::from(rgba)), rgba);
+        }
+    }
+
+    #[test]
+    fn hex_is_rrggbbaa_in_byte_order() {
+        let rgba = Rgba8::from_hex(0x1122_33FF);
+        assert_eq!(rgba.0, [0x11, 0x22, 0x33, 0xFF]);
+        assert_eq!(bytemuck::cast::<_, [u8; 4]>(rgba), [0x11, 0x22, 0x33, 0xFF]);
+        assert_eq!(rgba.to_hex(), 0x1122_33FF);
+        assert_eq!(Color::from_hex(0xFF80_00C0).to_hex(), 0xFF80_00C0);
+        assert_eq!(Rgba8::from(Color::RED).to_hex(), 0xFF00_00FF);
+        assert_eq!(
+            Rgba8::from(Color::BLUE.with_alpha(0.0)).to_hex(),
+            0x0000_FF00
+        );
+    }
+
+    #[test]
+    fn hsv_and_hsl_round_trip() {
+        // primaries and a mix, given in sRGB
+        assert_color_near(Color::from_hsv(0.0, 1.0, 1.0, 1.0), Color::RED, 1.0e-6);
+        assert_color_near(Color::from_hsv(120.0, 1.0, 1.0, 1.0), Color::GREEN, 1.0e-6);
+        assert_color_near(Color::from_hsl(240.0, 1.0, 0.5, 1.0), Color::BLUE, 1.0e-6);
+        assert_color_near(Color::from_hsl(0.0, 0.0, 1.0, 1.0), Color::WHITE, 1.0e-6);
+        let orange = Color::from_hex(0xFF80_00FF);
+        let hsv = orange.to_hsv();
+        assert!((hsv.0[0] - 30.1).abs() < 0.1, "{hsv:?}");
+        for hue in [0.0, 45.0, 150.0, 210.0, 300.0] {
+            let color = Color::from_hsv(hue, 0.6, 0.8, 0.25);
+            let V4([h, s, v, a]) = color.to_hsv();
+            assert_color_near(Color::from_hsv(h, s, v, a), color, 1.0e-5);
+            assert!((h - hue).abs() < 1.0e-3, "{hue} -> {h}");
+            let color = Color::from_hsl(hue, 0.6, 0.4, 0.25);
+            let V4([h, s, l, a]) = color.to_hsl();
+            assert_color_near(Color::from_hsl(h, s, l, a), color, 1.0e-5);
+            assert!((h - hue).abs() < 1.0e-3, "{hue} -> {h}");
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Shared values for the math tests.
+
+use super::{Dot, Mat4, Quat, V3, V4, Xform3};
+
+pub const EPS: f32 = 1.0e-5;
+
+/// Non-symmetric, so a transposed conversion can't pass: a 90 degree turn
+/// about Z, then a translation by `(10, 20, 30)`.
+pub fn turn_and_translate() -> Mat4 {
+    Mat4([
+        V4([0.0, 1.0, 0.0, 0.0]),
+        V4([-1.0, 0.0, 0.0, 0.0]),
+        V4([0.0, 0.0, 1.0, 0.0]),
+        V4([10.0, 20.0, 30.0, 1.0]),
+    ])
+}
+
+/// A transform with an off-axis rotation and the given scale.
+pub fn trs(scale: V3) -> Xform3 {
+    Xform3 {
+        pos: V3([1.0, -2.0, 3.0]),
+        scale,
+        rot: Quat::from_axis_angle(V3([1.0, 2.0, 3.0]).normalized(), 0.7),
+    }
+}
+
+pub fn assert_v3_near(a: V3, b: V3) {
+    assert!((a - b).length() < EPS, "{a:?} != {b:?}");
+}
+
+/// `q` and `-q` are the same rotation.
+pub fn assert_rot_near(a: Quat, b: Quat) {
+    assert!(
+        (a.normalized().dot(b.normalized()).abs() - 1.0).abs() < EPS,
+        "{a:?} != {b:?}"
+    );
+}
+
+pub fn assert_xform_near(a: &Xform3, b: &Xform3) {
+    assert_v3_near(a.pos, b.pos);
+    assert_v3_near(a.scale, b.scale);
+    assert_rot_near(a.rot, b.rot);
+}
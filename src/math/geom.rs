@@ -0,0 +1,350 @@
+use super::{Cross, Dot, Mat4, V2, V3, V4};
+
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aabb {
+    pub min: V3,
+    pub max: V3,
+}
+
+impl Aabb {
+    /// An inverted box that any `grow` will replace.
+    pub const EMPTY: Self = Self {
+        min: V3::splat(f32::INFINITY),
+        max: V3::splat(f32::NEG_INFINITY),
+    };
+
+    #[inline]
+    pub fn from_points<'a, I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = &'a V3>,
+    {
+        let mut aabb = Self::EMPTY;
+        for point in points {
+            aabb.grow(*point);
+        }
+        aabb
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        let V3([x1, y1, z1]) = self.min;
+        let V3([x2, y2, z2]) = self.max;
+        (x1 > x2) || (y1 > y2) || (z1 > z2)
+    }
+
+    #[inline]
+    pub fn grow(&mut self, point: V3) {
+        for i in 0..3 {
+            self.min.0[i] = self.min.0[i].min(point.0[i]);
+            self.max.0[i] = self.max.0[i].max(point.0[i]);
+        }
+    }
+
+    #[inline]
+    pub fn union(&self, rhs: &Aabb) -> Self {
+        let mut aabb = *self;
+        aabb.grow(rhs.min);
+        aabb.grow(rhs.max);
+        aabb
+    }
+
+    #[inline]
+    pub fn center(&self) -> V3 {
+        (self.min + self.max) * 0.5
+    }
+
+    #[inline]
+    pub fn extents(&self) -> V3 {
+        (self.max - self.min) * 0.5
+    }
+
+    #[inline]
+    pub fn contains(&self, point: V3) -> bool {
+        (0..3).all(|i| (self.min.0[i] <= point.0[i]) && (point.0[i] <= self.max.0[i]))
+    }
+
+    #[inline]
+    pub fn intersects(&self, rhs: &Aabb) -> bool {
+        (0..3).all(|i| (self.min.0[i] <= rhs.max.0[i]) && (rhs.min.0[i] <= self.max.0[i]))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sphere {
+    pub center: V3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    #[inline]
+    pub fn contains(&self, point: V3) -> bool {
+        (point - self.center).normal_squared() <= (self.radius * self.radius)
+    }
+
+    #[inline]
+    pub fn intersects(&self, rhs: &Sphere) -> bool {
+        let r = self.radius + rhs.radius;
+        (rhs.center - self.center).normal_squared() <= (r * r)
+    }
+}
+
+/// Points `p` on the plane satisfy `normal.dot(p) + dist == 0`. The normal
+/// points towards the positive half-space.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plane {
+    pub normal: V3,
+    pub dist: f32,
+}
+
+impl Plane {
+    #[inline]
+    pub fn from_point_normal(point: V3, normal: V3) -> Self {
+        let normal = normal.normalized();
+        Self {
+            normal,
+            dist: -normal.dot(point),
+        }
+    }
+
+    /// Plane through three points, facing the side they wind
+    /// counter-clockwise around.
+    #[inline]
+    pub fn from_points(a: V3, b: V3, c: V3) -> Self {
+        Self::from_point_normal(a, (b - a).cross(c - a))
+    }
+
+    #[inline]
+    pub fn normalized(&self) -> Self {
+        let rcp_len = 1.0 / self.normal.length();
+        Self {
+            normal: self.normal * rcp_len,
+            dist: self.dist * rcp_len,
+        }
+    }
+
+    /// Signed distance from `point` to the plane (positive on the normal side).
+    #[inline]
+    pub fn distance(&self, point: V3) -> f32 {
+        self.normal.dot(point) + self.dist
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ray {
+    pub origin: V3,
+    pub dir: V3,
+}
+
+impl Ray {
+    #[inline]
+    pub fn at(&self, t: f32) -> V3 {
+        self.origin + (self.dir * t)
+    }
+
+    /// Distance along the ray to the nearest hit in front of the origin. A
+    /// ray starting inside the box hits at `0.0`.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+        for i in 0..3 {
+            let rcp_dir = 1.0 / self.dir.0[i];
+            let t1 = (aabb.min.0[i] - self.origin.0[i]) * rcp_dir;
+            let t2 = (aabb.max.0[i] - self.origin.0[i]) * rcp_dir;
+            // `min`/`max` drop the NaN from `0 * inf` for axis-aligned rays
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+        }
+        if t_min <= t_max { Some(t_min) } else { None }
+    }
+
+    pub fn intersect_sphere(&self, sphere: &Sphere) -> Option<f32> {
+        let oc = self.origin - sphere.center;
+        let a = self.dir.normal_squared();
+        let b = oc.dot(self.dir);
+        let c = oc.normal_squared() - (sphere.radius * sphere.radius);
+        let disc = (b * b) - (a * c);
+        if disc < 0.0 {
+            return None;
+        }
+        let sqrt_disc = disc.sqrt();
+        let t = (-b - sqrt_disc) / a;
+        if t >= 0.0 {
+            return Some(t);
+        }
+        let t = (-b + sqrt_disc) / a;
+        if t >= 0.0 { Some(0.0) } else { None }
+    }
+
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let denom = plane.normal.dot(self.dir);
+        // relative to the lengths, so that short directions still hit
+        let scale = plane.normal.length() * self.dir.length();
+        if denom.abs() <= f32::EPSILON * scale {
+            return None;
+        }
+        let t = -plane.distance(self.origin) / denom;
+        if t >= 0.0 { Some(t) } else { None }
+    }
+
+    /// Möller–Trumbore intersection. Returns the distance along the ray and
+    /// the barycentric coordinates `(u, v)` of the hit relative to `b` and
+    /// `c`. Both faces are hit.
+    pub fn intersect_triangle(&self, tri: &Triangle) -> Option<(f32, V2)> {
+        let [a, b, c] = tri.0;
+        let e1 = b - a;
+        let e2 = c - a;
+        let p = self.dir.cross(e2);
+        let det = e1.dot(p);
+        // relative to the edge lengths, so that small triangles still hit
+        let scale = self.dir.length() * e1.length() * e2.length();
+        if det.abs() <= f32::EPSILON * scale {
+            return None;
+        }
+        let rcp_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(p) * rcp_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(e1);
+        let v = self.dir.dot(q) * rcp_det;
+        if (v < 0.0) || ((u + v) > 1.0) {
+            return None;
+        }
+        let t = e2.dot(q) * rcp_det;
+        if t >= 0.0 {
+            Some((t, V2([u, v])))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Triangle(pub [V3; 3]);
+
+impl Triangle {
+    /// Unnormalized face normal, facing the side the vertices wind
+    /// counter-clockwise around.
+    #[inline]
+    pub fn normal(&self) -> V3 {
+        let [a, b, c] = self.0;
+        (b - a).cross(c - a)
+    }
+
+    #[inline]
+    pub fn area(&self) -> f32 {
+        self.normal().length() * 0.5
+    }
+}
+
+/// Six inward-facing planes: left, right, bottom, top, near, far.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frustum(pub [Plane; 6]);
+
+impl Frustum {
+    pub fn contains(&self, point: V3) -> bool {
+        self.0.iter().all(|plane| plane.distance(point) >= 0.0)
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.0
+            .iter()
+            .all(|plane| plane.distance(sphere.center) >= -sphere.radius)
+    }
+
+    /// Conservative test: may report boxes just outside a frustum corner.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.0.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let mut p = aabb.min;
+            for i in 0..3 {
+                if plane.normal.0[i] >= 0.0 {
+                    p.0[i] = aabb.max.0[i];
+                }
+            }
+            plane.distance(p) >= 0.0
+        })
+    }
+}
+
+macro_rules! from_mat4_impl {
+    ($mat:ty) => {
+        impl From<$mat> for Frustum {
+            /// Extract the frustum of a view-projection matrix with a
+            /// `[-1, 1]` clip-space depth range.
+            fn from(mat: $mat) -> Self {
+                let row = |i: usize| {
+                    let [a, b, c, d] = mat.0;
+                    V4([a.0[i], b.0[i], c.0[i], d.0[i]])
+                };
+                let plane = |v: V4| {
+                    let (normal, dist) = v.narrowed();
+                    let plane = Plane { normal, dist };
+                    // infinite projections have a far plane that culls nothing
+                    if normal.normal_squared() > 0.0 {
+                        plane.normalized()
+                    } else {
+                        plane
+                    }
+                };
+                let [x, y, z, w] = [row(0), row(1), row(2), row(3)];
+                Frustum([
+                    plane(w + x),
+                    plane(w - x),
+                    plane(w + y),
+                    plane(w - y),
+                    plane(w + z),
+                    plane(w - z),
+                ])
+            }
+        }
+    };
+}
+
+from_mat4_impl!(Mat4);
+from_mat4_impl!(&Mat4);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_hits_sub_millimetre_triangle() {
+        let tri = Triangle([
+            V3([0.0, 0.0, 0.0]),
+            V3([1.0e-4, 0.0, 0.0]),
+            V3([0.0, 1.0e-4, 0.0]),
+        ]);
+        let ray = Ray {
+            origin: V3([2.5e-5, 2.5e-5, 1.0]),
+            dir: V3::BACKWARD,
+        };
+        let (t, uv) = ray.intersect_triangle(&tri).unwrap();
+        assert!((t - 1.0).abs() < 1.0e-6);
+        assert!((uv.0[0] - 0.25).abs() < 1.0e-3 && (uv.0[1] - 0.25).abs() < 1.0e-3);
+
+        let parallel = Ray {
+            origin: V3([2.5e-5, 2.5e-5, 1.0]),
+            dir: V3::RIGHT,
+        };
+        assert!(parallel.intersect_triangle(&tri).is_none());
+    }
+
+    #[test]
+    fn short_ray_hits_plane() {
+        let plane = Plane::from_point_normal(V3::splat(0.0), V3::UP);
+        let ray = Ray {
+            origin: V3([0.0, 1.0e-7, 0.0]),
+            dir: V3([0.0, -1.0e-8, 0.0]),
+        };
+        let t = ray.intersect_plane(&plane).unwrap();
+        assert!((t - 10.0).abs() < 1.0e-3);
+    }
+}
@@ -0,0 +1,117 @@
+//! Conversions to and from `glam`. Both crates store matrices column-major,
+//! so matrix conversions are plain copies.
+
+use super::{IV2, IV3, IV4, Mat2, Mat3, Mat4, Quat, UV2, UV3, UV4, V2, V3, V4, Xform3};
+
+macro_rules! glam_vec_impl {
+    ($vec:ident, $glam:ident) => {
+        impl From<$vec> for glam::$glam {
+            #[inline]
+            fn from(value: $vec) -> Self {
+                Self::from_array(value.0)
+            }
+        }
+
+        impl From<glam::$glam> for $vec {
+            #[inline]
+            fn from(value: glam::$glam) -> Self {
+                Self(value.to_array())
+            }
+        }
+    };
+}
+
+glam_vec_impl!(V2, Vec2);
+glam_vec_impl!(V3, Vec3);
+glam_vec_impl!(V4, Vec4);
+glam_vec_impl!(IV2, IVec2);
+glam_vec_impl!(IV3, IVec3);
+glam_vec_impl!(IV4, IVec4);
+glam_vec_impl!(UV2, UVec2);
+glam_vec_impl!(UV3, UVec3);
+glam_vec_impl!(UV4, UVec4);
+
+impl From<Quat> for glam::Quat {
+    #[inline]
+    fn from(value: Quat) -> Self {
+        Self::from_array(value.0.0)
+    }
+}
+
+impl From<glam::Quat> for Quat {
+    #[inline]
+    fn from(value: glam::Quat) -> Self {
+        Self(V4(value.to_array()))
+    }
+}
+
+macro_rules! glam_mat_impl {
+    ($mat:ident, $glam:ident) => {
+        impl From<$mat> for glam::$glam {
+            #[inline]
+            fn from(value: $mat) -> Self {
+                Self::from_cols_array_2d(&bytemuck::cast(value))
+            }
+        }
+
+        impl From<glam::$glam> for $mat {
+            #[inline]
+            fn from(value: glam::$glam) -> Self {
+                bytemuck::cast(value.to_cols_array_2d())
+            }
+        }
+    };
+}
+
+glam_mat_impl!(Mat2, Mat2);
+glam_mat_impl!(Mat3, Mat3);
+glam_mat_impl!(Mat4, Mat4);
+
+impl From<Xform3> for glam::Affine3A {
+    #[inline]
+    fn from(value: Xform3) -> Self {
+        Self::from_scale_rotation_translation(
+            value.scale.into(),
+            value.rot.into(),
+            value.pos.into(),
+        )
+    }
+}
+
+impl From<glam::Affine3A> for Xform3 {
+    /// Shear is discarded, unlike `Xform3::try_from(Mat4)` which rejects it.
+    #[inline]
+    fn from(value: glam::Affine3A) -> Self {
+        let (scale, rot, pos) = value.to_scale_rotation_translation();
+        Self {
+            pos: pos.into(),
+            scale: scale.into(),
+            rot: rot.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::fixtures::{assert_v3_near, assert_xform_near, trs, turn_and_translate};
+
+    #[test]
+    fn mat4_translation_lands_in_column_3() {
+        let m = turn_and_translate();
+        let glam = glam::Mat4::from(m);
+        assert_eq!(glam.col(3).to_array(), [10.0, 20.0, 30.0, 1.0]);
+        assert_eq!(glam.col(0).to_array(), [0.0, 1.0, 0.0, 0.0]);
+        let p = glam.transform_point3(glam::Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(p.to_array(), (m * V3([1.0, 2.0, 3.0])).0);
+    }
+
+    #[test]
+    fn xform3_round_trips_through_affine3a() {
+        let xform = trs(V3([2.0, 3.0, 4.0]));
+        let affine = glam::Affine3A::from(xform);
+        let p = affine.transform_point3(glam::Vec3::new(1.0, 0.0, 0.0));
+        assert_v3_near(p.into(), xform.transform_point(V3::RIGHT));
+        assert_xform_near(&Xform3::from(affine), &xform);
+    }
+}
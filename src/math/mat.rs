@@ -2,10 +2,12 @@ use std::ops::Mul;
 
 use bytemuck::{Pod, Zeroable};
 
-use super::{Dot, Quat, V3, V4, Xform3};
+use super::{Cross, Dot, Quat, V2, V3, V4, Xform3};
 
+/// Column-major 4x4 matrix (each `V4` is a column).
 #[repr(C)]
-#[derive(Copy, Clone, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mat4(pub [V4; 4]);
 
 impl Mat4 {
@@ -26,6 +28,157 @@ impl Mat4 {
             V4([a.0[3], b.0[3], c.0[3], d.0[3]]),
         ])
     }
+
+    pub fn determinant(&self) -> f32 {
+        let [
+            V4([m00, m01, m02, m03]),
+            V4([m10, m11, m12, m13]),
+            V4([m20, m21, m22, m23]),
+            V4([m30, m31, m32, m33]),
+        ] = self.0;
+        let a2323 = m22 * m33 - m23 * m32;
+        let a1323 = m21 * m33 - m23 * m31;
+        let a1223 = m21 * m32 - m22 * m31;
+        let a0323 = m20 * m33 - m23 * m30;
+        let a0223 = m20 * m32 - m22 * m30;
+        let a0123 = m20 * m31 - m21 * m30;
+        m00 * (m11 * a2323 - m12 * a1323 + m13 * a1223)
+            - m01 * (m10 * a2323 - m12 * a0323 + m13 * a0223)
+            + m02 * (m10 * a1323 - m11 * a0323 + m13 * a0123)
+            - m03 * (m10 * a1223 - m11 * a0223 + m12 * a0123)
+    }
+
+    /// General inverse. Returns `None` if the matrix is singular.
+    #[inline]
+    pub fn inverse(&self) -> Option<Self> {
+        mat4_inverse(self)
+    }
+
+    /// Inverse of a matrix whose last row is `[0, 0, 0, 1]` (rotation, scale
+    /// and translation only). Cheaper than `inverse`.
+    pub fn affine_inverse(&self) -> Option<Self> {
+        let [a, b, c, d] = self.0;
+        let (a, _) = a.narrowed();
+        let (b, _) = b.narrowed();
+        let (c, _) = c.narrowed();
+        let (t, _) = d.narrowed();
+        let bc = b.cross(c);
+        let rcp_det = 1.0 / a.dot(bc);
+        if !rcp_det.is_finite() {
+            return None;
+        }
+        let r0 = bc * rcp_det;
+        let r1 = c.cross(a) * rcp_det;
+        let r2 = a.cross(b) * rcp_det;
+        Some(Mat4([
+            V4([r0.0[0], r1.0[0], r2.0[0], 0.0]),
+            V4([r0.0[1], r1.0[1], r2.0[1], 0.0]),
+            V4([r0.0[2], r1.0[2], r2.0[2], 0.0]),
+            V4([-r0.dot(t), -r1.dot(t), -r2.dot(t), 1.0]),
+        ]))
+    }
+
+    /// Transform a point (`w = 1`), ignoring any projective component.
+    #[inline]
+    pub fn transform_point(&self, point: V3) -> V3 {
+        (self * point.extended(1.0)).narrowed().0
+    }
+
+    /// Transform a direction (`w = 0`), ignoring translation.
+    #[inline]
+    pub fn transform_vector(&self, vector: V3) -> V3 {
+        (self * vector.extended(0.0)).narrowed().0
+    }
+
+    /// Transform a point (`w = 1`) and divide the result by its `w`.
+    #[inline]
+    pub fn project_point(&self, point: V3) -> V3 {
+        let (v, w) = (self * point.extended(1.0)).narrowed();
+        v / w
+    }
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+fn mat4_inverse(mat: &Mat4) -> Option<Mat4> {
+    let [
+        V4([m00, m01, m02, m03]),
+        V4([m10, m11, m12, m13]),
+        V4([m20, m21, m22, m23]),
+        V4([m30, m31, m32, m33]),
+    ] = mat.0;
+
+    let coef00 = m22 * m33 - m32 * m23;
+    let coef02 = m12 * m33 - m32 * m13;
+    let coef03 = m12 * m23 - m22 * m13;
+    let coef04 = m21 * m33 - m31 * m23;
+    let coef06 = m11 * m33 - m31 * m13;
+    let coef07 = m11 * m23 - m21 * m13;
+    let coef08 = m21 * m32 - m31 * m22;
+    let coef10 = m11 * m32 - m31 * m12;
+    let coef11 = m11 * m22 - m21 * m12;
+    let coef12 = m20 * m33 - m30 * m23;
+    let coef14 = m10 * m33 - m30 * m13;
+    let coef15 = m10 * m23 - m20 * m13;
+    let coef16 = m20 * m32 - m30 * m22;
+    let coef18 = m10 * m32 - m30 * m12;
+    let coef19 = m10 * m22 - m20 * m12;
+    let coef20 = m20 * m31 - m30 * m21;
+    let coef22 = m10 * m31 - m30 * m11;
+    let coef23 = m10 * m21 - m20 * m11;
+
+    let fac0 = V4([coef00, coef00, coef02, coef03]);
+    let fac1 = V4([coef04, coef04, coef06, coef07]);
+    let fac2 = V4([coef08, coef08, coef10, coef11]);
+    let fac3 = V4([coef12, coef12, coef14, coef15]);
+    let fac4 = V4([coef16, coef16, coef18, coef19]);
+    let fac5 = V4([coef20, coef20, coef22, coef23]);
+
+    let vec0 = V4([m10, m00, m00, m00]);
+    let vec1 = V4([m11, m01, m01, m01]);
+    let vec2 = V4([m12, m02, m02, m02]);
+    let vec3 = V4([m13, m03, m03, m03]);
+
+    let sign_a = V4([1.0, -1.0, 1.0, -1.0]);
+    let sign_b = V4([-1.0, 1.0, -1.0, 1.0]);
+    let inv0 = (vec1 * fac0 - vec2 * fac1 + vec3 * fac2) * sign_a;
+    let inv1 = (vec0 * fac0 - vec2 * fac3 + vec3 * fac4) * sign_b;
+    let inv2 = (vec0 * fac1 - vec1 * fac3 + vec3 * fac5) * sign_a;
+    let inv3 = (vec0 * fac2 - vec1 * fac4 + vec2 * fac5) * sign_b;
+
+    let row0 = V4([inv0.0[0], inv1.0[0], inv2.0[0], inv3.0[0]]);
+    let rcp_det = 1.0 / mat.0[0].dot(row0);
+    if !rcp_det.is_finite() {
+        return None;
+    }
+    Some(Mat4([
+        inv0 * rcp_det,
+        inv1 * rcp_det,
+        inv2 * rcp_det,
+        inv3 * rcp_det,
+    ]))
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+use super::simd::{mat4_inverse, mat4_mul, mat4_mul_vec4};
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+#[inline]
+fn mat4_mul(lhs: &Mat4, rhs: &Mat4) -> Mat4 {
+    let [a, b, c, d] = rhs.0;
+    Mat4([
+        mat4_mul_vec4(lhs, a),
+        mat4_mul_vec4(lhs, b),
+        mat4_mul_vec4(lhs, c),
+        mat4_mul_vec4(lhs, d),
+    ])
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+#[inline]
+fn mat4_mul_vec4(lhs: &Mat4, rhs: V4) -> V4 {
+    let [x, y, z, w] = rhs.0;
+    let [a, b, c, d] = lhs.0;
+    (a * x) + (b * y) + (c * z) + (d * w)
 }
 
 macro_rules! from_quat_impl {
@@ -77,19 +230,19 @@ macro_rules! from_xform3_impl {
                 Mat4([
                     V4([
                         sx * (1.0 - 2.0 * (yy + zz)),
-                        sy * (2.0 * (xy - zw)),
-                        sz * (2.0 * (xz + yw)),
+                        sx * (2.0 * (xy + zw)),
+                        sx * (2.0 * (xz - yw)),
                         0.0,
                     ]),
                     V4([
-                        sx * (2.0 * (xy + zw)),
+                        sy * (2.0 * (xy - zw)),
                         sy * (1.0 - 2.0 * (xx + zz)),
-                        sz * (2.0 * (yz - xw)),
+                        sy * (2.0 * (yz + xw)),
                         0.0,
                     ]),
                     V4([
-                        sx * (2.0 * (xz - yw)),
-                        sy * (2.0 * (yz + xw)),
+                        sz * (2.0 * (xz + yw)),
+                        sz * (2.0 * (yz - xw)),
                         sz * (1.0 - 2.0 * (xx + yy)),
                         0.0,
                     ]),
@@ -103,15 +256,52 @@ macro_rules! from_xform3_impl {
 from_xform3_impl!(Xform3);
 from_xform3_impl!(&Xform3);
 
+macro_rules! mat4_mul_impl {
+    ($lhs:ty, $rhs:ty) => {
+        impl Mul<$rhs> for $lhs {
+            type Output = Mat4;
+            #[inline]
+            fn mul(self, rhs: $rhs) -> Self::Output {
+                mat4_mul(&self, &rhs)
+            }
+        }
+    };
+}
+
+mat4_mul_impl!(Mat4, Mat4);
+mat4_mul_impl!(&Mat4, Mat4);
+mat4_mul_impl!(&Mat4, &Mat4);
+mat4_mul_impl!(Mat4, &Mat4);
+
+macro_rules! vec4_mul_impl {
+    ($mat:ty, $vec:ty) => {
+        impl Mul<$vec> for $mat {
+            type Output = V4;
+            #[inline]
+            fn mul(self, rhs: $vec) -> Self::Output {
+                mat4_mul_vec4(&self, V4(rhs.0))
+            }
+        }
+    };
+}
+
+vec4_mul_impl!(Mat4, V4);
+vec4_mul_impl!(&Mat4, V4);
+vec4_mul_impl!(&Mat4, &V4);
+vec4_mul_impl!(Mat4, &V4);
+
 macro_rules! vec3_mul_impl {
     ($mat:ty, $vec:ty) => {
+        /// Transforms a point, translation included, like
+        /// `Mat4::transform_point`. Before the column-major product this
+        /// dotted each column with the point and dropped the translation,
+        /// use `Mat4::transform_vector` for that.
         impl Mul<$vec> for $mat {
             type Output = V3;
             #[inline]
             fn mul(self, rhs: $vec) -> Self::Output {
                 let rhs = rhs.extended(1.0);
-                let [a, b, c, _] = self.0;
-                V3([a.dot(rhs), b.dot(rhs), c.dot(rhs)])
+                (self * rhs).narrowed().0
             }
         }
     };
@@ -121,3 +311,311 @@ vec3_mul_impl!(Mat4, V3);
 vec3_mul_impl!(&Mat4, V3);
 vec3_mul_impl!(&Mat4, &V3);
 vec3_mul_impl!(Mat4, &V3);
+
+/// Column-major 3x3 matrix (each `V3` is a column).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mat3(pub [V3; 3]);
+
+impl Mat3 {
+    pub const IDENTITY: Self = Self([
+        V3([1.0, 0.0, 0.0]),
+        V3([0.0, 1.0, 0.0]),
+        V3([0.0, 0.0, 1.0]),
+    ]);
+
+    #[inline]
+    pub const fn from_scale(scale: V3) -> Self {
+        let [x, y, z] = scale.0;
+        Self([V3([x, 0.0, 0.0]), V3([0.0, y, 0.0]), V3([0.0, 0.0, z])])
+    }
+
+    #[inline]
+    pub fn transposed(&self) -> Self {
+        let [a, b, c] = self.0;
+        Mat3([
+            V3([a.0[0], b.0[0], c.0[0]]),
+            V3([a.0[1], b.0[1], c.0[1]]),
+            V3([a.0[2], b.0[2], c.0[2]]),
+        ])
+    }
+
+    #[inline]
+    pub fn determinant(&self) -> f32 {
+        let [a, b, c] = self.0;
+        a.dot(b.cross(c))
+    }
+
+    /// Returns `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        self.inverse_transposed().map(|mat| mat.transposed())
+    }
+
+    /// The matrix that transforms normals for this linear transform, so they
+    /// stay perpendicular to surfaces under non-uniform scale.
+    #[inline]
+    pub fn normal_matrix(&self) -> Option<Self> {
+        self.inverse_transposed()
+    }
+
+    #[inline]
+    fn inverse_transposed(&self) -> Option<Self> {
+        let [a, b, c] = self.0;
+        let bc = b.cross(c);
+        let rcp_det = 1.0 / a.dot(bc);
+        if !rcp_det.is_finite() {
+            return None;
+        }
+        Some(Mat3([
+            bc * rcp_det,
+            c.cross(a) * rcp_det,
+            a.cross(b) * rcp_det,
+        ]))
+    }
+}
+
+impl Mat4 {
+    /// Inverse-transpose of the upper 3x3. See `Mat3::normal_matrix`.
+    #[inline]
+    pub fn normal_matrix(&self) -> Option<Mat3> {
+        Mat3::from(self).normal_matrix()
+    }
+}
+
+macro_rules! mat3_from_mat4_impl {
+    ($mat:ty) => {
+        impl From<$mat> for Mat3 {
+            #[inline]
+            fn from(mat: $mat) -> Self {
+                let [a, b, c, _] = mat.0;
+                Mat3([a.narrowed().0, b.narrowed().0, c.narrowed().0])
+            }
+        }
+    };
+}
+
+mat3_from_mat4_impl!(Mat4);
+mat3_from_mat4_impl!(&Mat4);
+
+macro_rules! mat4_from_mat3_impl {
+    ($mat:ty) => {
+        impl From<$mat> for Mat4 {
+            #[inline]
+            fn from(mat: $mat) -> Self {
+                let [a, b, c] = mat.0;
+                Mat4([
+                    a.extended(0.0),
+                    b.extended(0.0),
+                    c.extended(0.0),
+                    V4([0.0, 0.0, 0.0, 1.0]),
+                ])
+            }
+        }
+    };
+}
+
+mat4_from_mat3_impl!(Mat3);
+mat4_from_mat3_impl!(&Mat3);
+
+macro_rules! mat3_from_quat_impl {
+    ($quat:ty) => {
+        impl From<$quat> for Mat3 {
+            #[inline]
+            fn from(quat: $quat) -> Self {
+                Mat3::from(Mat4::from(quat))
+            }
+        }
+    };
+}
+
+mat3_from_quat_impl!(Quat);
+mat3_from_quat_impl!(&Quat);
+
+macro_rules! mat3_from_xform3_impl {
+    ($xform:ty) => {
+        impl From<$xform> for Mat3 {
+            /// The rotation and scale of the transform (translation is dropped).
+            #[inline]
+            fn from(xform: $xform) -> Self {
+                Mat3::from(Mat4::from(xform))
+            }
+        }
+    };
+}
+
+mat3_from_xform3_impl!(Xform3);
+mat3_from_xform3_impl!(&Xform3);
+
+macro_rules! mat3_mul_impl {
+    ($lhs:ty, $rhs:ty) => {
+        impl Mul<$rhs> for $lhs {
+            type Output = Mat3;
+            #[inline]
+            fn mul(self, rhs: $rhs) -> Self::Output {
+                let [a, b, c] = rhs.0;
+                Mat3([self * a, self * b, self * c])
+            }
+        }
+    };
+}
+
+mat3_mul_impl!(Mat3, Mat3);
+mat3_mul_impl!(&Mat3, Mat3);
+mat3_mul_impl!(&Mat3, &Mat3);
+mat3_mul_impl!(Mat3, &Mat3);
+
+macro_rules! mat3_mul_vec3_impl {
+    ($mat:ty, $vec:ty) => {
+        impl Mul<$vec> for $mat {
+            type Output = V3;
+            #[inline]
+            fn mul(self, rhs: $vec) -> Self::Output {
+                let [x, y, z] = rhs.0;
+                let [a, b, c] = self.0;
+                (a * x) + (b * y) + (c * z)
+            }
+        }
+    };
+}
+
+mat3_mul_vec3_impl!(Mat3, V3);
+mat3_mul_vec3_impl!(&Mat3, V3);
+mat3_mul_vec3_impl!(&Mat3, &V3);
+mat3_mul_vec3_impl!(Mat3, &V3);
+
+/// Column-major 2x2 matrix (each `V2` is a column).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mat2(pub [V2; 2]);
+
+impl Mat2 {
+    pub const IDENTITY: Self = Self([V2([1.0, 0.0]), V2([0.0, 1.0])]);
+
+    /// Counter-clockwise rotation by `theta` radians.
+    #[inline]
+    pub fn from_angle(theta: f32) -> Self {
+        let (sin, cos) = theta.sin_cos();
+        Self([V2([cos, sin]), V2([-sin, cos])])
+    }
+
+    #[inline]
+    pub const fn from_scale(scale: V2) -> Self {
+        let [x, y] = scale.0;
+        Self([V2([x, 0.0]), V2([0.0, y])])
+    }
+
+    #[inline]
+    pub fn transposed(&self) -> Self {
+        let [V2([a, b]), V2([c, d])] = self.0;
+        Self([V2([a, c]), V2([b, d])])
+    }
+
+    #[inline]
+    pub fn determinant(&self) -> f32 {
+        let [V2([a, b]), V2([c, d])] = self.0;
+        (a * d) - (b * c)
+    }
+
+    /// Returns `None` if the matrix is singular.
+    #[inline]
+    pub fn inverse(&self) -> Option<Self> {
+        let [V2([a, b]), V2([c, d])] = self.0;
+        let rcp_det = 1.0 / self.determinant();
+        if !rcp_det.is_finite() {
+            return None;
+        }
+        Some(Self([
+            V2([d * rcp_det, -b * rcp_det]),
+            V2([-c * rcp_det, a * rcp_det]),
+        ]))
+    }
+
+    /// See `Mat3::normal_matrix`.
+    #[inline]
+    pub fn normal_matrix(&self) -> Option<Self> {
+        self.inverse().map(|mat| mat.transposed())
+    }
+}
+
+macro_rules! mat2_from_mat3_impl {
+    ($mat:ty) => {
+        impl From<$mat> for Mat2 {
+            #[inline]
+            fn from(mat: $mat) -> Self {
+                let [V3([a, b, _]), V3([c, d, _]), _] = mat.0;
+                Mat2([V2([a, b]), V2([c, d])])
+            }
+        }
+    };
+}
+
+mat2_from_mat3_impl!(Mat3);
+mat2_from_mat3_impl!(&Mat3);
+
+macro_rules! mat2_mul_impl {
+    ($lhs:ty, $rhs:ty) => {
+        impl Mul<$rhs> for $lhs {
+            type Output = Mat2;
+            #[inline]
+            fn mul(self, rhs: $rhs) -> Self::Output {
+                let [a, b] = rhs.0;
+                Mat2([self * a, self * b])
+            }
+        }
+    };
+}
+
+mat2_mul_impl!(Mat2, Mat2);
+mat2_mul_impl!(&Mat2, Mat2);
+mat2_mul_impl!(&Mat2, &Mat2);
+mat2_mul_impl!(Mat2, &Mat2);
+
+macro_rules! mat2_mul_vec2_impl {
+    ($mat:ty, $vec:ty) => {
+        impl Mul<$vec> for $mat {
+            type Output = V2;
+            #[inline]
+            fn mul(self, rhs: $vec) -> Self::Output {
+                let [x, y] = rhs.0;
+                let [a, b] = self.0;
+                (a * x) + (b * y)
+            }
+        }
+    };
+}
+
+mat2_mul_vec2_impl!(Mat2, V2);
+mat2_mul_vec2_impl!(&Mat2, V2);
+mat2_mul_vec2_impl!(&Mat2, &V2);
+mat2_mul_vec2_impl!(Mat2, &V2);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::fixtures::turn_and_translate;
+
+    #[test]
+    fn mat4_mul_v3_is_a_column_major_point_transform() {
+        let m = turn_and_translate();
+        let p = V3([1.0, 2.0, 3.0]);
+        assert_eq!((m * p).0, [8.0, 21.0, 33.0]);
+        assert_eq!((m * p).0, m.transform_point(p).0);
+        assert_eq!(m.transform_vector(p).0, [-2.0, 1.0, 3.0]);
+    }
+
+    #[test]
+    fn mat4_from_xform3_rotates_like_the_quat() {
+        let xform = Xform3 {
+            pos: V3([10.0, 0.0, 0.0]),
+            scale: V3([2.0, 2.0, 2.0]),
+            rot: Quat::from_axis_angle(V3::FORWARD, std::f32::consts::FRAC_PI_2),
+        };
+        let m = Mat4::from(&xform);
+        let p = m.transform_point(V3::RIGHT);
+        assert!((p - V3([10.0, 2.0, 0.0])).length() < 1.0e-5, "{p:?}");
+        let v = m.transform_vector(V3::UP);
+        assert!((v - V3([-2.0, 0.0, 0.0])).length() < 1.0e-5, "{v:?}");
+    }
+}
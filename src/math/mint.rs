@@ -0,0 +1,97 @@
+//! Conversions to and from `mint`, the common interop types most math
+//! crates can convert through.
+
+use super::{IV2, IV3, IV4, Mat2, Mat3, Mat4, Quat, UV2, UV3, UV4, V2, V3, V4};
+
+macro_rules! mint_vec_impl {
+    ($vec:ident, $mint:ident, $scalar:ident) => {
+        impl From<$vec> for mint::$mint<$scalar> {
+            #[inline]
+            fn from(value: $vec) -> Self {
+                value.0.into()
+            }
+        }
+
+        impl From<mint::$mint<$scalar>> for $vec {
+            #[inline]
+            fn from(value: mint::$mint<$scalar>) -> Self {
+                Self(value.into())
+            }
+        }
+    };
+}
+
+mint_vec_impl!(V2, Vector2, f32);
+mint_vec_impl!(V3, Vector3, f32);
+mint_vec_impl!(V4, Vector4, f32);
+mint_vec_impl!(IV2, Vector2, i32);
+mint_vec_impl!(IV3, Vector3, i32);
+mint_vec_impl!(IV4, Vector4, i32);
+mint_vec_impl!(UV2, Vector2, u32);
+mint_vec_impl!(UV3, Vector3, u32);
+mint_vec_impl!(UV4, Vector4, u32);
+
+impl From<Quat> for mint::Quaternion<f32> {
+    #[inline]
+    fn from(value: Quat) -> Self {
+        let (v, s) = value.0.narrowed();
+        Self { v: v.into(), s }
+    }
+}
+
+impl From<mint::Quaternion<f32>> for Quat {
+    #[inline]
+    fn from(value: mint::Quaternion<f32>) -> Self {
+        Self(V3::from(value.v).extended(value.s))
+    }
+}
+
+macro_rules! mint_mat_impl {
+    ($mat:ident, $mint:ident, $col:ident, [$($field:ident),+]) => {
+        impl From<$mat> for mint::$mint<f32> {
+            #[inline]
+            fn from(value: $mat) -> Self {
+                let [$($field),+] = value.0;
+                Self {
+                    $($field: mint::$col::from($field)),+
+                }
+            }
+        }
+
+        impl From<mint::$mint<f32>> for $mat {
+            #[inline]
+            fn from(value: mint::$mint<f32>) -> Self {
+                Self([$(value.$field.into()),+])
+            }
+        }
+    };
+}
+
+mint_mat_impl!(Mat2, ColumnMatrix2, Vector2, [x, y]);
+mint_mat_impl!(Mat3, ColumnMatrix3, Vector3, [x, y, z]);
+mint_mat_impl!(Mat4, ColumnMatrix4, Vector4, [x, y, z, w]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{
+        Xform3,
+        fixtures::{assert_xform_near, trs, turn_and_translate},
+    };
+
+    #[test]
+    fn mat4_translation_lands_in_column_3() {
+        let m = turn_and_translate();
+        let mint = mint::ColumnMatrix4::from(m);
+        assert_eq!(<[f32; 4]>::from(mint.w), [10.0, 20.0, 30.0, 1.0]);
+        assert_eq!(<[f32; 4]>::from(mint.x), [0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(Mat4::from(mint).0.map(|col| col.0), m.0.map(|col| col.0));
+    }
+
+    #[test]
+    fn xform3_round_trips_through_mint_matrix() {
+        let xform = trs(V3([2.0, 3.0, 4.0]));
+        let mint = mint::ColumnMatrix4::from(Mat4::from(&xform));
+        assert_xform_near(&Xform3::try_from(Mat4::from(mint)).unwrap(), &xform);
+    }
+}
@@ -1,9 +1,27 @@
+mod color;
+#[cfg(test)]
+mod fixtures;
+mod geom;
+#[cfg(feature = "glam")]
+mod glam;
 mod mat;
+#[cfg(feature = "mint")]
+mod mint;
+#[cfg(feature = "nalgebra")]
+mod nalgebra;
 mod quat;
+mod rand;
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod simd;
+mod spline;
 mod vec;
 mod xform;
 
+pub use color::*;
+pub use geom::*;
 pub use mat::*;
 pub use quat::*;
+pub use rand::*;
+pub use spline::*;
 pub use vec::*;
 pub use xform::*;
@@ -0,0 +1,158 @@
+//! Conversions to and from `nalgebra`. Both crates store matrices
+//! column-major, so matrix conversions are plain copies.
+
+use nalgebra::{
+    Affine3, Isometry3, Matrix2, Matrix3, Matrix4, Quaternion, Translation3, UnitQuaternion,
+    Vector2, Vector3, Vector4,
+};
+
+use super::{
+    DecomposeError, IV2, IV3, IV4, Mat2, Mat3, Mat4, Quat, UV2, UV3, UV4, V2, V3, V4, Xform3,
+};
+
+macro_rules! nalgebra_vec_impl {
+    ($vec:ident, $na:ident, $scalar:ident) => {
+        impl From<$vec> for $na<$scalar> {
+            #[inline]
+            fn from(value: $vec) -> Self {
+                Self::from(value.0)
+            }
+        }
+
+        impl From<$na<$scalar>> for $vec {
+            #[inline]
+            fn from(value: $na<$scalar>) -> Self {
+                Self(value.into())
+            }
+        }
+    };
+}
+
+nalgebra_vec_impl!(V2, Vector2, f32);
+nalgebra_vec_impl!(V3, Vector3, f32);
+nalgebra_vec_impl!(V4, Vector4, f32);
+nalgebra_vec_impl!(IV2, Vector2, i32);
+nalgebra_vec_impl!(IV3, Vector3, i32);
+nalgebra_vec_impl!(IV4, Vector4, i32);
+nalgebra_vec_impl!(UV2, Vector2, u32);
+nalgebra_vec_impl!(UV3, Vector3, u32);
+nalgebra_vec_impl!(UV4, Vector4, u32);
+
+impl From<Quat> for Quaternion<f32> {
+    #[inline]
+    fn from(value: Quat) -> Self {
+        // `coords` is stored as `[i, j, k, w]`, same as `Quat`
+        Self::from_vector(value.0.into())
+    }
+}
+
+impl From<Quaternion<f32>> for Quat {
+    #[inline]
+    fn from(value: Quaternion<f32>) -> Self {
+        Self(value.coords.into())
+    }
+}
+
+impl From<Quat> for UnitQuaternion<f32> {
+    #[inline]
+    fn from(value: Quat) -> Self {
+        Self::new_normalize(value.into())
+    }
+}
+
+impl From<UnitQuaternion<f32>> for Quat {
+    #[inline]
+    fn from(value: UnitQuaternion<f32>) -> Self {
+        value.into_inner().into()
+    }
+}
+
+macro_rules! nalgebra_mat_impl {
+    ($mat:ident, $na:ident, $n:literal) => {
+        impl From<$mat> for $na<f32> {
+            #[inline]
+            fn from(value: $mat) -> Self {
+                // nalgebra takes an array of columns
+                Self::from(bytemuck::cast::<_, [[f32; $n]; $n]>(value))
+            }
+        }
+
+        impl From<$na<f32>> for $mat {
+            #[inline]
+            fn from(value: $na<f32>) -> Self {
+                bytemuck::cast::<[[f32; $n]; $n], _>(value.into())
+            }
+        }
+    };
+}
+
+nalgebra_mat_impl!(Mat2, Matrix2, 2);
+nalgebra_mat_impl!(Mat3, Matrix3, 3);
+nalgebra_mat_impl!(Mat4, Matrix4, 4);
+
+impl From<Xform3> for Affine3<f32> {
+    #[inline]
+    fn from(value: Xform3) -> Self {
+        Self::from_matrix_unchecked(Mat4::from(value).into())
+    }
+}
+
+impl TryFrom<Affine3<f32>> for Xform3 {
+    type Error = DecomposeError;
+
+    #[inline]
+    fn try_from(value: Affine3<f32>) -> Result<Self, Self::Error> {
+        Xform3::try_from(Mat4::from(value.into_inner()))
+    }
+}
+
+impl From<Xform3> for Isometry3<f32> {
+    /// Scale is dropped, since isometries only rotate and translate.
+    #[inline]
+    fn from(value: Xform3) -> Self {
+        Self::from_parts(Translation3::from(value.pos.0), value.rot.into())
+    }
+}
+
+impl From<Isometry3<f32>> for Xform3 {
+    #[inline]
+    fn from(value: Isometry3<f32>) -> Self {
+        Self {
+            pos: value.translation.vector.into(),
+            scale: V3::splat(1.0),
+            rot: value.rotation.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::fixtures::{assert_v3_near, assert_xform_near, trs, turn_and_translate};
+
+    #[test]
+    fn mat4_translation_lands_in_column_3() {
+        let m = turn_and_translate();
+        let na = Matrix4::from(m);
+        assert_eq!(na.column(3), Vector4::new(10.0, 20.0, 30.0, 1.0));
+        assert_eq!(na.column(0), Vector4::new(0.0, 1.0, 0.0, 0.0));
+        let p = na.transform_point(&nalgebra::Point3::new(1.0, 2.0, 3.0));
+        assert_eq!(<[f32; 3]>::from(p.coords), (m * V3([1.0, 2.0, 3.0])).0);
+    }
+
+    #[test]
+    fn xform3_round_trips_through_isometry3() {
+        // isometries drop scale
+        let xform = trs(V3::splat(1.0));
+        let iso = Isometry3::from(xform);
+        let p = iso.transform_point(&nalgebra::Point3::new(1.0, 0.0, 0.0));
+        assert_v3_near(p.coords.into(), xform.transform_point(V3::RIGHT));
+        assert_xform_near(&Xform3::from(iso), &xform);
+    }
+
+    #[test]
+    fn xform3_round_trips_through_affine3() {
+        let xform = trs(V3([2.0, 3.0, 4.0]));
+        assert_xform_near(&Xform3::try_from(Affine3::from(xform)).unwrap(), &xform);
+    }
+}
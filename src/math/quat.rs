@@ -1,10 +1,46 @@
-use std::ops::Mul;
+use std::{f32::consts::PI, ops::Mul};
 
-use super::{Cross, V3, V4};
+use super::{Cross, Dot, Mat4, V3, V4};
 
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Quat(pub V4);
 
+/// Order in which per-axis Euler rotations are applied. `XYZ` rotates about
+/// X first, then Y, then Z (all about the fixed world axes).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EulerOrder {
+    XYZ,
+    XZY,
+    YXZ,
+    YZX,
+    ZXY,
+    ZYX,
+}
+
+impl EulerOrder {
+    #[inline]
+    const fn axes(self) -> [usize; 3] {
+        match self {
+            Self::XYZ => [0, 1, 2],
+            Self::XZY => [0, 2, 1],
+            Self::YXZ => [1, 0, 2],
+            Self::YZX => [1, 2, 0],
+            Self::ZXY => [2, 0, 1],
+            Self::ZYX => [2, 1, 0],
+        }
+    }
+
+    #[inline]
+    const fn parity(self) -> f32 {
+        match self {
+            Self::XYZ | Self::YZX | Self::ZXY => 1.0,
+            Self::XZY | Self::YXZ | Self::ZYX => -1.0,
+        }
+    }
+}
+
 impl Quat {
     pub const IDENTITY: Self = Self(V4([0.0, 0.0, 0.0, 1.0]));
 
@@ -26,18 +62,186 @@ impl Quat {
     pub fn normalized(&self) -> Self {
         Self(self.0.normalized())
     }
+
+    #[inline]
+    pub fn conjugate(&self) -> Self {
+        let V4([x, y, z, w]) = self.0;
+        Self(V4([-x, -y, -z, w]))
+    }
+
+    #[inline]
+    pub fn inverse(&self) -> Self {
+        Self(self.conjugate().0 / self.0.normal_squared())
+    }
+
+    /// Normalized linear interpolation along the shortest path.
+    #[inline]
+    pub fn nlerp(&self, rhs: Quat, t: f32) -> Self {
+        let rhs = if self.dot(rhs) < 0.0 { -rhs.0 } else { rhs.0 };
+        Self((self.0 + (rhs - self.0) * t).normalized())
+    }
+
+    /// Spherical linear interpolation along the shortest path.
+    pub fn slerp(&self, rhs: Quat, t: f32) -> Self {
+        let mut cos_theta = self.dot(rhs);
+        let rhs = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            -rhs.0
+        } else {
+            rhs.0
+        };
+        // nearly parallel, sin(theta) is too small to divide by
+        if cos_theta > 0.9995 {
+            return Self((self.0 + (rhs - self.0) * t).normalized());
+        }
+        let theta = cos_theta.acos();
+        let sin_theta = theta.sin();
+        let a = ((1.0 - t) * theta).sin() / sin_theta;
+        let b = (t * theta).sin() / sin_theta;
+        Self(self.0 * a + rhs * b)
+    }
+
+    /// Build a rotation from per-axis angles (in radians) applied in `order`.
+    pub fn from_euler(order: EulerOrder, angles: V3) -> Self {
+        let [i, j, k] = order.axes();
+        let axis = |n: usize| {
+            let mut v = V3::splat(0.0);
+            v.0[n] = 1.0;
+            Quat::from_axis_angle(v, angles.0[n])
+        };
+        axis(k) * axis(j) * axis(i)
+    }
+
+    /// Extract per-axis angles (in radians) that reproduce this rotation when
+    /// passed to `from_euler` with the same `order`.
+    pub fn to_euler(&self, order: EulerOrder) -> V3 {
+        let [i, j, k] = order.axes();
+        let s = order.parity();
+        let m = Mat4::from(self);
+        let r = |row: usize, col: usize| m.0[col].0[row];
+        let sin_b = (-s * r(k, i)).clamp(-1.0, 1.0);
+        let mut angles = V3::splat(0.0);
+        angles.0[j] = sin_b.asin();
+        if sin_b.abs() < 0.9999 {
+            angles.0[i] = (s * r(k, j)).atan2(r(k, k));
+            angles.0[k] = (s * r(j, i)).atan2(r(i, i));
+        } else {
+            // gimbal lock, fold everything into the first rotation
+            angles.0[i] = (-s * r(j, k)).atan2(r(j, j));
+        }
+        angles
+    }
+
+    /// Shortest rotation taking direction `from` onto direction `to`.
+    pub fn from_rotation_arc(from: V3, to: V3) -> Self {
+        let from = from.normalized();
+        let to = to.normalized();
+        let d = from.dot(to);
+        if d < -0.9999 {
+            // opposite directions, any perpendicular axis will do
+            let axis = if from.0[0].abs() < 0.9 {
+                V3::RIGHT.cross(from)
+            } else {
+                V3::UP.cross(from)
+            };
+            return Self::from_axis_angle(axis, PI);
+        }
+        let V3([x, y, z]) = from.cross(to);
+        Self(V4([x, y, z, 1.0 + d]).normalized())
+    }
+
+    /// Rotation taking `V3::FORWARD` onto `forward` while keeping `V3::UP`
+    /// as close to `up` as possible.
+    pub fn look_rotation(forward: V3, up: V3) -> Self {
+        let z = forward.normalized();
+        let x = up.cross(z).normalized();
+        let y = z.cross(x);
+        Self::from(Mat4([
+            x.extended(0.0),
+            y.extended(0.0),
+            z.extended(0.0),
+            V4([0.0, 0.0, 0.0, 1.0]),
+        ]))
+    }
+}
+
+macro_rules! from_mat4_impl {
+    ($mat:ty) => {
+        impl From<$mat> for Quat {
+            /// Extract the rotation from a matrix. The upper 3x3 must be a
+            /// pure rotation (no scale or shear).
+            fn from(mat: $mat) -> Self {
+                let r = |row: usize, col: usize| mat.0[col].0[row];
+                let trace = r(0, 0) + r(1, 1) + r(2, 2);
+                let q = if trace > 0.0 {
+                    let s = (trace + 1.0).sqrt() * 2.0;
+                    V4([
+                        (r(2, 1) - r(1, 2)) / s,
+                        (r(0, 2) - r(2, 0)) / s,
+                        (r(1, 0) - r(0, 1)) / s,
+                        0.25 * s,
+                    ])
+                } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+                    let s = (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt() * 2.0;
+                    V4([
+                        0.25 * s,
+                        (r(0, 1) + r(1, 0)) / s,
+                        (r(0, 2) + r(2, 0)) / s,
+                        (r(2, 1) - r(1, 2)) / s,
+                    ])
+                } else if r(1, 1) > r(2, 2) {
+                    let s = (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt() * 2.0;
+                    V4([
+                        (r(0, 1) + r(1, 0)) / s,
+                        0.25 * s,
+                        (r(1, 2) + r(2, 1)) / s,
+                        (r(0, 2) - r(2, 0)) / s,
+                    ])
+                } else {
+                    let s = (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt() * 2.0;
+                    V4([
+                        (r(0, 2) + r(2, 0)) / s,
+                        (r(1, 2) + r(2, 1)) / s,
+                        0.25 * s,
+                        (r(1, 0) - r(0, 1)) / s,
+                    ])
+                };
+                Quat(q.normalized())
+            }
+        }
+    };
+}
+
+from_mat4_impl!(Mat4);
+from_mat4_impl!(&Mat4);
+
+macro_rules! quat_dot_impl {
+    ($lhs:ty, $rhs:ty) => {
+        impl Dot<$rhs> for $lhs {
+            type Output = f32;
+            #[inline]
+            fn dot(self, rhs: $rhs) -> f32 {
+                self.0.dot(rhs.0)
+            }
+        }
+    };
 }
 
+quat_dot_impl!(Quat, Quat);
+quat_dot_impl!(&Quat, Quat);
+quat_dot_impl!(Quat, &Quat);
+quat_dot_impl!(&Quat, &Quat);
+
 macro_rules! quat_mul_vec3_impl {
     ($lhs:ty, $rhs:ty) => {
+        /// Rotates the vector by the quaternion (`q * v * q^-1`).
         impl Mul<$rhs> for $lhs {
             type Output = V3;
             #[inline]
             fn mul(self, rhs: $rhs) -> V3 {
                 let (v3, w) = self.normalized().0.narrowed();
-                let conj = -v3;
-                let t = conj.cross(rhs) * 2.0;
-                rhs + (t * w) + conj.cross(t)
+                let t = v3.cross(rhs) * 2.0;
+                rhs + (t * w) + v3.cross(t)
             }
         }
     };
@@ -48,20 +252,29 @@ quat_mul_vec3_impl!(&Quat, V3);
 quat_mul_vec3_impl!(&Quat, &V3);
 quat_mul_vec3_impl!(Quat, &V3);
 
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+#[inline]
+fn quat_mul(lhs: &Quat, rhs: &Quat) -> Quat {
+    let V4([x1, y1, z1, w1]) = lhs.0;
+    let V4([x2, y2, z2, w2]) = rhs.0;
+    Quat(V4([
+        w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
+        w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
+        w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
+        w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
+    ]))
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+use super::simd::quat_mul;
+
 macro_rules! quat_mul_impl {
     ($lhs:ty, $rhs:ty) => {
         impl Mul<$rhs> for $lhs {
             type Output = Quat;
             #[inline]
             fn mul(self, rhs: $rhs) -> Quat {
-                let V4([x1, y1, z1, w1]) = self.0;
-                let V4([x2, y2, z2, w2]) = rhs.0;
-                Quat(V4([
-                    w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
-                    w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
-                    w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
-                    w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
-                ]))
+                quat_mul(&self, &rhs)
             }
         }
     };
@@ -71,3 +284,138 @@ quat_mul_impl!(Quat, Quat);
 quat_mul_impl!(&Quat, Quat);
 quat_mul_impl!(Quat, &Quat);
 quat_mul_impl!(&Quat, &Quat);
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    use super::*;
+    use crate::math::fixtures::{assert_rot_near, assert_v3_near};
+
+    const ORDERS: [EulerOrder; 6] = [
+        EulerOrder::XYZ,
+        EulerOrder::XZY,
+        EulerOrder::YXZ,
+        EulerOrder::YZX,
+        EulerOrder::ZXY,
+        EulerOrder::ZYX,
+    ];
+
+    #[test]
+    fn slerp_and_nlerp_endpoints_and_midpoint() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_axis_angle(V3::UP, FRAC_PI_2);
+        let mid = Quat::from_axis_angle(V3::UP, FRAC_PI_4);
+        for lerp in [Quat::slerp, Quat::nlerp] {
+            assert_rot_near(lerp(&a, b, 0.0), a);
+            assert_rot_near(lerp(&a, b, 0.5), mid);
+            assert_rot_near(lerp(&a, b, 1.0), b);
+        }
+        // slerp is constant speed, a quarter of the way is a quarter turn
+        let quarter = Quat::from_axis_angle(V3::UP, FRAC_PI_2 / 4.0);
+        assert_rot_near(a.slerp(b, 0.25), quarter);
+    }
+
+    #[test]
+    fn slerp_and_nlerp_take_the_shortest_path() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_axis_angle(V3::UP, FRAC_PI_2);
+        // same rotation, opposite hemisphere
+        let flipped = Quat(-b.0);
+        let mid = Quat::from_axis_angle(V3::UP, FRAC_PI_4);
+        for lerp in [Quat::slerp, Quat::nlerp] {
+            let q = lerp(&a, flipped, 0.5);
+            assert_rot_near(q, mid);
+            assert!(q.dot(a) > 0.0);
+        }
+    }
+
+    #[test]
+    fn euler_round_trips_every_order() {
+        for order in ORDERS {
+            let angles = V3([0.3, -0.7, 1.1]);
+            let back = Quat::from_euler(order, angles).to_euler(order);
+            assert_v3_near(back, angles);
+        }
+    }
+
+    #[test]
+    fn euler_round_trips_through_gimbal_lock() {
+        for order in ORDERS {
+            let [i, j, k] = order.axes();
+            for middle in [FRAC_PI_2, -FRAC_PI_2] {
+                let mut angles = V3::splat(0.0);
+                angles.0[i] = 0.4;
+                angles.0[j] = middle;
+                angles.0[k] = -0.2;
+                let q = Quat::from_euler(order, angles);
+                let back = q.to_euler(order);
+                assert!((back.0[j] - middle).abs() < 1.0e-2, "{order:?} {back:?}");
+                // the first and last angles are folded together, but the
+                // rotation survives
+                assert_rot_near(Quat::from_euler(order, back), q);
+            }
+        }
+    }
+
+    #[test]
+    fn euler_applies_the_first_axis_first() {
+        // X then Z: +Y goes to +Z, then stays
+        let q = Quat::from_euler(EulerOrder::XYZ, V3([FRAC_PI_2, 0.0, FRAC_PI_2]));
+        assert_v3_near(q * V3::UP, V3::FORWARD);
+        // Z then X: +Y goes to -X, then stays
+        let q = Quat::from_euler(EulerOrder::ZYX, V3([FRAC_PI_2, 0.0, FRAC_PI_2]));
+        assert_v3_near(q * V3::UP, V3::LEFT);
+    }
+
+    #[test]
+    fn rotation_arc_between_parallel_and_opposite_vectors() {
+        let dirs = [V3::RIGHT, V3::UP, V3::FORWARD, V3([1.0, 2.0, -3.0])];
+        for from in dirs {
+            let unit = from.normalized();
+            assert_rot_near(Quat::from_rotation_arc(from, from * 3.0), Quat::IDENTITY);
+            let q = Quat::from_rotation_arc(from, -from);
+            assert_v3_near(q * unit, -unit);
+        }
+        let q = Quat::from_rotation_arc(V3::RIGHT, V3::UP);
+        assert_rot_near(q, Quat::from_axis_angle(V3::FORWARD, FRAC_PI_2));
+    }
+
+    #[test]
+    fn look_rotation_points_forward_and_keeps_up() {
+        let q = Quat::look_rotation(V3::FORWARD, V3::UP);
+        assert_rot_near(q, Quat::IDENTITY);
+
+        let q = Quat::look_rotation(V3::RIGHT, V3::UP);
+        assert_v3_near(q * V3::FORWARD, V3::RIGHT);
+        assert_v3_near(q * V3::UP, V3::UP);
+
+        // up is only a hint, it gets orthogonalized
+        let forward = V3([1.0, 1.0, 0.0]);
+        let q = Quat::look_rotation(forward, V3::UP);
+        assert_v3_near(q * V3::FORWARD, forward.normalized());
+        assert!((q * V3::UP).dot(V3::UP) > 0.0);
+    }
+
+    #[test]
+    fn mat4_round_trip() {
+        let rots = [
+            Quat::IDENTITY,
+            Quat::from_axis_angle(V3([1.0, 2.0, 3.0]), 0.8),
+            Quat::from_axis_angle(V3::RIGHT, PI),
+            Quat::from_axis_angle(V3::UP, PI),
+            Quat::from_axis_angle(V3::FORWARD, PI),
+            Quat::from_axis_angle(V3([-1.0, 0.5, 0.2]), 3.0),
+        ];
+        for q in rots {
+            assert_rot_near(Quat::from(Mat4::from(q)), q);
+        }
+    }
+
+    #[test]
+    fn mul_v3_rotates_by_q_not_its_conjugate() {
+        let q = Quat::from_axis_angle(V3::FORWARD, FRAC_PI_2);
+        assert_v3_near(q * V3::RIGHT, V3::UP);
+        assert_v3_near(q.conjugate() * V3::RIGHT, V3::DOWN);
+    }
+}
@@ -0,0 +1,269 @@
+//! SSE2 (and AVX/FMA, when enabled at compile time) kernels for the
+//! `[f32; 4]`-backed types. Layouts are unchanged; values are moved in and
+//! out of registers with `bytemuck::cast`.
+//!
+//! SSE and SSE2 are part of the x86_64 baseline, and the AVX/FMA paths are
+//! only compiled when those features are enabled for the whole build, so
+//! every intrinsic call below is sound.
+
+use std::{
+    arch::x86_64::*,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use super::{Dot, Mat4, Quat, V4};
+
+#[inline(always)]
+fn load(v: [f32; 4]) -> __m128 {
+    bytemuck::cast(v)
+}
+
+#[inline(always)]
+fn store(v: __m128) -> V4 {
+    bytemuck::cast(v)
+}
+
+#[cfg(target_feature = "fma")]
+#[inline(always)]
+fn mul_add(a: __m128, b: __m128, c: __m128) -> __m128 {
+    unsafe { _mm_fmadd_ps(a, b, c) }
+}
+
+#[cfg(not(target_feature = "fma"))]
+#[inline(always)]
+fn mul_add(a: __m128, b: __m128, c: __m128) -> __m128 {
+    unsafe { _mm_add_ps(_mm_mul_ps(a, b), c) }
+}
+
+#[inline(always)]
+fn dot4(a: __m128, b: __m128) -> f32 {
+    unsafe {
+        let m = _mm_mul_ps(a, b);
+        // (y, x, w, z)
+        let shuf = _mm_shuffle_ps::<0b10_11_00_01>(m, m);
+        // (x + y, _, z + w, _)
+        let sums = _mm_add_ps(m, shuf);
+        let shuf = _mm_movehl_ps(shuf, sums);
+        _mm_cvtss_f32(_mm_add_ss(sums, shuf))
+    }
+}
+
+macro_rules! vec4_binop {
+    ($op_trait:ident, $op_name:ident, $intrin:ident) => {
+        impl $op_trait<V4> for V4 {
+            type Output = V4;
+            #[inline]
+            fn $op_name(self, rhs: V4) -> Self::Output {
+                unsafe { store($intrin(load(self.0), load(rhs.0))) }
+            }
+        }
+
+        impl $op_trait<V4> for &V4 {
+            type Output = V4;
+            #[inline]
+            fn $op_name(self, rhs: V4) -> Self::Output {
+                unsafe { store($intrin(load(self.0), load(rhs.0))) }
+            }
+        }
+
+        impl $op_trait<&V4> for V4 {
+            type Output = V4;
+            #[inline]
+            fn $op_name(self, rhs: &V4) -> Self::Output {
+                unsafe { store($intrin(load(self.0), load(rhs.0))) }
+            }
+        }
+
+        impl $op_trait<&V4> for &V4 {
+            type Output = V4;
+            #[inline]
+            fn $op_name(self, rhs: &V4) -> Self::Output {
+                unsafe { store($intrin(load(self.0), load(rhs.0))) }
+            }
+        }
+
+        impl $op_trait<f32> for V4 {
+            type Output = V4;
+            #[inline]
+            fn $op_name(self, rhs: f32) -> Self::Output {
+                unsafe { store($intrin(load(self.0), _mm_set1_ps(rhs))) }
+            }
+        }
+
+        impl $op_trait<f32> for &V4 {
+            type Output = V4;
+            #[inline]
+            fn $op_name(self, rhs: f32) -> Self::Output {
+                unsafe { store($intrin(load(self.0), _mm_set1_ps(rhs))) }
+            }
+        }
+    };
+}
+
+vec4_binop!(Add, add, _mm_add_ps);
+vec4_binop!(Sub, sub, _mm_sub_ps);
+vec4_binop!(Mul, mul, _mm_mul_ps);
+vec4_binop!(Div, div, _mm_div_ps);
+
+impl Neg for V4 {
+    type Output = V4;
+
+    #[inline]
+    fn neg(self) -> Self::Output {
+        unsafe { store(_mm_xor_ps(load(self.0), _mm_set1_ps(-0.0))) }
+    }
+}
+
+macro_rules! vec4_dot {
+    ($lhs:ty, $rhs:ty) => {
+        impl Dot<$rhs> for $lhs {
+            type Output = f32;
+            #[inline]
+            fn dot(self, rhs: $rhs) -> f32 {
+                dot4(load(self.0), load(rhs.0))
+            }
+        }
+    };
+}
+
+vec4_dot!(V4, V4);
+vec4_dot!(V4, &V4);
+vec4_dot!(&V4, V4);
+vec4_dot!(&V4, &V4);
+
+#[inline]
+pub(super) fn quat_mul(lhs: &Quat, rhs: &Quat) -> Quat {
+    unsafe {
+        let V4([x1, y1, z1, w1]) = lhs.0;
+        let b = load(rhs.0.0);
+        // (w, z, y, x) * (+, -, +, -)
+        let bx = _mm_mul_ps(
+            _mm_shuffle_ps::<0b00_01_10_11>(b, b),
+            _mm_set_ps(-1.0, 1.0, -1.0, 1.0),
+        );
+        // (z, w, x, y) * (+, +, -, -)
+        let by = _mm_mul_ps(
+            _mm_shuffle_ps::<0b01_00_11_10>(b, b),
+            _mm_set_ps(-1.0, -1.0, 1.0, 1.0),
+        );
+        // (y, x, w, z) * (-, +, +, -)
+        let bz = _mm_mul_ps(
+            _mm_shuffle_ps::<0b10_11_00_01>(b, b),
+            _mm_set_ps(-1.0, 1.0, 1.0, -1.0),
+        );
+        let r = _mm_mul_ps(_mm_set1_ps(w1), b);
+        let r = mul_add(_mm_set1_ps(x1), bx, r);
+        let r = mul_add(_mm_set1_ps(y1), by, r);
+        let r = mul_add(_mm_set1_ps(z1), bz, r);
+        Quat(store(r))
+    }
+}
+
+#[inline]
+pub(super) fn mat4_mul_vec4(lhs: &Mat4, rhs: V4) -> V4 {
+    unsafe {
+        let [a, b, c, d] = lhs.0;
+        let V4([x, y, z, w]) = rhs;
+        let r = _mm_mul_ps(load(a.0), _mm_set1_ps(x));
+        let r = mul_add(load(b.0), _mm_set1_ps(y), r);
+        let r = mul_add(load(c.0), _mm_set1_ps(z), r);
+        store(mul_add(load(d.0), _mm_set1_ps(w), r))
+    }
+}
+
+#[cfg(not(target_feature = "avx"))]
+#[inline]
+pub(super) fn mat4_mul(lhs: &Mat4, rhs: &Mat4) -> Mat4 {
+    let [a, b, c, d] = rhs.0;
+    Mat4([
+        mat4_mul_vec4(lhs, a),
+        mat4_mul_vec4(lhs, b),
+        mat4_mul_vec4(lhs, c),
+        mat4_mul_vec4(lhs, d),
+    ])
+}
+
+/// Two result columns per 256-bit register.
+#[cfg(target_feature = "avx")]
+#[inline]
+pub(super) fn mat4_mul(lhs: &Mat4, rhs: &Mat4) -> Mat4 {
+    unsafe {
+        let cols = lhs.0.map(|col| {
+            let col = load(col.0);
+            _mm256_set_m128(col, col)
+        });
+        let pair = |j: usize| {
+            let (lo, hi) = (rhs.0[j].0, rhs.0[j + 1].0);
+            let mut r = _mm256_setzero_ps();
+            for k in 0..4 {
+                let s = _mm256_set_m128(_mm_set1_ps(hi[k]), _mm_set1_ps(lo[k]));
+                r = _mm256_add_ps(r, _mm256_mul_ps(cols[k], s));
+            }
+            let [lo, hi]: [V4; 2] = bytemuck::cast(r);
+            (lo, hi)
+        };
+        let (a, b) = pair(0);
+        let (c, d) = pair(2);
+        Mat4([a, b, c, d])
+    }
+}
+
+pub(super) fn mat4_inverse(mat: &Mat4) -> Option<Mat4> {
+    unsafe {
+        let [x, y, z, w] = mat.0.map(|col| load(col.0));
+
+        macro_rules! fac {
+            ($a:literal, $b:literal, $c:literal, $d:literal) => {{
+                let swp0a = _mm_shuffle_ps::<$a>(w, z);
+                let swp0b = _mm_shuffle_ps::<$b>(w, z);
+                let swp00 = _mm_shuffle_ps::<$c>(z, y);
+                let swp01 = _mm_shuffle_ps::<0b10_00_00_00>(swp0a, swp0a);
+                let swp02 = _mm_shuffle_ps::<0b10_00_00_00>(swp0b, swp0b);
+                let swp03 = _mm_shuffle_ps::<$d>(z, y);
+                _mm_sub_ps(_mm_mul_ps(swp00, swp01), _mm_mul_ps(swp02, swp03))
+            }};
+        }
+
+        let fac0 = fac!(0b11_11_11_11, 0b10_10_10_10, 0b10_10_10_10, 0b11_11_11_11);
+        let fac1 = fac!(0b11_11_11_11, 0b01_01_01_01, 0b01_01_01_01, 0b11_11_11_11);
+        let fac2 = fac!(0b10_10_10_10, 0b01_01_01_01, 0b01_01_01_01, 0b10_10_10_10);
+        let fac3 = fac!(0b11_11_11_11, 0b00_00_00_00, 0b00_00_00_00, 0b11_11_11_11);
+        let fac4 = fac!(0b10_10_10_10, 0b00_00_00_00, 0b00_00_00_00, 0b10_10_10_10);
+        let fac5 = fac!(0b01_01_01_01, 0b00_00_00_00, 0b00_00_00_00, 0b01_01_01_01);
+
+        macro_rules! col {
+            ($lane:literal) => {{
+                let tmp = _mm_shuffle_ps::<$lane>(y, x);
+                _mm_shuffle_ps::<0b10_10_10_00>(tmp, tmp)
+            }};
+        }
+
+        let vec0 = col!(0b00_00_00_00);
+        let vec1 = col!(0b01_01_01_01);
+        let vec2 = col!(0b10_10_10_10);
+        let vec3 = col!(0b11_11_11_11);
+
+        let sign_a = _mm_set_ps(-1.0, 1.0, -1.0, 1.0);
+        let sign_b = _mm_set_ps(1.0, -1.0, 1.0, -1.0);
+        let inv0 = _mm_sub_ps(_mm_mul_ps(vec1, fac0), _mm_mul_ps(vec2, fac1));
+        let inv0 = _mm_mul_ps(mul_add(vec3, fac2, inv0), sign_a);
+        let inv1 = _mm_sub_ps(_mm_mul_ps(vec0, fac0), _mm_mul_ps(vec2, fac3));
+        let inv1 = _mm_mul_ps(mul_add(vec3, fac4, inv1), sign_b);
+        let inv2 = _mm_sub_ps(_mm_mul_ps(vec0, fac1), _mm_mul_ps(vec1, fac3));
+        let inv2 = _mm_mul_ps(mul_add(vec3, fac5, inv2), sign_a);
+        let inv3 = _mm_sub_ps(_mm_mul_ps(vec0, fac2), _mm_mul_ps(vec1, fac4));
+        let inv3 = _mm_mul_ps(mul_add(vec2, fac5, inv3), sign_b);
+
+        let tmp0 = _mm_shuffle_ps::<0b00_00_00_00>(inv0, inv1);
+        let tmp1 = _mm_shuffle_ps::<0b00_00_00_00>(inv2, inv3);
+        let row0 = _mm_shuffle_ps::<0b10_00_10_00>(tmp0, tmp1);
+        let rcp_det = 1.0 / dot4(x, row0);
+        if !rcp_det.is_finite() {
+            return None;
+        }
+        let rcp_det = _mm_set1_ps(rcp_det);
+        Some(Mat4(
+            [inv0, inv1, inv2, inv3].map(|v| store(_mm_mul_ps(v, rcp_det))),
+        ))
+    }
+}
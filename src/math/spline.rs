@@ -0,0 +1,302 @@
+use std::ops::{Add, Mul, Sub};
+
+use super::{Dot, V2, V3};
+
+/// Anything a curve can be built over.
+pub trait CurvePoint:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self> + Dot<Output = f32>
+{
+}
+
+impl CurvePoint for V2 {}
+impl CurvePoint for V3 {}
+
+/// A curve parameterized over `[0, 1]`.
+pub trait Curve<P: CurvePoint> {
+    fn eval(&self, t: f32) -> P;
+
+    /// First derivative with respect to `t`.
+    fn tangent(&self, t: f32) -> P;
+
+    /// Parameter of the point on the curve closest to `point`. Coarsely
+    /// samples the curve and then refines around the best sample, so very
+    /// tight loops may resolve to a local minimum.
+    fn closest_param(&self, point: P) -> f32 {
+        const SAMPLES: usize = 64;
+        const ITERS: usize = 24;
+        let dist_sq = |t: f32| {
+            let d = self.eval(t) - point;
+            d.dot(d)
+        };
+        let mut best = 0.0;
+        let mut best_dist = f32::INFINITY;
+        for i in 0..=SAMPLES {
+            let t = (i as f32) / (SAMPLES as f32);
+            let d = dist_sq(t);
+            if d < best_dist {
+                best = t;
+                best_dist = d;
+            }
+        }
+        // ternary search inside the neighbouring samples
+        let step = 1.0 / (SAMPLES as f32);
+        let mut lo = (best - step).max(0.0);
+        let mut hi = (best + step).min(1.0);
+        for _ in 0..ITERS {
+            let a = lo + ((hi - lo) / 3.0);
+            let b = hi - ((hi - lo) / 3.0);
+            if dist_sq(a) < dist_sq(b) {
+                hi = b;
+            } else {
+                lo = a;
+            }
+        }
+        (lo + hi) * 0.5
+    }
+
+    #[inline]
+    fn closest_point(&self, point: P) -> P {
+        self.eval(self.closest_param(point))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct CubicBezier<P> {
+    pub p0: P,
+    pub p1: P,
+    pub p2: P,
+    pub p3: P,
+}
+
+impl<P: CurvePoint> Curve<P> for CubicBezier<P> {
+    #[inline]
+    fn eval(&self, t: f32) -> P {
+        let u = 1.0 - t;
+        (self.p0 * (u * u * u))
+            + (self.p1 * (3.0 * u * u * t))
+            + (self.p2 * (3.0 * u * t * t))
+            + (self.p3 * (t * t * t))
+    }
+
+    #[inline]
+    fn tangent(&self, t: f32) -> P {
+        let u = 1.0 - t;
+        ((self.p1 - self.p0) * (3.0 * u * u))
+            + ((self.p2 - self.p1) * (6.0 * u * t))
+            + ((self.p3 - self.p2) * (3.0 * t * t))
+    }
+}
+
+/// Cubic Hermite segment from `p0` to `p1` with tangents `m0` and `m1`.
+#[derive(Copy, Clone, Debug)]
+pub struct Hermite<P> {
+    pub p0: P,
+    pub m0: P,
+    pub p1: P,
+    pub m1: P,
+}
+
+impl<P: CurvePoint> Curve<P> for Hermite<P> {
+    #[inline]
+    fn eval(&self, t: f32) -> P {
+        let t2 = t * t;
+        let t3 = t2 * t;
+        (self.p0 * ((2.0 * t3) - (3.0 * t2) + 1.0))
+            + (self.m0 * (t3 - (2.0 * t2) + t))
+            + (self.p1 * ((-2.0 * t3) + (3.0 * t2)))
+            + (self.m1 * (t3 - t2))
+    }
+
+    #[inline]
+    fn tangent(&self, t: f32) -> P {
+        let t2 = t * t;
+        (self.p0 * ((6.0 * t2) - (6.0 * t)))
+            + (self.m0 * ((3.0 * t2) - (4.0 * t) + 1.0))
+            + (self.p1 * ((-6.0 * t2) + (6.0 * t)))
+            + (self.m1 * ((3.0 * t2) - (2.0 * t)))
+    }
+}
+
+/// Uniform Catmull-Rom spline through every point in `points`. The whole
+/// spline maps onto `[0, 1]`, with each segment taking an equal share.
+#[derive(Clone, Debug)]
+pub struct CatmullRom<P> {
+    points: Vec<P>, // never empty
+    pub closed: bool,
+}
+
+impl<P: CurvePoint> CatmullRom<P> {
+    /// `None` if `points` is empty. A single point gives a spline that stays
+    /// on it with a zero tangent.
+    #[inline]
+    pub fn new(points: Vec<P>, closed: bool) -> Option<Self> {
+        (!points.is_empty()).then_some(Self { points, closed })
+    }
+
+    #[inline]
+    pub fn points(&self) -> &[P] {
+        &self.points
+    }
+
+    #[inline]
+    pub fn segments(&self) -> usize {
+        match (self.points.len(), self.closed) {
+            (0 | 1, _) => 0,
+            (n, true) => n,
+            (n, false) => n - 1,
+        }
+    }
+
+    /// The segment at global parameter `t` as a Hermite curve, and `t`
+    /// relative to that segment. With a single point the segment is constant.
+    pub fn segment(&self, t: f32) -> (Hermite<P>, f32) {
+        let n = self.points.len();
+        let segments = self.segments();
+        if segments == 0 {
+            let p = self.points[0];
+            let zero = p * 0.0;
+            let hermite = Hermite {
+                p0: p,
+                m0: zero,
+                p1: p,
+                m1: zero,
+            };
+            return (hermite, t.clamp(0.0, 1.0));
+        }
+        let st = t.clamp(0.0, 1.0) * (segments as f32);
+        let idx = (st as usize).min(segments - 1);
+        let point = |i: isize| {
+            if self.closed {
+                self.points[i.rem_euclid(n as isize) as usize]
+            } else if i < 0 {
+                // mirror the missing neighbours past the ends
+                (self.points[0] * 2.0) - self.points[1]
+            } else if (i as usize) >= n {
+                (self.points[n - 1] * 2.0) - self.points[n - 2]
+            } else {
+                self.points[i as usize]
+            }
+        };
+        let i = idx as isize;
+        let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+        let hermite = Hermite {
+            p0: p1,
+            m0: (p2 - p0) * 0.5,
+            p1: p2,
+            m1: (p3 - p1) * 0.5,
+        };
+        (hermite, st - (idx as f32))
+    }
+}
+
+impl<P: CurvePoint> Curve<P> for CatmullRom<P> {
+    #[inline]
+    fn eval(&self, t: f32) -> P {
+        let (seg, t) = self.segment(t);
+        seg.eval(t)
+    }
+
+    #[inline]
+    fn tangent(&self, t: f32) -> P {
+        let (seg, t) = self.segment(t);
+        seg.tangent(t) * (self.segments() as f32)
+    }
+}
+
+/// Arc-length lookup table for moving along a curve at constant speed.
+pub struct ArcLength {
+    /// Cumulative length at `i / (lengths.len() - 1)`.
+    lengths: Vec<f32>,
+}
+
+impl ArcLength {
+    pub fn new<P, C>(curve: &C, samples: usize) -> Self
+    where
+        P: CurvePoint,
+        C: Curve<P>,
+    {
+        let samples = samples.max(1);
+        let mut lengths = Vec::with_capacity(samples + 1);
+        let mut total = 0.0;
+        let mut prev = curve.eval(0.0);
+        lengths.push(0.0);
+        for i in 1..=samples {
+            let next = curve.eval((i as f32) / (samples as f32));
+            let d = next - prev;
+            total += d.dot(d).sqrt();
+            lengths.push(total);
+            prev = next;
+        }
+        Self { lengths }
+    }
+
+    #[inline]
+    pub fn length(&self) -> f32 {
+        *self.lengths.last().unwrap()
+    }
+
+    /// Curve parameter at `distance` along the curve.
+    pub fn param(&self, distance: f32) -> f32 {
+        let distance = distance.clamp(0.0, self.length());
+        let samples = self.lengths.len() - 1;
+        let idx = match self
+            .lengths
+            .binary_search_by(|len| len.total_cmp(&distance))
+        {
+            Ok(idx) => return (idx as f32) / (samples as f32),
+            Err(idx) => idx.clamp(1, samples),
+        };
+        let lo = self.lengths[idx - 1];
+        let hi = self.lengths[idx];
+        let frac = if hi > lo {
+            (distance - lo) / (hi - lo)
+        } else {
+            0.0
+        };
+        ((idx - 1) as f32 + frac) / (samples as f32)
+    }
+
+    /// Point at `distance` along `curve`, which must be the curve the table
+    /// was built from.
+    #[inline]
+    pub fn eval<P, C>(&self, curve: &C, distance: f32) -> P
+    where
+        P: CurvePoint,
+        C: Curve<P>,
+    {
+        curve.eval(self.param(distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catmull_rom_rejects_no_points() {
+        assert!(CatmullRom::<V2>::new(Vec::new(), false).is_none());
+        assert!(CatmullRom::<V2>::new(Vec::new(), true).is_none());
+    }
+
+    #[test]
+    fn single_point_catmull_rom_stays_put() {
+        let p = V2([3.0, -2.0]);
+        for closed in [false, true] {
+            let spline = CatmullRom::new(vec![p], closed).unwrap();
+            for t in [0.0, 0.5, 1.0] {
+                assert_eq!(spline.eval(t).0, p.0);
+                assert_eq!(spline.tangent(t).0, [0.0, 0.0]);
+            }
+            assert_eq!(ArcLength::new(&spline, 16).length(), 0.0);
+        }
+    }
+
+    #[test]
+    fn two_point_catmull_rom_runs_between_them() {
+        let (a, b) = (V2([0.0, 0.0]), V2([4.0, 0.0]));
+        let spline = CatmullRom::new(vec![a, b], false).unwrap();
+        assert_eq!(spline.eval(0.0).0, a.0);
+        assert_eq!(spline.eval(1.0).0, b.0);
+        assert!((ArcLength::new(&spline, 64).length() - 4.0).abs() < 1e-4);
+    }
+}
@@ -14,14 +14,17 @@ pub trait Cross<Rhs = Self> {
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct V2(pub [f32; 2]);
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IV2(pub [i32; 2]);
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UV2(pub [u32; 2]);
 
 impl V2 {
@@ -52,6 +55,14 @@ impl From<IV2> for V2 {
     }
 }
 
+impl From<UV2> for V2 {
+    #[inline]
+    fn from(value: UV2) -> Self {
+        let [x, y] = value.0;
+        Self([x as f32, y as f32])
+    }
+}
+
 impl From<UV2> for IV2 {
     #[inline]
     fn from(value: UV2) -> Self {
@@ -224,14 +235,17 @@ vec2_binop!(UV2, u32, Rem, rem);
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct V3(pub [f32; 3]);
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IV3(pub [i32; 3]);
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UV3(pub [u32; 3]);
 
 impl V3 {
@@ -485,14 +499,17 @@ vec3_binop!(UV3, u32, Rem, rem);
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct V4(pub [f32; 4]);
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IV4(pub [i32; 4]);
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UV4(pub [u32; 4]);
 
 impl V4 {
@@ -680,13 +697,21 @@ macro_rules! vec4_dot {
 }
 
 vec4_impl!(V4, f32);
+vec4_binop!(V4, f32, Rem, rem);
+
+// see `simd.rs` for the accelerated versions
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
 vec4_dot!(V4, f32);
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
 vec4_neg!(V4, f32);
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
 vec4_binop!(V4, f32, Add, add);
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
 vec4_binop!(V4, f32, Sub, sub);
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
 vec4_binop!(V4, f32, Mul, mul);
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
 vec4_binop!(V4, f32, Div, div);
-vec4_binop!(V4, f32, Rem, rem);
 
 vec4_impl!(IV4, i32);
 vec4_dot!(IV4, i32);
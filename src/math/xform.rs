@@ -1,6 +1,9 @@
-use super::{Quat, V3};
+use std::fmt;
+
+use super::{Cross, Dot, Mat3, Mat4, Quat, V3, V4};
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Xform3 {
     pub pos: V3,
     pub scale: V3,
@@ -13,8 +16,138 @@ impl Xform3 {
         scale: V3::splat(1.0),
         rot: Quat::IDENTITY,
     };
+
+    /// Inverse transform. Only exact for uniform scale, since a rotation
+    /// followed by non-uniform scale cannot be expressed as an `Xform3`.
+    #[inline]
+    pub fn inverse(&self) -> Self {
+        let rot = self.rot.inverse();
+        let scale = V3::splat(1.0) / self.scale;
+        Self {
+            pos: -(scale * (rot * self.pos)),
+            scale,
+            rot,
+        }
+    }
+
+    #[inline]
+    pub fn transform_point(&self, point: V3) -> V3 {
+        self.pos + (self.rot * (self.scale * point))
+    }
+
+    #[inline]
+    pub fn transform_vector(&self, vector: V3) -> V3 {
+        self.rot * (self.scale * vector)
+    }
+
+    /// Map a point back into the space this transform was applied to.
+    /// Exact for non-uniform scale, unlike `inverse().transform_point()`.
+    #[inline]
+    pub fn inverse_transform_point(&self, point: V3) -> V3 {
+        (self.rot.inverse() * (point - self.pos)) / self.scale
+    }
+
+    #[inline]
+    pub fn inverse_transform_vector(&self, vector: V3) -> V3 {
+        (self.rot.inverse() * vector) / self.scale
+    }
+
+    /// Transforms normals correctly under non-uniform scale. Equivalent to
+    /// `Mat3::from(self).normal_matrix()` without the general inverse.
+    #[inline]
+    pub fn normal_matrix(&self) -> Mat3 {
+        Mat3::from(self.rot) * Mat3::from_scale(V3::splat(1.0) / self.scale)
+    }
+
+    /// Lerp position and scale, slerp rotation.
+    #[inline]
+    pub fn lerp(&self, rhs: &Xform3, t: f32) -> Self {
+        Self {
+            pos: self.pos + (rhs.pos - self.pos) * t,
+            scale: self.scale + (rhs.scale - self.scale) * t,
+            rot: self.rot.slerp(rhs.rot, t),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecomposeError {
+    /// The bottom row is not `[0, 0, 0, 1]`.
+    NotAffine,
+    /// One of the basis vectors has zero length.
+    Degenerate,
+    /// The basis vectors are not orthogonal, so there is no rotation and
+    /// scale that reproduce it.
+    Sheared,
 }
 
+impl fmt::Display for DecomposeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAffine => write!(f, "matrix is not affine"),
+            Self::Degenerate => write!(f, "matrix has a degenerate basis"),
+            Self::Sheared => write!(f, "matrix has a sheared basis"),
+        }
+    }
+}
+
+macro_rules! try_from_mat4_impl {
+    ($mat:ty) => {
+        impl TryFrom<$mat> for Xform3 {
+            type Error = DecomposeError;
+
+            /// Decompose into translation, rotation and scale. A reflection
+            /// is folded into a negative X scale.
+            fn try_from(mat: $mat) -> Result<Self, Self::Error> {
+                const EPSILON: f32 = 1.0e-5;
+                let [a, b, c, d] = mat.0;
+                let (a, aw) = a.narrowed();
+                let (b, bw) = b.narrowed();
+                let (c, cw) = c.narrowed();
+                let (pos, dw) = d.narrowed();
+                if aw.abs() > EPSILON
+                    || bw.abs() > EPSILON
+                    || cw.abs() > EPSILON
+                    || (dw - 1.0).abs() > EPSILON
+                {
+                    return Err(DecomposeError::NotAffine);
+                }
+                let mut sx = a.length();
+                let sy = b.length();
+                let sz = c.length();
+                if sx < EPSILON || sy < EPSILON || sz < EPSILON {
+                    return Err(DecomposeError::Degenerate);
+                }
+                // compare directions, so the tolerance doesn't depend on scale
+                const SHEAR_EPSILON: f32 = 1.0e-4;
+                if (a.dot(b) / (sx * sy)).abs() > SHEAR_EPSILON
+                    || (b.dot(c) / (sy * sz)).abs() > SHEAR_EPSILON
+                    || (c.dot(a) / (sz * sx)).abs() > SHEAR_EPSILON
+                {
+                    return Err(DecomposeError::Sheared);
+                }
+                if a.dot(b.cross(c)) < 0.0 {
+                    sx = -sx;
+                }
+                let rot = Quat::from(Mat4([
+                    (a / sx).extended(0.0),
+                    (b / sy).extended(0.0),
+                    (c / sz).extended(0.0),
+                    V4([0.0, 0.0, 0.0, 1.0]),
+                ]));
+                Ok(Xform3 {
+                    pos,
+                    scale: V3([sx, sy, sz]),
+                    rot,
+                })
+            }
+        }
+    };
+}
+
+try_from_mat4_impl!(Mat4);
+try_from_mat4_impl!(&Mat4);
+
 pub trait Concat<Rhs = Self> {
     type Output;
     fn concat(self, rhs: Rhs) -> Self::Output;
@@ -40,3 +173,78 @@ xform3_concat_impl!(Xform3, Xform3);
 xform3_concat_impl!(&Xform3, Xform3);
 xform3_concat_impl!(Xform3, &Xform3);
 xform3_concat_impl!(&Xform3, &Xform3);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::fixtures::{assert_rot_near, assert_v3_near, assert_xform_near, trs};
+
+    #[test]
+    fn inverse_round_trips_under_uniform_scale() {
+        let xform = trs(V3::splat(2.5));
+        let inv = xform.inverse();
+        assert_xform_near(&xform.concat(inv), &Xform3::IDENTITY);
+        assert_xform_near(&inv.concat(xform), &Xform3::IDENTITY);
+        let p = V3([0.5, 4.0, -1.0]);
+        assert_v3_near(inv.transform_point(xform.transform_point(p)), p);
+        assert_v3_near(inv.transform_vector(xform.transform_vector(p)), p);
+    }
+
+    #[test]
+    fn inverse_transform_is_exact_under_non_uniform_scale() {
+        let xform = trs(V3([2.0, 3.0, 4.0]));
+        let p = V3([0.5, 4.0, -1.0]);
+        assert_v3_near(xform.inverse_transform_point(xform.transform_point(p)), p);
+        assert_v3_near(xform.inverse_transform_vector(xform.transform_vector(p)), p);
+    }
+
+    #[test]
+    fn decomposes_trs_matrices() {
+        let xform = trs(V3([2.0, 3.0, 4.0]));
+        assert_xform_near(&Xform3::try_from(Mat4::from(&xform)).unwrap(), &xform);
+        // a reflection comes back as a negative X scale
+        let mirrored = trs(V3([-2.0, 3.0, 4.0]));
+        assert_xform_near(&Xform3::try_from(Mat4::from(&mirrored)).unwrap(), &mirrored);
+    }
+
+    #[test]
+    fn decomposition_rejects_singular_sheared_and_projective_matrices() {
+        let flat = Mat4::from(&trs(V3([2.0, 3.0, 0.0])));
+        assert_eq!(
+            Xform3::try_from(flat).err(),
+            Some(DecomposeError::Degenerate)
+        );
+
+        let mut sheared = Mat4::from(&trs(V3([2.0, 3.0, 4.0])));
+        sheared.0[1] = sheared.0[1] + sheared.0[0] * 0.5;
+        assert_eq!(
+            Xform3::try_from(sheared).err(),
+            Some(DecomposeError::Sheared)
+        );
+
+        let mut projective = Mat4::from(&trs(V3::splat(1.0)));
+        projective.0[2].0[3] = -1.0;
+        assert_eq!(
+            Xform3::try_from(projective).err(),
+            Some(DecomposeError::NotAffine)
+        );
+    }
+
+    #[test]
+    fn lerp_endpoints_and_midpoint() {
+        let a = trs(V3::splat(1.0));
+        let b = Xform3 {
+            pos: V3([5.0, 0.0, -1.0]),
+            scale: V3([3.0, 1.0, 2.0]),
+            rot: Quat::IDENTITY,
+        };
+        assert_xform_near(&a.lerp(&b, 0.0), &a);
+        assert_xform_near(&b.lerp(&a, 0.0), &b);
+        assert_xform_near(&a.lerp(&b, 1.0), &b);
+        let mid = a.lerp(&b, 0.5);
+        assert_v3_near(mid.pos, V3([3.0, -1.0, 1.0]));
+        assert_v3_near(mid.scale, V3([2.0, 1.0, 1.5]));
+        let half = Quat::from_axis_angle(V3([1.0, 2.0, 3.0]).normalized(), 0.35);
+        assert_rot_near(mid.rot, half);
+    }
+}
@@ -0,0 +1,528 @@
+use std::{
+    alloc::{self, Layout},
+    cell::{Cell, RefCell},
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const CHUNK_ALIGN: usize = 16;
+
+/// Unique across all arenas, so a `FrameSlice` can't validate against the
+/// wrong arena or frame.
+static EPOCH: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Clone, Copy)]
+struct Chunk {
+    ptr: NonNull<u8>,
+    cap: usize,
+}
+
+impl Chunk {
+    fn new(cap: usize) -> Self {
+        if cap == 0 {
+            return Self {
+                ptr: NonNull::dangling(),
+                cap,
+            };
+        }
+        let layout = Layout::from_size_align(cap, CHUNK_ALIGN).unwrap();
+        let Some(ptr) = NonNull::new(unsafe { alloc::alloc(layout) }) else {
+            alloc::handle_alloc_error(layout);
+        };
+        Self { ptr, cap }
+    }
+
+    fn free(self) {
+        if self.cap != 0 {
+            let layout = Layout::from_size_align(self.cap, CHUNK_ALIGN).unwrap();
+            unsafe { alloc::dealloc(self.ptr.as_ptr(), layout) };
+        }
+    }
+}
+
+/// Bump allocator for data that only lives for a frame (or a pass).
+/// Allocations borrow the arena, and `reset` frees them all at once in O(1).
+/// Outgrowing the arena mid-frame chains another chunk, which `reset` folds
+/// into one, so a steady workload stops allocating after the first frames.
+///
+/// Destructors of values in the arena are never run.
+pub struct FrameArena {
+    cur: Cell<Chunk>,
+    used: Cell<usize>,
+    full: RefCell<Vec<Chunk>>, // chunks outgrown this frame
+    epoch: u64,
+}
+
+impl FrameArena {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            cur: Cell::new(Chunk::new(capacity.next_multiple_of(CHUNK_ALIGN))),
+            used: Cell::new(0),
+            full: RefCell::new(Vec::new()),
+            epoch: EPOCH.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Bytes used this frame, counting outgrown chunks in full.
+    #[inline]
+    pub fn used(&self) -> usize {
+        let full: usize = self.full.borrow().iter().map(|chunk| chunk.cap).sum();
+        full + self.used.get()
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        let full: usize = self.full.borrow().iter().map(|chunk| chunk.cap).sum();
+        full + self.cur.get().cap
+    }
+
+    /// Free everything. Chunks chained this frame are merged into one big
+    /// enough for the whole frame.
+    pub fn reset(&mut self) {
+        let full = self.full.get_mut();
+        if !full.is_empty() {
+            let mut cap = self.cur.get().cap;
+            for chunk in full.drain(..) {
+                cap += chunk.cap;
+                chunk.free();
+            }
+            self.cur.get().free();
+            self.cur.set(Chunk::new(cap));
+            log::debug!("Frame arena grew to {cap} bytes");
+        }
+        self.used.set(0);
+        self.epoch = EPOCH.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            return NonNull::new(ptr::without_provenance_mut(layout.align())).unwrap();
+        }
+        let chunk = self.cur.get();
+        let used = self.used.get();
+        let addr = chunk.ptr.as_ptr() as usize + used;
+        let start = used + (addr.next_multiple_of(layout.align()) - addr);
+        if start + layout.size() <= chunk.cap {
+            self.used.set(start + layout.size());
+            return unsafe { chunk.ptr.add(start) };
+        }
+        let cap = (chunk.cap * 2)
+            .max(layout.size() + layout.align())
+            .next_multiple_of(CHUNK_ALIGN);
+        self.full.borrow_mut().push(chunk);
+        let chunk = Chunk::new(cap);
+        self.cur.set(chunk);
+        let addr = chunk.ptr.as_ptr() as usize;
+        let start = addr.next_multiple_of(layout.align()) - addr;
+        self.used.set(start + layout.size());
+        unsafe { chunk.ptr.add(start) }
+    }
+
+    /// Uninitialized room for `len` values of `T`.
+    #[inline]
+    fn alloc_raw<T>(&self, len: usize) -> NonNull<T> {
+        let Ok(layout) = Layout::array::<T>(len) else {
+            crate::fatal!("Frame arena allocation of {len} values overflows");
+        };
+        self.alloc_layout(layout).cast()
+    }
+
+    /// Whether `[ptr, ptr + bytes)` ends exactly at the top of the arena.
+    #[inline]
+    fn is_top(&self, ptr: NonNull<u8>, bytes: usize) -> bool {
+        let chunk = self.cur.get();
+        (ptr.as_ptr() as usize + bytes) == (chunk.ptr.as_ptr() as usize + self.used.get())
+    }
+
+    /// Extend the allocation at the top of the arena in place, if it fits.
+    #[inline]
+    fn try_grow_top(&self, ptr: NonNull<u8>, bytes: usize, extra: usize) -> bool {
+        let chunk = self.cur.get();
+        let used = self.used.get();
+        if !self.is_top(ptr, bytes) || (used + extra > chunk.cap) {
+            return false;
+        }
+        self.used.set(used + extra);
+        true
+    }
+
+    // each call hands out memory no other borrow can reach
+    #[allow(clippy::mut_from_ref)]
+    #[inline]
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_raw::<T>(1);
+        unsafe {
+            ptr.write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    #[inline]
+    pub fn alloc_slice_copy<T: Copy>(&self, data: &[T]) -> &mut [T] {
+        let ptr = self.alloc_raw::<T>(data.len());
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), ptr.as_ptr(), data.len());
+            slice::from_raw_parts_mut(ptr.as_ptr(), data.len())
+        }
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    #[inline]
+    pub fn alloc_slice_fill<T: Copy>(&self, len: usize, value: T) -> &mut [T] {
+        let ptr = self.alloc_raw::<T>(len);
+        unsafe {
+            for i in 0..len {
+                ptr.add(i).write(value);
+            }
+            slice::from_raw_parts_mut(ptr.as_ptr(), len)
+        }
+    }
+
+    /// Copy `data` into the arena behind a handle instead of a borrow, so it
+    /// can be read back after the arena is no longer borrowed (see
+    /// `DoubleArena`).
+    #[inline]
+    pub fn stash<T: Copy>(&self, data: &[T]) -> FrameSlice<T> {
+        let ptr = self.alloc_raw::<T>(data.len());
+        unsafe { ptr::copy_nonoverlapping(data.as_ptr(), ptr.as_ptr(), data.len()) };
+        FrameSlice {
+            ptr,
+            len: data.len(),
+            epoch: self.epoch,
+            _marker: PhantomData,
+        }
+    }
+
+    /// `None` if the slice was stashed in another arena, or before the last
+    /// `reset`.
+    #[inline]
+    pub fn get<T>(&self, slice: FrameSlice<T>) -> Option<&[T]> {
+        if slice.epoch != self.epoch {
+            return None;
+        }
+        Some(unsafe { slice::from_raw_parts(slice.ptr.as_ptr(), slice.len) })
+    }
+}
+
+impl Drop for FrameArena {
+    fn drop(&mut self) {
+        for chunk in self.full.get_mut().drain(..) {
+            chunk.free();
+        }
+        self.cur.get().free();
+    }
+}
+
+/// Handle to data stashed in a `FrameArena`, valid until the arena resets.
+pub struct FrameSlice<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    epoch: u64,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> FrameSlice<T> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Clone for FrameSlice<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for FrameSlice<T> {}
+
+impl<T> fmt::Debug for FrameSlice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FrameSlice({}@{})", self.len, self.epoch)
+    }
+}
+
+/// Two `FrameArena`s used on alternating frames, so whatever was stashed
+/// during one frame can still be read during the next through `previous`.
+pub struct DoubleArena {
+    arenas: [FrameArena; 2],
+    cur: usize,
+}
+
+impl DoubleArena {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            arenas: [FrameArena::new(capacity), FrameArena::new(capacity)],
+            cur: 0,
+        }
+    }
+
+    #[inline]
+    pub fn current(&self) -> &FrameArena {
+        &self.arenas[self.cur]
+    }
+
+    #[inline]
+    pub fn previous(&self) -> &FrameArena {
+        &self.arenas[self.cur ^ 1]
+    }
+
+    /// Start a new frame. The frame that just ended becomes `previous`, and
+    /// the one before it is reset.
+    #[inline]
+    pub fn flip(&mut self) {
+        self.cur ^= 1;
+        self.arenas[self.cur].reset();
+    }
+}
+
+/// Growable array in a `FrameArena`. Growing copies into a new allocation
+/// unless the array is at the top of the arena, and the old one is only
+/// reclaimed by `reset`.
+pub struct ArenaVec<'a, T> {
+    arena: &'a FrameArena,
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+}
+
+impl<'a, T> ArenaVec<'a, T> {
+    #[inline]
+    pub fn new_in(arena: &'a FrameArena) -> Self {
+        Self {
+            arena,
+            ptr: NonNull::dangling(),
+            len: 0,
+            cap: if mem::size_of::<T>() == 0 {
+                usize::MAX
+            } else {
+                0
+            },
+        }
+    }
+
+    #[inline]
+    pub fn with_capacity_in(cap: usize, arena: &'a FrameArena) -> Self {
+        let mut vec = Self::new_in(arena);
+        vec.reserve(cap);
+        vec
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn reserve(&mut self, extra: usize) {
+        let Some(needed) = self.len.checked_add(extra) else {
+            crate::fatal!("Arena vector capacity overflows");
+        };
+        if needed <= self.cap {
+            return;
+        }
+        let cap = needed.max(self.cap * 2).max(4);
+        let size = mem::size_of::<T>();
+        if self.cap > 0
+            && self
+                .arena
+                .try_grow_top(self.ptr.cast(), self.cap * size, (cap - self.cap) * size)
+        {
+            self.cap = cap;
+            return;
+        }
+        let ptr = self.arena.alloc_raw::<T>(cap);
+        unsafe { ptr::copy_nonoverlapping(self.ptr.as_ptr(), ptr.as_ptr(), self.len) };
+        self.ptr = ptr;
+        self.cap = cap;
+    }
+
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        if self.len == self.cap {
+            self.reserve(1);
+        }
+        unsafe { self.ptr.add(self.len).write(value) };
+        self.len += 1;
+    }
+
+    pub fn insert(&mut self, idx: usize, value: T) {
+        assert!(idx <= self.len);
+        if self.len == self.cap {
+            self.reserve(1);
+        }
+        unsafe {
+            let at = self.ptr.add(idx).as_ptr();
+            ptr::copy(at, at.add(1), self.len - idx);
+            at.write(value);
+        }
+        self.len += 1;
+    }
+
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { self.ptr.add(self.len).read() })
+    }
+
+    /// Forget the elements, keeping the capacity.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    #[inline]
+    pub fn into_slice(self) -> &'a mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<'a, T> Deref for ArenaVec<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<'a, T> DerefMut for ArenaVec<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<'a, T> Extend<T> for ArenaVec<'a, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(align(64))]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Line([u8; 64]);
+
+    fn is_aligned<T>(value: &T) -> bool {
+        (value as *const T).is_aligned()
+    }
+
+    #[test]
+    fn aligns_mixed_types() {
+        let arena = FrameArena::new(256);
+        let a = arena.alloc(1u8);
+        let b = arena.alloc(2u64);
+        let c = arena.alloc(3u16);
+        let d = arena.alloc(Line([4; 64]));
+        let e = arena.alloc_slice_fill(3, 5u32);
+        let f = arena.alloc(6u128);
+        let g = arena.alloc(());
+        assert!(is_aligned(b) && is_aligned(c) && is_aligned(d) && is_aligned(f) && is_aligned(g));
+        assert!(is_aligned(&e[0]));
+        assert_eq!(
+            (*a, *b, *c, *d, &*e, *f),
+            (1, 2, 3, Line([4; 64]), &[5; 3][..], 6)
+        );
+    }
+
+    #[test]
+    fn grows_past_the_first_chunk_and_merges_on_reset() {
+        let mut arena = FrameArena::new(64);
+        for frame in 0..3 {
+            let values: Vec<&mut [u64]> = (0..20)
+                .map(|i| arena.alloc_slice_fill(5, i as u64))
+                .collect();
+            for (i, value) in values.iter().enumerate() {
+                assert_eq!(**value, [i as u64; 5]);
+            }
+            let capacity = arena.capacity();
+            assert!(arena.used() >= 20 * 5 * 8);
+            if frame == 0 {
+                assert!(arena.full.borrow().len() > 1);
+            } else {
+                // the merged chunk holds a whole frame
+                assert!(arena.full.borrow().is_empty());
+            }
+            arena.reset();
+            assert_eq!(arena.capacity(), capacity);
+            assert_eq!(arena.used(), 0);
+        }
+    }
+
+    #[test]
+    fn arena_vec_grows_in_place_at_the_top() {
+        let arena = FrameArena::new(1024);
+        let mut vec = ArenaVec::with_capacity_in(4, &arena);
+        vec.extend(0..4u32);
+        let ptr = vec.as_ptr();
+        vec.push(4);
+        assert_eq!(vec.as_ptr(), ptr);
+        assert_eq!(vec.capacity(), 8);
+        assert_eq!(arena.used(), 8 * 4);
+        assert_eq!(*vec, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn arena_vec_copies_when_not_at_the_top() {
+        let arena = FrameArena::new(1024);
+        let mut vec = ArenaVec::with_capacity_in(4, &arena);
+        vec.extend(0..4u32);
+        let ptr = vec.as_ptr();
+        let other = arena.alloc(7u8);
+        vec.insert(0, 9);
+        assert_ne!(vec.as_ptr(), ptr);
+        assert_eq!(*vec, [9, 0, 1, 2, 3]);
+        assert_eq!(*other, 7);
+
+        // and when the top has no room left
+        let mut vec = ArenaVec::new_in(&arena);
+        for i in 0..300u32 {
+            vec.push(i);
+        }
+        assert!(!arena.full.borrow().is_empty());
+        assert_eq!(vec.pop(), Some(299));
+        assert!(vec.iter().copied().eq(0..299));
+    }
+
+    #[test]
+    fn stashed_slices_expire() {
+        let mut arena = FrameArena::new(64);
+        let other = FrameArena::new(64);
+        let slice = arena.stash(&[1, 2, 3]);
+        assert_eq!(arena.get(slice), Some(&[1, 2, 3][..]));
+        assert_eq!(other.get(slice), None);
+        arena.reset();
+        assert_eq!(arena.get(slice), None);
+
+        let mut double = DoubleArena::new(64);
+        let slice = double.current().stash(&[4, 5]);
+        double.flip();
+        assert_eq!(double.previous().get(slice), Some(&[4, 5][..]));
+        assert_eq!(double.current().get(slice), None);
+        double.flip();
+        assert_eq!(double.previous().get(slice), None);
+        assert_eq!(double.current().get(slice), None);
+    }
+}
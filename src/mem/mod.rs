@@ -1,39 +1,205 @@
-use std::{collections::VecDeque, ops::Range};
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::Range,
+};
 
-pub struct Handles<T> {
-    pub items: Vec<T>,
+mod arena;
+mod shared;
+mod slotmap;
+mod tlsf;
+
+pub use arena::*;
+pub use shared::*;
+pub use slotmap::*;
+pub use tlsf::*;
+
+/// Slot index plus the generation the slot had when it was handed out, so
+/// that stale handles and double frees can be detected. `T` only tags the
+/// kind of handle.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound = ""))]
+pub struct Handle<T> {
+    idx: u32,
+    generation: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    #[inline]
+    pub(crate) const fn new(idx: u32, generation: u32) -> Self {
+        Self {
+            idx,
+            generation,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub const fn idx(&self) -> u32 {
+        self.idx
+    }
+
+    #[inline]
+    pub const fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        (self.idx, self.generation) == (other.idx, other.generation)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> PartialOrd for Handle<T> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Handle<T> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.idx, self.generation).cmp(&(other.idx, other.generation))
+    }
+}
+
+impl<T> Hash for Handle<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.idx, self.generation).hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({}v{})", self.idx, self.generation)
+    }
+}
+
+/// Per-slot generations. A slot's generation is odd while it is in use, so
+/// handles to free slots never validate.
+#[derive(Default)]
+pub struct Generations {
+    gens: Vec<u32>,
+}
+
+impl Generations {
+    /// Mark `idx` as used and hand out a handle to it.
+    #[inline]
+    pub fn acquire<T>(&mut self, idx: usize) -> Handle<T> {
+        if idx >= self.gens.len() {
+            self.gens.resize(idx + 1, 0);
+        }
+        debug_assert!(self.gens[idx].is_multiple_of(2));
+        self.gens[idx] = self.gens[idx].wrapping_add(1);
+        Handle::new(idx as u32, self.gens[idx])
+    }
+
+    /// Mark the handle's slot as free. Returns `false` (and changes nothing)
+    /// if the handle is stale.
+    #[inline]
+    pub fn release<T>(&mut self, hnd: Handle<T>) -> bool {
+        if !self.is_valid(hnd) {
+            return false;
+        }
+        let generation = &mut self.gens[hnd.idx as usize];
+        *generation = generation.wrapping_add(1);
+        true
+    }
+
+    #[inline]
+    pub fn is_valid<T>(&self, hnd: Handle<T>) -> bool {
+        (hnd.generation % 2 == 1) && (self.gens.get(hnd.idx as usize) == Some(&hnd.generation))
+    }
+}
+
+/// Items addressed by generational handles. `K` tags the handles, and
+/// defaults to the item type.
+pub struct Handles<T, K = T> {
+    items: Vec<T>,
+    gens: Generations,
     free_list: Vec<usize>,
+    _marker: PhantomData<fn() -> K>,
 }
 
-impl<T> Handles<T> {
+impl<T, K> Handles<T, K> {
     #[inline]
     pub fn new() -> Self {
         Self {
             items: Vec::new(),
+            gens: Generations::default(),
             free_list: Vec::new(),
+            _marker: PhantomData,
         }
     }
 
     #[inline]
-    pub fn track(&mut self, item: T) -> usize {
+    pub fn track(&mut self, item: T) -> Handle<K> {
         if let Some(idx) = self.free_list.pop() {
             self.items[idx] = item;
-            idx
+            self.gens.acquire(idx)
         } else {
             let idx = self.items.len();
             self.items.push(item);
-            idx
+            self.gens.acquire(idx)
         }
     }
 
+    /// Returns `false` if the handle is stale (e.g. a double free).
     #[inline]
-    pub fn untrack(&mut self, idx: usize) {
-        self.free_list.push(idx);
+    pub fn untrack(&mut self, hnd: Handle<K>) -> bool {
+        if !self.gens.release(hnd) {
+            return false;
+        }
+        self.free_list.push(hnd.idx as usize);
+        true
+    }
+
+    #[inline]
+    pub fn is_valid(&self, hnd: Handle<K>) -> bool {
+        self.gens.is_valid(hnd)
+    }
+
+    #[inline]
+    pub fn get(&self, hnd: Handle<K>) -> Option<&T> {
+        if self.gens.is_valid(hnd) {
+            Some(&self.items[hnd.idx as usize])
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, hnd: Handle<K>) -> Option<&mut T> {
+        if self.gens.is_valid(hnd) {
+            Some(&mut self.items[hnd.idx as usize])
+        } else {
+            None
+        }
     }
 }
 
 pub struct HandlePool<T> {
-    pub items: Vec<T>,
+    items: Vec<T>,
+    gens: Generations,
     free_list: Vec<usize>,
 }
 
@@ -42,6 +208,7 @@ impl<T: Default> HandlePool<T> {
     pub fn new() -> Self {
         Self {
             items: Vec::new(),
+            gens: Generations::default(),
             free_list: Vec::new(),
         }
     }
@@ -61,143 +228,710 @@ impl<T: Default> HandlePool<T> {
     }
 
     #[inline]
-    pub fn track<I: Fn(&mut T)>(&mut self, init: I) -> usize
+    pub fn track<I: Fn(&mut T)>(&mut self, init: I) -> Handle<T>
     where
         T: Default,
     {
         let idx = self.find_free();
         init(&mut self.items[idx]);
-        idx
+        self.gens.acquire(idx)
+    }
+
+    /// Returns `false` if the handle is stale (e.g. a double free).
+    #[inline]
+    pub fn untrack(&mut self, hnd: Handle<T>) -> bool {
+        if !self.gens.release(hnd) {
+            return false;
+        }
+        self.free_list.push(hnd.idx as usize);
+        true
+    }
+
+    #[inline]
+    pub fn get(&self, hnd: Handle<T>) -> Option<&T> {
+        if self.gens.is_valid(hnd) {
+            Some(&self.items[hnd.idx as usize])
+        } else {
+            None
+        }
     }
 
     #[inline]
-    pub fn untrack(&mut self, idx: usize) {
-        self.free_list.push(idx);
+    pub fn get_mut(&mut self, hnd: Handle<T>) -> Option<&mut T> {
+        if self.gens.is_valid(hnd) {
+            Some(&mut self.items[hnd.idx as usize])
+        } else {
+            None
+        }
     }
 }
 
 #[derive(Default)]
 pub struct MetaAlloc {
     pub range: Range<usize>,
-    // TODO: generations for double free detection?
 }
 
+/// Buddy allocator over an abstract address range. Free blocks of each order
+/// are kept in intrusive lists threaded through per-unit links (a unit being
+/// the smallest block), so alloc and free are O(max_order) and buddy lookup
+/// is O(1).
 pub struct MetaAllocator {
     min_order: usize, // smallest allocation size: 2^(min_order)
     max_order: usize, // largest allocation size: 2^(max_order)
-    free_lists: Vec<VecDeque<usize>>,
+    heads: Vec<u32>,  // first free unit of each order
+    units: Vec<Unit>,
+    free_blocks: Vec<usize>,
+    used_blocks: Vec<usize>,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Block {
+    /// Inside a larger block.
+    None,
+    Free(u8),
+    Used(u8),
+}
+
+#[derive(Clone, Copy)]
+struct Unit {
+    block: Block,
+    prev: u32,
+    next: u32,
+}
+
+const NIL: u32 = u32::MAX;
+
 impl MetaAllocator {
     #[inline]
     pub fn new(size: usize, min_size: usize) -> Self {
         let size = size.next_power_of_two();
-        let min_size = min_size.next_power_of_two();
         let max_order = size.trailing_zeros() as usize;
-        let min_order = min_size.trailing_zeros() as usize;
-        let mut free_lists = vec![VecDeque::new(); max_order + 1];
-        free_lists[max_order].push_back(0);
-        Self {
+        let min_order = (min_size.next_power_of_two().trailing_zeros() as usize).min(max_order);
+        let unit_count = 1 << (max_order - min_order);
+        if unit_count > NIL as usize {
+            crate::fatal!("Allocator of {size} bytes has too many {min_size} byte units");
+        }
+        let mut alloc = Self {
             min_order,
             max_order,
-            free_lists,
+            heads: vec![NIL; max_order + 1],
+            units: vec![
+                Unit {
+                    block: Block::None,
+                    prev: NIL,
+                    next: NIL,
+                };
+                unit_count
+            ],
+            free_blocks: vec![0; max_order + 1],
+            used_blocks: vec![0; max_order + 1],
+        };
+        alloc.push_free(0, max_order);
+        alloc
+    }
+
+    #[inline]
+    fn order_of(&self, size: usize) -> usize {
+        (size.next_power_of_two().trailing_zeros() as usize).max(self.min_order)
+    }
+
+    #[inline]
+    fn unit_of(&self, offset: usize) -> usize {
+        offset >> self.min_order
+    }
+
+    fn push_free(&mut self, offset: usize, order: usize) {
+        let unit = self.unit_of(offset);
+        let head = self.heads[order];
+        self.units[unit] = Unit {
+            block: Block::Free(order as u8),
+            prev: NIL,
+            next: head,
+        };
+        if head != NIL {
+            self.units[head as usize].prev = unit as u32;
         }
+        self.heads[order] = unit as u32;
+        self.free_blocks[order] += 1;
+    }
+
+    fn remove_free(&mut self, unit: usize, order: usize) {
+        let Unit { prev, next, .. } = self.units[unit];
+        if prev == NIL {
+            self.heads[order] = next;
+        } else {
+            self.units[prev as usize].next = next;
+        }
+        if next != NIL {
+            self.units[next as usize].prev = prev;
+        }
+        self.units[unit].block = Block::None;
+        self.free_blocks[order] -= 1;
     }
 
     pub fn alloc(&mut self, size: usize) -> Option<MetaAlloc> {
-        let order = size
-            .max(self.min_order)
-            .next_power_of_two()
-            .trailing_zeros() as usize;
+        let order = self.order_of(size);
         if order > self.max_order {
             return None;
         }
-        let mut found_offset = None;
-        for cur_order in order..=self.max_order {
-            if let Some(offset) = self.free_lists[cur_order].pop_front() {
-                for split_order in (order..cur_order).rev() {
-                    let buddy = offset + (1 << split_order);
-                    self.free_lists[split_order].push_back(buddy);
-                }
-                found_offset = Some(offset);
-            }
-        }
-        if let Some(offset) = found_offset {
-            Some(MetaAlloc {
-                range: offset..(offset + (1 << order)),
-            })
-        } else {
-            None
+        let cur_order = (order..=self.max_order).find(|&o| self.heads[o] != NIL)?;
+        let unit = self.heads[cur_order] as usize;
+        self.remove_free(unit, cur_order);
+        let offset = unit << self.min_order;
+        for split_order in (order..cur_order).rev() {
+            self.push_free(offset + (1 << split_order), split_order);
         }
+        self.units[unit].block = Block::Used(order as u8);
+        self.used_blocks[order] += 1;
+        Some(MetaAlloc {
+            range: offset..(offset + (1 << order)),
+        })
     }
 
+    /// Ranges that were not handed out by this allocator (or were already
+    /// freed) are logged and ignored.
     pub fn free(&mut self, alloc: MetaAlloc) {
-        let order = alloc
-            .range
-            .len()
-            .max(self.min_order)
-            .next_power_of_two()
-            .trailing_zeros() as usize;
+        let order = self.order_of(alloc.range.len());
         let mut cur_offset = alloc.range.start;
+        let unit = self.unit_of(cur_offset);
+        // blocks are handed out whole and aligned to their size
+        let whole = alloc.range.len() == 1 << order && cur_offset & ((1 << order) - 1) == 0;
+        if !whole || self.units.get(unit).map(|u| u.block) != Some(Block::Used(order as u8)) {
+            log::warn!("Ignoring free of unallocated range {:?}", alloc.range);
+            return;
+        }
+        self.units[unit].block = Block::None;
+        self.used_blocks[order] -= 1;
         let mut cur_order = order;
         while cur_order < self.max_order {
             let buddy = cur_offset ^ (1 << cur_order);
-            if let Some(pos) = self.free_lists[cur_order]
-                .iter()
-                .position(|&off| off == buddy)
-            {
-                self.free_lists[cur_order].remove(pos);
-                cur_offset = cur_offset.min(buddy);
-                cur_order += 1;
-            } else {
+            let buddy_unit = self.unit_of(buddy);
+            if self.units[buddy_unit].block != Block::Free(cur_order as u8) {
                 break;
             }
+            self.remove_free(buddy_unit, cur_order);
+            cur_offset = cur_offset.min(buddy);
+            cur_order += 1;
         }
-        self.free_lists[cur_order].push_back(cur_offset);
+        self.push_free(cur_offset, cur_order);
     }
+
+    pub fn stats(&self) -> MetaAllocStats {
+        let block_bytes = |counts: &[usize]| -> usize {
+            counts
+                .iter()
+                .enumerate()
+                .map(|(order, &count)| count << order)
+                .sum()
+        };
+        let free = block_bytes(&self.free_blocks);
+        let largest_free = (0..=self.max_order)
+            .rev()
+            .find(|&o| self.heads[o] != NIL)
+            .map_or(0, |o| 1 << o);
+        MetaAllocStats {
+            size: 1 << self.max_order,
+            used: block_bytes(&self.used_blocks),
+            free,
+            largest_free,
+            fragmentation: if free == 0 {
+                0.0
+            } else {
+                1.0 - (largest_free as f32 / free as f32)
+            },
+            free_blocks: self.free_blocks.clone(),
+            used_blocks: self.used_blocks.clone(),
+        }
+    }
+}
+
+/// Snapshot of a `MetaAllocator` or `TlsfAllocator`. Sizes are in the
+/// allocator's units (bytes for GPU buffers), and include rounding.
+#[derive(Clone, Debug, Default)]
+pub struct MetaAllocStats {
+    pub size: usize,
+    pub used: usize,
+    pub free: usize,
+    pub largest_free: usize,
+    /// `1 - largest_free / free`: `0.0` when all free space is contiguous,
+    /// approaching `1.0` as it splinters.
+    pub fragmentation: f32,
+    /// Free block counts, indexed by order (block sizes from `2^order` up
+    /// to, but excluding, `2^(order + 1)`).
+    pub free_blocks: Vec<usize>,
+    /// Allocated block counts, indexed by order.
+    pub used_blocks: Vec<usize>,
 }
 
+/// Bit set with a summary hierarchy on top: each level has one bit per word
+/// of the level below, set while that word is full. Searches for zero bits
+/// skip full regions 64 words at a time, so they take O(log n).
 pub struct BitMap {
-    words: Vec<u64>,
-    free_list: Vec<usize>,
+    len: usize,
+    ones: usize,
+    levels: Vec<Vec<u64>>, // bits first, then summaries up to a single word
 }
 
 impl BitMap {
     const BITS: usize = u64::BITS as usize;
 
-    #[inline]
-    pub fn new(size: usize) -> Self {
-        let num_words = (size + (Self::BITS - 1)) / Self::BITS;
+    pub fn new(len: usize) -> Self {
+        let mut levels = Vec::new();
+        let mut count = len;
+        loop {
+            let num_words = count.div_ceil(Self::BITS).max(1);
+            let mut words = vec![0; num_words];
+            // padding past the end is permanently set, so it is never found
+            for idx in count..(num_words * Self::BITS) {
+                words[idx / Self::BITS] |= 1 << (idx % Self::BITS);
+            }
+            levels.push(words);
+            if num_words == 1 {
+                break;
+            }
+            count = num_words;
+        }
         Self {
-            words: vec![0; num_words],
-            free_list: Vec::new(),
+            len,
+            ones: 0,
+            levels,
+        }
+    }
+
+    /// Extend to `len` bits. Set bits stay set, the new ones are unset.
+    pub fn grow(&mut self, len: usize) {
+        debug_assert!(len >= self.len);
+        let mut grown = Self::new(len);
+        for idx in self.iter_ones() {
+            grown.set(idx);
+        }
+        *self = grown;
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn count_ones(&self) -> usize {
+        self.ones
+    }
+
+    #[inline]
+    pub fn is_set(&self, idx: usize) -> bool {
+        debug_assert!(idx < self.len);
+        (self.levels[0][idx / Self::BITS] & (1 << (idx % Self::BITS))) != 0
+    }
+
+    /// Returns `false` if the bit was already set.
+    #[inline]
+    pub fn set(&mut self, idx: usize) -> bool {
+        debug_assert!(idx < self.len);
+        let bit = 1 << (idx % Self::BITS);
+        let old = self.set_bits(0, idx / Self::BITS, bit);
+        let changed = (old & bit) == 0;
+        self.ones += changed as usize;
+        changed
+    }
+
+    /// Returns `false` if the bit was already unset, which usually means a
+    /// double free. Bits past the end are logged and left alone.
+    #[inline]
+    pub fn unset(&mut self, idx: usize) -> bool {
+        if idx >= self.len {
+            log::warn!("Ignoring unset of bit {idx} in a bit map of {}", self.len);
+            return false;
         }
+        let bit = 1 << (idx % Self::BITS);
+        let old = self.clear_bits(0, idx / Self::BITS, bit);
+        let changed = (old & bit) != 0;
+        self.ones -= changed as usize;
+        changed
     }
 
+    /// Set the lowest unset bit.
+    #[inline]
     pub fn set_any(&mut self) -> Option<usize> {
-        if let Some(idx) = self.free_list.pop() {
-            let word_idx = idx / Self::BITS;
-            let bit_idx = idx % Self::BITS;
-            self.words[word_idx] |= 1 << bit_idx;
-            Some(idx)
-        } else {
-            for (word_idx, word) in self.words.iter_mut().enumerate() {
-                if *word == u64::MAX {
-                    continue;
+        let idx = self.next_zero(0, 0)?;
+        self.set(idx);
+        Some(idx)
+    }
+
+    /// Set the lowest run of `count` unset bits.
+    #[inline]
+    pub fn set_any_range(&mut self, count: usize) -> Option<Range<usize>> {
+        let start = self.find_zeros(count)?;
+        self.set_range(start..(start + count));
+        Some(start..(start + count))
+    }
+
+    pub fn set_range(&mut self, range: Range<usize>) {
+        debug_assert!(range.end <= self.len);
+        for (word_idx, mask) in Self::word_masks(range) {
+            let old = self.set_bits(0, word_idx, mask);
+            self.ones += (mask & !old).count_ones() as usize;
+        }
+    }
+
+    /// Ranges reaching past the end are logged and ignored, like `unset`.
+    pub fn clear_range(&mut self, range: Range<usize>) {
+        if range.end > self.len {
+            log::warn!(
+                "Ignoring clear of bits {range:?} in a bit map of {}",
+                self.len
+            );
+            return;
+        }
+        for (word_idx, mask) in Self::word_masks(range) {
+            let old = self.clear_bits(0, word_idx, mask);
+            self.ones -= (mask & old).count_ones() as usize;
+        }
+    }
+
+    /// Lowest unset bit at or after `from`.
+    #[inline]
+    pub fn find_zero(&self, from: usize) -> Option<usize> {
+        self.next_zero(0, from)
+    }
+
+    /// Start of the lowest run of `count` unset bits.
+    pub fn find_zeros(&self, count: usize) -> Option<usize> {
+        let mut start = self.next_zero(0, 0)?;
+        loop {
+            let end = start.checked_add(count)?;
+            if end > self.len {
+                return None;
+            }
+            match self.next_one(start..end) {
+                None => return Some(start),
+                Some(one) => start = self.next_zero(0, one)?,
+            }
+        }
+    }
+
+    /// Length of the longest run of unset bits. O(ones).
+    pub fn longest_zero_run(&self) -> usize {
+        let mut longest = 0;
+        let mut start = 0;
+        for idx in self.iter_ones() {
+            longest = longest.max(idx - start);
+            start = idx + 1;
+        }
+        longest.max(self.len - start)
+    }
+
+    /// Indices of the set bits, in order.
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        let len = self.len;
+        self.levels[0]
+            .iter()
+            .enumerate()
+            .flat_map(|(word_idx, &word)| {
+                let mut word = word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit_idx = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    Some((word_idx * Self::BITS) + bit_idx)
+                })
+            })
+            .take_while(move |&idx| idx < len)
+    }
+
+    /// Words and masks covering `range`.
+    fn word_masks(range: Range<usize>) -> impl Iterator<Item = (usize, u64)> {
+        let first = range.start / Self::BITS;
+        let last = range.end.div_ceil(Self::BITS);
+        (first..last).filter_map(move |word_idx| {
+            let lo = range.start.max(word_idx * Self::BITS) - (word_idx * Self::BITS);
+            let hi = range.end.min((word_idx + 1) * Self::BITS) - (word_idx * Self::BITS);
+            if lo >= hi {
+                return None;
+            }
+            let mask = (u64::MAX >> (Self::BITS - (hi - lo))) << lo;
+            Some((word_idx, mask))
+        })
+    }
+
+    /// Returns the old word, marking it full in the level above if needed.
+    fn set_bits(&mut self, level: usize, word_idx: usize, mask: u64) -> u64 {
+        let word = &mut self.levels[level][word_idx];
+        let old = *word;
+        *word |= mask;
+        if (old != u64::MAX) && (*word == u64::MAX) && (level + 1 < self.levels.len()) {
+            self.set_bits(
+                level + 1,
+                word_idx / Self::BITS,
+                1 << (word_idx % Self::BITS),
+            );
+        }
+        old
+    }
+
+    /// Returns the old word, marking it not full in the level above if needed.
+    fn clear_bits(&mut self, level: usize, word_idx: usize, mask: u64) -> u64 {
+        let word = &mut self.levels[level][word_idx];
+        let old = *word;
+        *word &= !mask;
+        if (old == u64::MAX) && (*word != u64::MAX) && (level + 1 < self.levels.len()) {
+            self.clear_bits(
+                level + 1,
+                word_idx / Self::BITS,
+                1 << (word_idx % Self::BITS),
+            );
+        }
+        old
+    }
+
+    fn next_zero(&self, level: usize, from: usize) -> Option<usize> {
+        let words = &self.levels[level];
+        let word_idx = from / Self::BITS;
+        let word = *words.get(word_idx)?;
+        let zeros = !word & (u64::MAX << (from % Self::BITS));
+        if zeros != 0 {
+            return Some((word_idx * Self::BITS) + zeros.trailing_zeros() as usize);
+        }
+        if level + 1 == self.levels.len() {
+            // the top level is a single word
+            return None;
+        }
+        let next = self.next_zero(level + 1, word_idx + 1)?;
+        Some((next * Self::BITS) + words[next].trailing_ones() as usize)
+    }
+
+    fn next_one(&self, range: Range<usize>) -> Option<usize> {
+        Self::word_masks(range).find_map(|(word_idx, mask)| {
+            let ones = self.levels[0][word_idx] & mask;
+            (ones != 0).then(|| (word_idx * Self::BITS) + ones.trailing_zeros() as usize)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Rng;
+
+    fn range(range: &Range<usize>) -> MetaAlloc {
+        MetaAlloc {
+            range: range.clone(),
+        }
+    }
+
+    /// Checks the live ranges against each other and the allocator's stats.
+    fn check_meta(alloc: &MetaAllocator, live: &[Range<usize>]) {
+        let stats = alloc.stats();
+        let mut sorted = live.to_vec();
+        sorted.sort_by_key(|r| r.start);
+        for pair in sorted.windows(2) {
+            assert!(pair[0].end <= pair[1].start, "{pair:?} overlap");
+        }
+        assert!(sorted.last().is_none_or(|r| r.end <= stats.size));
+        assert_eq!(stats.used, live.iter().map(|r| r.len()).sum::<usize>());
+        assert_eq!(stats.used + stats.free, stats.size);
+        assert_eq!(stats.used_blocks.iter().sum::<usize>(), live.len());
+    }
+
+    #[test]
+    fn meta_allocator_matches_model() {
+        let mut rng = Rng::new(17);
+        let mut alloc = MetaAllocator::new(1 << 16, 64);
+        let mut live: Vec<Range<usize>> = Vec::new();
+        for _ in 0..20_000 {
+            if rng.next_bool() && !live.is_empty() {
+                let idx = rng.range_u32(0..live.len() as u32) as usize;
+                alloc.free(range(&live.swap_remove(idx)));
+            } else {
+                // mostly small requests, with the odd one that cannot fit
+                let max = if rng.range_u32(0..16) == 0 {
+                    1 << 17
+                } else {
+                    1 << 12
+                };
+                let size = rng.range_u32(1..max) as usize;
+                let rounded = size.next_power_of_two().max(64);
+                let largest_free = alloc.stats().largest_free;
+                match alloc.alloc(size) {
+                    Some(MetaAlloc { range }) => {
+                        assert_eq!(range.len(), rounded);
+                        assert_eq!(range.start % rounded, 0);
+                        live.push(range);
+                    }
+                    None => assert!(largest_free < rounded),
                 }
-                let bit_idx = word.trailing_ones() as usize;
-                *word |= 1 << bit_idx;
-                return Some((word_idx * Self::BITS) + bit_idx);
             }
-            None
+            check_meta(&alloc, &live);
         }
+        for r in live.drain(..) {
+            alloc.free(range(&r));
+        }
+        let stats = alloc.stats();
+        assert_eq!(stats.largest_free, 1 << 16);
+        assert_eq!(stats.free_blocks.iter().sum::<usize>(), 1);
+        assert_eq!(stats.free_blocks[16], 1);
     }
 
-    #[inline]
-    pub fn unset(&mut self, idx: usize) {
-        let word_idx = idx / Self::BITS;
-        let bit_idx = idx % Self::BITS;
-        self.words[word_idx] &= !(1 << bit_idx);
-        self.free_list.push(idx);
+    #[test]
+    fn meta_allocator_ignores_double_and_foreign_frees() {
+        let mut alloc = MetaAllocator::new(1024, 64);
+        let a = alloc.alloc(100).unwrap().range;
+        let b = alloc.alloc(64).unwrap().range;
+        assert_eq!(a, 0..128);
+        assert_eq!(b, 128..192);
+        let before = alloc.stats();
+
+        let foreign = [
+            // never handed out
+            256..320,
+            // part of, or not aligned with, a used block
+            64..128,
+            0..64,
+            1..129,
+            0..100,
+            // the parent of a used block
+            0..256,
+            // past the end
+            2048..2112,
+        ];
+        for r in &foreign {
+            alloc.free(range(r));
+            let stats = alloc.stats();
+            assert_eq!(stats.used_blocks, before.used_blocks, "{r:?}");
+            assert_eq!(stats.free_blocks, before.free_blocks, "{r:?}");
+        }
+
+        alloc.free(range(&a));
+        let once = alloc.stats();
+        alloc.free(range(&a));
+        let twice = alloc.stats();
+        assert_eq!(once.used_blocks, twice.used_blocks);
+        assert_eq!(once.free_blocks, twice.free_blocks);
+        check_meta(&alloc, std::slice::from_ref(&b));
+
+        alloc.free(range(&b));
+        assert_eq!(alloc.stats().free_blocks[10], 1);
+        assert_eq!(alloc.alloc(1024).unwrap().range, 0..1024);
+    }
+
+    /// Every summary bit is set exactly when the word below it is full.
+    fn check_summaries(bits: &BitMap) {
+        for pair in bits.levels.windows(2) {
+            let [below, above] = pair else { unreachable!() };
+            assert_eq!(above.len(), below.len().div_ceil(64));
+            for (idx, &word) in below.iter().enumerate() {
+                let summary = above[idx / 64] & (1 << (idx % 64)) != 0;
+                assert_eq!(summary, word == u64::MAX, "word {idx}");
+            }
+        }
+        assert_eq!(bits.levels.last().unwrap().len(), 1);
+    }
+
+    fn check_bits(bits: &BitMap, model: &[bool]) {
+        assert_eq!(bits.len(), model.len());
+        assert_eq!(bits.count_ones(), model.iter().filter(|&&b| b).count());
+        assert!(bits.iter_ones().eq((0..model.len()).filter(|&i| model[i])));
+        let longest = model.split(|&b| b).map(|run| run.len()).max().unwrap_or(0);
+        assert_eq!(bits.longest_zero_run(), longest);
+        check_summaries(bits);
+    }
+
+    fn find_zeros(model: &[bool], count: usize) -> Option<usize> {
+        let mut run = 0;
+        for (i, &b) in model.iter().enumerate() {
+            run = if b { 0 } else { run + 1 };
+            if run == count {
+                return Some(i + 1 - count);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn bit_map_matches_model() {
+        let mut rng = Rng::new(19);
+        for len in [1, 63, 64, 65, 200, 64 * 64, 64 * 64 + 1, 70_000] {
+            let mut bits = BitMap::new(len);
+            let mut model = vec![false; len];
+            check_bits(&bits, &model);
+            for step in 0..600 {
+                let len = model.len();
+                let idx = rng.range_u32(0..len as u32) as usize;
+                // ranges are mostly short, sometimes long enough to fill
+                // whole summary words
+                let max_count = if rng.next_bool() { 130 } else { len / 3 + 1 };
+                let count = rng.range_u32(1..max_count as u32 + 1) as usize;
+                let range = idx..(idx + count).min(len);
+                match rng.range_u32(0..9) {
+                    0 => assert_eq!(bits.set(idx), !std::mem::replace(&mut model[idx], true)),
+                    1 => assert_eq!(bits.unset(idx), std::mem::replace(&mut model[idx], false)),
+                    2 => {
+                        bits.set_range(range.clone());
+                        model[range].fill(true);
+                    }
+                    3 => {
+                        bits.clear_range(range.clone());
+                        model[range].fill(false);
+                    }
+                    4 => {
+                        let expected = model.iter().position(|&b| !b);
+                        assert_eq!(bits.set_any(), expected);
+                        if let Some(i) = expected {
+                            model[i] = true;
+                        }
+                    }
+                    5 => {
+                        let expected = find_zeros(&model, count);
+                        assert_eq!(bits.find_zeros(count), expected);
+                        let range = bits.set_any_range(count);
+                        assert_eq!(range, expected.map(|i| i..i + count));
+                        if let Some(range) = range {
+                            model[range].fill(true);
+                        }
+                    }
+                    6 => {
+                        let from = rng.range_u32(0..len as u32 + 70) as usize;
+                        let expected = (from..len).find(|&i| !model[i]);
+                        assert_eq!(bits.find_zero(from), expected, "from {from}");
+                    }
+                    7 => {
+                        for i in [idx, range.end.saturating_sub(1)] {
+                            assert_eq!(bits.is_set(i), model[i]);
+                        }
+                    }
+                    _ if step % 100 == 99 => {
+                        let grown = len + rng.range_u32(0..200) as usize;
+                        bits.grow(grown);
+                        model.resize(grown, false);
+                    }
+                    _ => {}
+                }
+                if step % 20 == 0 {
+                    check_bits(&bits, &model);
+                }
+            }
+            check_bits(&bits, &model);
+        }
+    }
+
+    #[test]
+    fn bit_map_keeps_padding_set() {
+        let mut bits = BitMap::new(70);
+        bits.set_range(0..70);
+        assert!(!bits.unset(70));
+        assert!(!bits.unset(127));
+        bits.clear_range(60..128);
+        assert_eq!(bits.count_ones(), 70);
+        assert_eq!(bits.find_zero(0), None);
+        assert_eq!(bits.find_zeros(1), None);
+        assert_eq!(bits.longest_zero_run(), 0);
+        check_summaries(&bits);
     }
 }
@@ -0,0 +1,250 @@
+use std::{
+    marker::PhantomData,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+};
+
+use super::Handle;
+
+const NIL: u32 = u32::MAX;
+
+/// Generational handles that any thread can reserve and release through a
+/// shared reference, without locking. Freed slots go on an atomic free list
+/// whose head carries a tag, so a slot popped and pushed back while another
+/// thread is mid-pop can't corrupt it. Follows the same odd-while-live
+/// generation scheme as `Generations`.
+pub struct SharedHandles<T> {
+    slots: Box<[Slot]>,
+    head: AtomicU64, // tag in the high half, first free slot in the low half
+    next: AtomicU32, // first slot that was never handed out
+    _marker: PhantomData<fn() -> T>,
+}
+
+struct Slot {
+    generation: AtomicU32,
+    next_free: AtomicU32,
+}
+
+impl<T> SharedHandles<T> {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.min(NIL as usize);
+        Self {
+            slots: (0..capacity)
+                .map(|_| Slot {
+                    generation: AtomicU32::new(0),
+                    next_free: AtomicU32::new(NIL),
+                })
+                .collect(),
+            head: AtomicU64::new(NIL as u64),
+            next: AtomicU32::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// `None` if every slot is in use.
+    pub fn reserve(&self) -> Option<Handle<T>> {
+        let mut head = self.head.load(Ordering::Acquire);
+        while head as u32 != NIL {
+            let idx = head as u32;
+            // may be stale if the slot was popped meanwhile, but then the tag
+            // has moved on and the exchange fails
+            let next = self.slots[idx as usize].next_free.load(Ordering::Relaxed);
+            let tag = (head >> 32).wrapping_add(1);
+            match self.head.compare_exchange_weak(
+                head,
+                (tag << 32) | (next as u64),
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(self.activate(idx)),
+                Err(actual) => head = actual,
+            }
+        }
+        let capacity = self.slots.len() as u32;
+        let idx = self
+            .next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+                (next < capacity).then_some(next + 1)
+            })
+            .ok()?;
+        Some(self.activate(idx))
+    }
+
+    #[inline]
+    fn activate(&self, idx: u32) -> Handle<T> {
+        let generation = &self.slots[idx as usize].generation;
+        let prev = generation.fetch_add(1, Ordering::AcqRel);
+        debug_assert!(prev.is_multiple_of(2));
+        Handle::new(idx, prev.wrapping_add(1))
+    }
+
+    /// Returns `false` (and changes nothing) if the handle is stale, so of
+    /// two threads releasing the same handle only one succeeds.
+    pub fn release(&self, hnd: Handle<T>) -> bool {
+        let Some(slot) = self.slots.get(hnd.idx() as usize) else {
+            return false;
+        };
+        if hnd.generation().is_multiple_of(2)
+            || slot
+                .generation
+                .compare_exchange(
+                    hnd.generation(),
+                    hnd.generation().wrapping_add(1),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return false;
+        }
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            slot.next_free.store(head as u32, Ordering::Relaxed);
+            let tag = (head >> 32).wrapping_add(1);
+            match self.head.compare_exchange_weak(
+                head,
+                (tag << 32) | (hnd.idx() as u64),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_valid(&self, hnd: Handle<T>) -> bool {
+        (hnd.generation() % 2 == 1)
+            && self
+                .slots
+                .get(hnd.idx() as usize)
+                .is_some_and(|slot| slot.generation.load(Ordering::Acquire) == hnd.generation())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{
+            Barrier, Mutex,
+            atomic::{AtomicBool, AtomicUsize},
+        },
+        thread,
+    };
+
+    use super::*;
+    use crate::math::Rng;
+
+    const THREADS: usize = 8;
+
+    #[test]
+    fn churn_never_hands_out_a_slot_twice() {
+        let pool = SharedHandles::<()>::new(64);
+        let in_use: Vec<AtomicBool> = (0..64).map(|_| AtomicBool::new(false)).collect();
+        let handed_out = Mutex::new(Vec::new());
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let (pool, in_use, handed_out) = (&pool, &in_use, &handed_out);
+                s.spawn(move || {
+                    let mut rng = Rng::new(t as u64);
+                    let mut held = Vec::new();
+                    let mut seen = Vec::new();
+                    for _ in 0..20_000 {
+                        if held.len() < 8 && rng.next_bool() {
+                            if let Some(hnd) = pool.reserve() {
+                                assert!(!in_use[hnd.idx() as usize].swap(true, Ordering::AcqRel));
+                                assert!(pool.is_valid(hnd));
+                                held.push(hnd);
+                                seen.push(hnd);
+                            }
+                        } else if !held.is_empty() {
+                            let hnd =
+                                held.swap_remove(rng.range_u32(0..held.len() as u32) as usize);
+                            in_use[hnd.idx() as usize].store(false, Ordering::Release);
+                            assert!(pool.release(hnd));
+                            assert!(!pool.release(hnd));
+                        }
+                    }
+                    for hnd in held {
+                        in_use[hnd.idx() as usize].store(false, Ordering::Release);
+                        assert!(pool.release(hnd));
+                    }
+                    handed_out.lock().unwrap().extend(seen);
+                });
+            }
+        });
+        // every reservation got a fresh generation
+        let handed_out = handed_out.into_inner().unwrap();
+        let unique: HashSet<_> = handed_out.iter().copied().collect();
+        assert_eq!(unique.len(), handed_out.len());
+        assert!(handed_out.iter().all(|&hnd| !pool.is_valid(hnd)));
+    }
+
+    #[test]
+    fn racing_releases_have_one_winner() {
+        let pool = SharedHandles::<()>::new(THREADS);
+        let barrier = Barrier::new(THREADS);
+        let handles = Mutex::new([None; THREADS / 2]);
+        let wins: Vec<AtomicUsize> = (0..THREADS / 2).map(|_| AtomicUsize::new(0)).collect();
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let (pool, barrier, handles, wins) = (&pool, &barrier, &handles, &wins);
+                s.spawn(move || {
+                    let pair = t / 2;
+                    for _ in 0..2000 {
+                        if t % 2 == 0 {
+                            handles.lock().unwrap()[pair] = pool.reserve();
+                        }
+                        barrier.wait();
+                        let hnd = handles.lock().unwrap()[pair].unwrap();
+                        if pool.release(hnd) {
+                            wins[pair].fetch_add(1, Ordering::Relaxed);
+                        }
+                        barrier.wait();
+                        if t % 2 == 0 {
+                            assert_eq!(wins[pair].swap(0, Ordering::Relaxed), 1);
+                        }
+                        barrier.wait();
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn exhausted_pool_returns_none() {
+        let pool = SharedHandles::<()>::new(100);
+        let reserved = Mutex::new(Vec::new());
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                let (pool, reserved) = (&pool, &reserved);
+                s.spawn(move || {
+                    let mut mine = Vec::new();
+                    while let Some(hnd) = pool.reserve() {
+                        mine.push(hnd);
+                    }
+                    reserved.lock().unwrap().extend(mine);
+                });
+            }
+        });
+        let reserved = reserved.into_inner().unwrap();
+        let slots: HashSet<_> = reserved.iter().map(|hnd| hnd.idx()).collect();
+        assert_eq!(slots.len(), 100);
+        assert_eq!(reserved.len(), 100);
+        assert!(pool.reserve().is_none());
+
+        // a released slot comes back with a newer generation
+        let hnd = reserved[17];
+        assert!(pool.release(hnd));
+        let again = pool.reserve().unwrap();
+        assert_eq!(again.idx(), hnd.idx());
+        assert_eq!(again.generation(), hnd.generation() + 2);
+        assert!(pool.reserve().is_none());
+    }
+}
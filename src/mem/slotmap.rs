@@ -0,0 +1,389 @@
+use std::{fmt, mem};
+
+use super::{Generations, Handle};
+
+/// Values addressed by generational `Handle<K>`s, packed densely so that
+/// iteration only touches live entries. Removal swaps the last value into
+/// the hole, so iteration order is not insertion order.
+pub struct SlotMap<K, V> {
+    values: Vec<V>,
+    keys: Vec<Handle<K>>, // key of each value
+    slots: Vec<u32>,      // value index of each live slot
+    gens: Generations,
+    free_list: Vec<u32>,
+}
+
+impl<K, V> SlotMap<K, V> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            values: Vec::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            gens: Generations::default(),
+            free_list: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn insert(&mut self, value: V) -> Handle<K> {
+        let slot = match self.free_list.pop() {
+            Some(slot) => slot as usize,
+            None => {
+                self.slots.push(0);
+                self.slots.len() - 1
+            }
+        };
+        let key = self.gens.acquire(slot);
+        self.slots[slot] = self.values.len() as u32;
+        self.values.push(value);
+        self.keys.push(key);
+        key
+    }
+
+    /// `None` if the key is stale.
+    pub fn remove(&mut self, key: Handle<K>) -> Option<V> {
+        if !self.gens.release(key) {
+            return None;
+        }
+        let idx = self.slots[key.idx() as usize] as usize;
+        self.free_list.push(key.idx());
+        self.keys.swap_remove(idx);
+        let value = self.values.swap_remove(idx);
+        if let Some(moved) = self.keys.get(idx) {
+            self.slots[moved.idx() as usize] = idx as u32;
+        }
+        Some(value)
+    }
+
+    #[inline]
+    pub fn contains_key(&self, key: Handle<K>) -> bool {
+        self.gens.is_valid(key)
+    }
+
+    #[inline]
+    pub fn get(&self, key: Handle<K>) -> Option<&V> {
+        if !self.gens.is_valid(key) {
+            return None;
+        }
+        Some(&self.values[self.slots[key.idx() as usize] as usize])
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, key: Handle<K>) -> Option<&mut V> {
+        if !self.gens.is_valid(key) {
+            return None;
+        }
+        Some(&mut self.values[self.slots[key.idx() as usize] as usize])
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Handle<K>, &V)> {
+        self.keys.iter().copied().zip(self.values.iter())
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = (Handle<K>, &mut V)> {
+        self.keys.iter().copied().zip(self.values.iter_mut())
+    }
+
+    #[inline]
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = Handle<K>> + '_ {
+        self.keys.iter().copied()
+    }
+
+    /// The live values, packed. Indices are not stable across removals.
+    #[inline]
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    #[inline]
+    pub fn values_mut(&mut self) -> &mut [V] {
+        &mut self.values
+    }
+
+    /// Remove every entry. All keys are invalidated up front, even if the
+    /// iterator is not consumed.
+    pub fn drain(&mut self) -> impl Iterator<Item = (Handle<K>, V)> + '_ {
+        for &key in &self.keys {
+            self.gens.release(key);
+            self.free_list.push(key.idx());
+        }
+        self.keys.drain(..).zip(self.values.drain(..))
+    }
+
+    /// Remove the entries `keep` returns `false` for.
+    pub fn retain<F: FnMut(Handle<K>, &mut V) -> bool>(&mut self, mut keep: F) {
+        let mut idx = 0;
+        while idx < self.values.len() {
+            if keep(self.keys[idx], &mut self.values[idx]) {
+                idx += 1;
+            } else {
+                // the last entry moves into `idx`, so look at it next
+                self.remove(self.keys[idx]);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.drain().for_each(drop);
+    }
+}
+
+impl<K, V> Default for SlotMap<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V: fmt::Debug> fmt::Debug for SlotMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Values for handles issued elsewhere, e.g. by a `SharedHandles`. Packed
+/// like `SlotMap`, but a key is only valid here once a value was inserted
+/// for it, and until it is removed or replaced by a newer generation.
+pub struct SecondaryMap<K, V> {
+    values: Vec<V>,
+    keys: Vec<Handle<K>>, // key of each value
+    slots: Vec<u32>,      // value index of each occupied slot, or `NIL`
+}
+
+const NIL: u32 = u32::MAX;
+
+impl<K, V> SecondaryMap<K, V> {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            values: Vec::new(),
+            keys: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    #[inline]
+    fn index_of(&self, key: Handle<K>) -> Option<usize> {
+        let idx = *self.slots.get(key.idx() as usize)?;
+        (idx != NIL && self.keys[idx as usize] == key).then_some(idx as usize)
+    }
+
+    /// Returns the value replaced, if the key already had one. A value for
+    /// an older generation of the slot is dropped.
+    pub fn insert(&mut self, key: Handle<K>, value: V) -> Option<V> {
+        let slot = key.idx() as usize;
+        if slot >= self.slots.len() {
+            self.slots.resize(slot + 1, NIL);
+        }
+        let idx = self.slots[slot];
+        if idx == NIL {
+            self.slots[slot] = self.values.len() as u32;
+            self.values.push(value);
+            self.keys.push(key);
+            return None;
+        }
+        let old = mem::replace(&mut self.values[idx as usize], value);
+        let old_key = mem::replace(&mut self.keys[idx as usize], key);
+        (old_key == key).then_some(old)
+    }
+
+    /// `None` if the key has no value.
+    pub fn remove(&mut self, key: Handle<K>) -> Option<V> {
+        let idx = self.index_of(key)?;
+        self.slots[key.idx() as usize] = NIL;
+        self.keys.swap_remove(idx);
+        let value = self.values.swap_remove(idx);
+        if let Some(moved) = self.keys.get(idx) {
+            self.slots[moved.idx() as usize] = idx as u32;
+        }
+        Some(value)
+    }
+
+    #[inline]
+    pub fn contains_key(&self, key: Handle<K>) -> bool {
+        self.index_of(key).is_some()
+    }
+
+    #[inline]
+    pub fn get(&self, key: Handle<K>) -> Option<&V> {
+        Some(&self.values[self.index_of(key)?])
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, key: Handle<K>) -> Option<&mut V> {
+        let idx = self.index_of(key)?;
+        Some(&mut self.values[idx])
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Handle<K>, &V)> {
+        self.keys.iter().copied().zip(self.values.iter())
+    }
+
+    #[inline]
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = Handle<K>> + '_ {
+        self.keys.iter().copied()
+    }
+
+    /// The values, packed. Indices are not stable across removals.
+    #[inline]
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+}
+
+impl<K, V> Default for SecondaryMap<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V: fmt::Debug> fmt::Debug for SecondaryMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Rng;
+
+    #[test]
+    fn stale_keys_miss_after_remove_and_reinsert() {
+        let mut map = SlotMap::<(), &str>::new();
+        let a = map.insert("a");
+        assert_eq!(map.remove(a), Some("a"));
+        assert_eq!(map.get(a), None);
+        assert_eq!(map.remove(a), None);
+        let b = map.insert("b");
+        // same slot, newer generation
+        assert_eq!(b.idx(), a.idx());
+        assert!(!map.contains_key(a));
+        assert_eq!(map.get_mut(a), None);
+        assert_eq!(map.get(b), Some(&"b"));
+    }
+
+    #[test]
+    fn removal_reindexes_the_moved_value() {
+        let mut map = SlotMap::<(), u32>::new();
+        let keys: Vec<_> = (0..4).map(|i| map.insert(i)).collect();
+        map.remove(keys[0]);
+        // the last value moved into the hole
+        assert_eq!(map.values(), [3, 1, 2]);
+        assert_eq!(map.get(keys[3]), Some(&3));
+        *map.get_mut(keys[3]).unwrap() += 10;
+        assert_eq!(map.remove(keys[3]), Some(13));
+        assert_eq!(map.values(), [2, 1]);
+        assert_eq!(map.get(keys[2]), Some(&2));
+        for (key, value) in map.iter() {
+            assert_eq!(map.get(key), Some(value));
+        }
+    }
+
+    #[test]
+    fn retain_and_drain_invalidate_keys() {
+        let mut map = SlotMap::<(), u32>::new();
+        let keys: Vec<_> = (0..10).map(|i| map.insert(i)).collect();
+        map.retain(|_, value| *value % 3 != 0);
+        assert_eq!(map.len(), 6);
+        for (i, &key) in keys.iter().enumerate() {
+            let expected = (i % 3 != 0).then_some(i as u32);
+            assert_eq!(map.get(key).copied(), expected);
+        }
+
+        // keys go stale even if the drain is not consumed
+        drop(map.drain());
+        assert!(map.is_empty());
+        let fresh: Vec<_> = (0..10).map(|i| map.insert(i + 100)).collect();
+        for (i, (&old, &new)) in keys.iter().zip(&fresh).enumerate() {
+            assert_eq!(map.get(old), None);
+            assert_eq!(map.get(new), Some(&(i as u32 + 100)));
+        }
+    }
+
+    #[test]
+    fn slot_map_matches_model() {
+        let mut rng = Rng::new(21);
+        let mut map = SlotMap::<(), u32>::new();
+        let mut live: Vec<(Handle<()>, u32)> = Vec::new();
+        let mut stale = Vec::new();
+        for value in 0..5000 {
+            if rng.range_u32(0..3) == 0 && !live.is_empty() {
+                let (key, expected) =
+                    live.swap_remove(rng.range_u32(0..live.len() as u32) as usize);
+                assert_eq!(map.remove(key), Some(expected));
+                stale.push(key);
+            } else {
+                live.push((map.insert(value), value));
+            }
+            assert_eq!(map.len(), live.len());
+        }
+        for &(key, value) in &live {
+            assert_eq!(map.get(key), Some(&value));
+        }
+        for &key in &stale {
+            assert_eq!(map.get(key), None);
+        }
+    }
+
+    #[test]
+    fn secondary_map_follows_generations() {
+        let mut primary = SlotMap::<(), ()>::new();
+        let mut map = SecondaryMap::<(), &str>::new();
+        let a = primary.insert(());
+        let b = primary.insert(());
+        let c = primary.insert(());
+        assert_eq!(map.get(a), None);
+        map.insert(a, "a");
+        map.insert(b, "b");
+        map.insert(c, "c");
+        assert_eq!(map.insert(b, "b2"), Some("b"));
+
+        // removing `a` moves `c` into its place
+        assert_eq!(map.remove(a), Some("a"));
+        assert_eq!(map.remove(a), None);
+        assert_eq!(map.values(), ["c", "b2"]);
+        assert_eq!(map.get(c), Some(&"c"));
+        assert_eq!(map.get(b), Some(&"b2"));
+
+        // a newer generation of `b`'s slot replaces its value
+        primary.remove(b);
+        let d = primary.insert(());
+        assert_eq!(d.idx(), b.idx());
+        assert_eq!(map.insert(d, "d"), None);
+        assert_eq!(map.get(b), None);
+        assert!(!map.contains_key(b));
+        assert_eq!(map.get(d), Some(&"d"));
+        assert_eq!(map.len(), 2);
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+use super::{MetaAlloc, MetaAllocStats};
+
+const SL_BITS: u32 = 4;
+const SL_COUNT: usize = 1 << SL_BITS;
+const NIL: u32 = u32::MAX;
+
+/// Two-level segregated fit allocator over an abstract address range.
+/// Allocations are rounded up to `granularity` only, so odd sizes waste far
+/// less than with `MetaAllocator`, at the cost of some external
+/// fragmentation. Alloc and free are O(1), apart from the used block lookup.
+pub struct TlsfAllocator {
+    size: usize,
+    granularity: usize,
+    fl_bitmap: u64,
+    sl_bitmaps: Vec<u16>,
+    heads: Vec<u32>, // first free node of each bin
+    nodes: Vec<Node>,
+    spare_nodes: Vec<u32>,
+    used: HashMap<usize, u32>, // offset to node
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    offset: usize,
+    size: usize,
+    free: bool,
+    prev_phys: u32,
+    next_phys: u32,
+    prev_free: u32,
+    next_free: u32,
+}
+
+impl TlsfAllocator {
+    pub fn new(size: usize, granularity: usize) -> Self {
+        let granularity = granularity.next_power_of_two();
+        let size = size - (size % granularity);
+        let bins = (bin_of(size / granularity).0 + 1) * SL_COUNT;
+        let mut alloc = Self {
+            size,
+            granularity,
+            fl_bitmap: 0,
+            sl_bitmaps: vec![0; bins / SL_COUNT],
+            heads: vec![NIL; bins],
+            nodes: Vec::new(),
+            spare_nodes: Vec::new(),
+            used: HashMap::new(),
+        };
+        if size > 0 {
+            let node = alloc.new_node(0, size);
+            alloc.insert_free(node);
+        }
+        alloc
+    }
+
+    fn new_node(&mut self, offset: usize, size: usize) -> u32 {
+        let node = Node {
+            offset,
+            size,
+            free: false,
+            prev_phys: NIL,
+            next_phys: NIL,
+            prev_free: NIL,
+            next_free: NIL,
+        };
+        if let Some(idx) = self.spare_nodes.pop() {
+            self.nodes[idx as usize] = node;
+            idx
+        } else {
+            self.nodes.push(node);
+            (self.nodes.len() - 1) as u32
+        }
+    }
+
+    #[inline]
+    fn bin_index(&self, size: usize) -> usize {
+        let (fl, sl) = bin_of(size / self.granularity);
+        (fl * SL_COUNT) + sl
+    }
+
+    fn insert_free(&mut self, idx: u32) {
+        let bin = self.bin_index(self.nodes[idx as usize].size);
+        let head = self.heads[bin];
+        let node = &mut self.nodes[idx as usize];
+        node.free = true;
+        node.prev_free = NIL;
+        node.next_free = head;
+        if head != NIL {
+            self.nodes[head as usize].prev_free = idx;
+        }
+        self.heads[bin] = idx;
+        self.fl_bitmap |= 1 << (bin / SL_COUNT);
+        self.sl_bitmaps[bin / SL_COUNT] |= 1 << (bin % SL_COUNT);
+    }
+
+    fn remove_free(&mut self, idx: u32) {
+        let bin = self.bin_index(self.nodes[idx as usize].size);
+        let Node {
+            prev_free,
+            next_free,
+            ..
+        } = self.nodes[idx as usize];
+        if prev_free == NIL {
+            self.heads[bin] = next_free;
+        } else {
+            self.nodes[prev_free as usize].next_free = next_free;
+        }
+        if next_free != NIL {
+            self.nodes[next_free as usize].prev_free = prev_free;
+        }
+        self.nodes[idx as usize].free = false;
+        if self.heads[bin] == NIL {
+            let fl = bin / SL_COUNT;
+            self.sl_bitmaps[fl] &= !(1 << (bin % SL_COUNT));
+            if self.sl_bitmaps[fl] == 0 {
+                self.fl_bitmap &= !(1 << fl);
+            }
+        }
+    }
+
+    /// First non-empty bin at or above `bin`.
+    fn find_bin(&self, bin: usize) -> Option<usize> {
+        let fl = bin / SL_COUNT;
+        if fl >= self.sl_bitmaps.len() {
+            return None;
+        }
+        let sl_map = self.sl_bitmaps[fl] & (u16::MAX << (bin % SL_COUNT));
+        if sl_map != 0 {
+            return Some((fl * SL_COUNT) + sl_map.trailing_zeros() as usize);
+        }
+        let fl_map = self.fl_bitmap & u64::MAX.checked_shl(fl as u32 + 1).unwrap_or(0);
+        if fl_map == 0 {
+            return None;
+        }
+        let fl = fl_map.trailing_zeros() as usize;
+        Some((fl * SL_COUNT) + self.sl_bitmaps[fl].trailing_zeros() as usize)
+    }
+
+    pub fn alloc(&mut self, size: usize) -> Option<MetaAlloc> {
+        let size = size.max(1).checked_next_multiple_of(self.granularity)?;
+        if size > self.size {
+            return None;
+        }
+        // rounding the request up to the next bin means any block found is
+        // large enough, failing that the request's own bin may still hold one
+        // that fits
+        let units = size / self.granularity;
+        let (fl, _) = bin_of(units);
+        let rounded = units + (1 << fl.saturating_sub(1)) - 1;
+        let idx = if let Some(bin) = self.find_bin(self.bin_index(rounded * self.granularity)) {
+            self.heads[bin]
+        } else {
+            let mut idx = self.heads[self.bin_index(size)];
+            while idx != NIL && self.nodes[idx as usize].size < size {
+                idx = self.nodes[idx as usize].next_free;
+            }
+            if idx == NIL {
+                return None;
+            }
+            idx
+        };
+        self.remove_free(idx);
+
+        let node = self.nodes[idx as usize];
+        if node.size > size {
+            let rest = self.new_node(node.offset + size, node.size - size);
+            self.nodes[rest as usize].prev_phys = idx;
+            self.nodes[rest as usize].next_phys = node.next_phys;
+            if node.next_phys != NIL {
+                self.nodes[node.next_phys as usize].prev_phys = rest;
+            }
+            self.nodes[idx as usize].next_phys = rest;
+            self.nodes[idx as usize].size = size;
+            self.insert_free(rest);
+        }
+        self.used.insert(node.offset, idx);
+        Some(MetaAlloc {
+            range: node.offset..(node.offset + size),
+        })
+    }
+
+    /// Ranges that were not handed out by this allocator (or were already
+    /// freed) are logged and ignored.
+    pub fn free(&mut self, alloc: MetaAlloc) {
+        let idx = match self.used.get(&alloc.range.start) {
+            Some(&idx) if self.nodes[idx as usize].size == alloc.range.len() => idx,
+            _ => {
+                log::warn!("Ignoring free of unallocated range {:?}", alloc.range);
+                return;
+            }
+        };
+        self.used.remove(&alloc.range.start);
+
+        let mut idx = idx;
+        let prev = self.nodes[idx as usize].prev_phys;
+        if prev != NIL && self.nodes[prev as usize].free {
+            self.remove_free(prev);
+            self.merge_next(prev);
+            idx = prev;
+        }
+        let next = self.nodes[idx as usize].next_phys;
+        if next != NIL && self.nodes[next as usize].free {
+            self.remove_free(next);
+            self.merge_next(idx);
+        }
+        self.insert_free(idx);
+    }
+
+    /// Absorb the node after `idx`, which must not be in a free list.
+    fn merge_next(&mut self, idx: u32) {
+        let next = self.nodes[idx as usize].next_phys;
+        let Node {
+            size, next_phys, ..
+        } = self.nodes[next as usize];
+        self.nodes[idx as usize].size += size;
+        self.nodes[idx as usize].next_phys = next_phys;
+        if next_phys != NIL {
+            self.nodes[next_phys as usize].prev_phys = idx;
+        }
+        self.spare_nodes.push(next);
+    }
+
+    /// O(blocks), unlike `MetaAllocator::stats`.
+    pub fn stats(&self) -> MetaAllocStats {
+        let orders = (usize::BITS - self.size.leading_zeros()) as usize;
+        let mut stats = MetaAllocStats {
+            size: self.size,
+            free_blocks: vec![0; orders],
+            used_blocks: vec![0; orders],
+            ..Default::default()
+        };
+        for &idx in self.used.values() {
+            let size = self.nodes[idx as usize].size;
+            stats.used += size;
+            stats.used_blocks[size.ilog2() as usize] += 1;
+        }
+        for &head in &self.heads {
+            let mut idx = head;
+            while idx != NIL {
+                let Node {
+                    size, next_free, ..
+                } = self.nodes[idx as usize];
+                stats.free += size;
+                stats.largest_free = stats.largest_free.max(size);
+                stats.free_blocks[size.ilog2() as usize] += 1;
+                idx = next_free;
+            }
+        }
+        if stats.free > 0 {
+            stats.fragmentation = 1.0 - (stats.largest_free as f32 / stats.free as f32);
+        }
+        stats
+    }
+}
+
+/// First and second level bins of a size in units. Sizes below `SL_COUNT`
+/// map linearly into the first bin row.
+#[inline]
+fn bin_of(units: usize) -> (usize, usize) {
+    if units < SL_COUNT {
+        return (0, units);
+    }
+    let log2 = units.ilog2();
+    let fl = (log2 - SL_BITS + 1) as usize;
+    let sl = (units >> (log2 - SL_BITS)) - SL_COUNT;
+    (fl, sl)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::*;
+    use crate::math::Rng;
+
+    fn range(range: &Range<usize>) -> MetaAlloc {
+        MetaAlloc {
+            range: range.clone(),
+        }
+    }
+
+    /// Checks the live ranges against each other and the allocator's stats.
+    /// Free blocks are merged eagerly, so there is one per gap between live
+    /// ranges.
+    fn check(alloc: &TlsfAllocator, live: &[Range<usize>]) {
+        let stats = alloc.stats();
+        let mut sorted = live.to_vec();
+        sorted.sort_by_key(|r| r.start);
+        let mut gaps = 0;
+        let mut end = 0;
+        for r in &sorted {
+            assert!(end <= r.start, "{r:?} overlaps");
+            gaps += (end < r.start) as usize;
+            end = r.end;
+        }
+        assert!(end <= stats.size);
+        gaps += (end < stats.size) as usize;
+        assert_eq!(stats.used, live.iter().map(|r| r.len()).sum::<usize>());
+        assert_eq!(stats.used + stats.free, stats.size);
+        assert_eq!(stats.used_blocks.iter().sum::<usize>(), live.len());
+        assert_eq!(stats.free_blocks.iter().sum::<usize>(), gaps);
+    }
+
+    #[test]
+    fn matches_model() {
+        let mut rng = Rng::new(18);
+        let mut alloc = TlsfAllocator::new(100_000, 16);
+        let mut live: Vec<Range<usize>> = Vec::new();
+        for _ in 0..20_000 {
+            if rng.next_bool() && !live.is_empty() {
+                let idx = rng.range_u32(0..live.len() as u32) as usize;
+                alloc.free(range(&live.swap_remove(idx)));
+            } else {
+                let max = if rng.range_u32(0..16) == 0 {
+                    50_000
+                } else {
+                    4000
+                };
+                let size = rng.range_u32(0..max) as usize;
+                let rounded = size.max(1).next_multiple_of(16);
+                let largest_free = alloc.stats().largest_free;
+                match alloc.alloc(size) {
+                    Some(MetaAlloc { range }) => {
+                        assert_eq!(range.len(), rounded);
+                        assert_eq!(range.start % 16, 0);
+                        live.push(range);
+                    }
+                    None => assert!(largest_free < rounded),
+                }
+            }
+            check(&alloc, &live);
+        }
+        for r in live.drain(..) {
+            alloc.free(range(&r));
+        }
+        check(&alloc, &live);
+        assert_eq!(alloc.stats().largest_free, 100_000);
+    }
+
+    #[test]
+    fn rounds_requests_to_the_next_bin_and_scans_its_own() {
+        let mut alloc = TlsfAllocator::new(100, 1);
+        let a = alloc.alloc(35).unwrap().range;
+        let b = alloc.alloc(1).unwrap().range;
+        let c = alloc.alloc(64).unwrap().range;
+        assert_eq!((a.clone(), b.clone(), c.clone()), (0..35, 35..36, 36..100));
+        alloc.free(range(&a));
+        check(&alloc, &[b.clone(), c.clone()]);
+
+        // 35 is not the start of its bin (34 and 35), so the search starts at
+        // the next bin, which is empty, and falls back to scanning
+        assert!(alloc.alloc(36).is_none());
+        let a = alloc.alloc(35).unwrap().range;
+        assert_eq!(a, 0..35);
+
+        // the scan rejects a 34 block in the same bin
+        alloc.free(range(&a));
+        let d = alloc.alloc(34).unwrap().range;
+        assert_eq!(alloc.stats().largest_free, 1);
+        alloc.free(range(&d));
+        let d = alloc.alloc(1).unwrap().range;
+        assert_eq!(d, 0..1);
+        assert_eq!(alloc.stats().largest_free, 34);
+        assert!(alloc.alloc(35).is_none());
+        check(&alloc, &[b, c, d]);
+    }
+
+    #[test]
+    fn splits_and_merges_neighbors() {
+        let mut alloc = TlsfAllocator::new(64 * 10, 64);
+        let blocks: Vec<_> = (0..10).map(|_| alloc.alloc(64).unwrap().range).collect();
+        assert!(alloc.alloc(1).is_none());
+        check(&alloc, &blocks);
+
+        // free every other block, then the ones between, merging both ways
+        for r in blocks.iter().step_by(2) {
+            alloc.free(range(r));
+        }
+        assert_eq!(alloc.stats().free_blocks.iter().sum::<usize>(), 5);
+        for r in blocks.iter().skip(1).step_by(2) {
+            alloc.free(range(r));
+        }
+        check(&alloc, &[]);
+        assert_eq!(alloc.stats().largest_free, 640);
+
+        // the whole range is split again from the front
+        let a = alloc.alloc(100).unwrap().range;
+        assert_eq!(a, 0..128);
+        assert_eq!(alloc.stats().largest_free, 512);
+    }
+
+    #[test]
+    fn ignores_double_and_foreign_frees() {
+        let mut alloc = TlsfAllocator::new(1024, 16);
+        let a = alloc.alloc(100).unwrap().range;
+        let b = alloc.alloc(16).unwrap().range;
+        // a used start with the wrong length, inside a block, out of range
+        for r in [0..16, 1..113, 112..113, 2048..2064] {
+            alloc.free(range(&r));
+            check(&alloc, &[a.clone(), b.clone()]);
+        }
+        alloc.free(range(&a));
+        alloc.free(range(&a));
+        check(&alloc, std::slice::from_ref(&b));
+        alloc.free(range(&b));
+        check(&alloc, &[]);
+    }
+}
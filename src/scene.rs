@@ -1,12 +1,15 @@
 use std::collections::VecDeque;
 
 use crate::{
+    codec::{Decode, DecodeError, Encode, Reader, Writer, struct_codec_impl},
     gfx::Drawable,
     math::{Concat, Xform3},
 };
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scene {
     nodes: Vec<Node>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     nodeq: VecDeque<(u32, Xform3)>,
 }
 
@@ -65,7 +68,26 @@ impl Scene {
     }
 }
 
+/// Only the nodes are persisted, world transforms are refreshed by `update`.
+impl Encode for Scene {
+    #[inline]
+    fn encode(&self, w: &mut Writer) {
+        self.nodes.encode(w);
+    }
+}
+
+impl Decode for Scene {
+    #[inline]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Scene {
+            nodes: Decode::decode(r)?,
+            nodeq: VecDeque::new(),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub sib: u32,
     pub kid: u32,
@@ -83,3 +105,11 @@ impl Node {
         self.kid != Self::INACTIVE
     }
 }
+
+struct_codec_impl!(Node {
+    sib,
+    kid,
+    local,
+    world,
+    draw
+});